@@ -0,0 +1,462 @@
+#[cfg(feature = "gamepad")]
+use gilrs::{Axis, Button};
+use glutin::VirtualKeyCode;
+use input::KeyType;
+use std::fmt;
+use std::fs;
+
+// What a key or pad input can do, joypad keys and fast forward last as long as they're held,
+// everything else happens once when pressed
+#[derive(Copy, Clone, PartialEq)]
+pub enum Action {
+    Joypad(KeyType),
+    FastForward,
+    Pause,
+    Reset,
+    SaveState(u8),
+    LoadState(u8),
+    Screenshot,
+    RecordMovie,
+    RecordAudio,
+    RecordVideo,
+    NextPalette,
+    // channels are numbered from 0 here, and from 1 in the bindings
+    MuteChannel(usize),
+    SoloChannel(usize),
+    Visualizer,
+    Quit,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Binding {
+    Key {
+        code: VirtualKeyCode,
+        ctrl: bool,
+        shift: bool,
+    },
+    #[cfg(feature = "gamepad")]
+    PadButton(Button),
+    // an axis pushed past the threshold in the positive or negative direction
+    #[cfg(feature = "gamepad")]
+    PadAxis(Axis, bool),
+}
+
+pub struct Bindings {
+    bindings: Vec<(Action, Binding)>,
+}
+
+const STATE_SLOTS: u8 = 9;
+
+macro_rules! names {
+    ($list:ident, $type:ident, [$($name:ident),*]) => {
+        const $list: &[(&str, $type)] = &[$((stringify!($name), $type::$name)),*];
+    };
+}
+
+names!(
+    KEYS,
+    VirtualKeyCode,
+    [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Key1, Key2, Key3, Key4, Key5,
+        Key6, Key7, Key8, Key9, Key0, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, Up, Down, Left, Right, Space,
+        Return, Back, Tab, Escape, Insert, Delete, Home, End, PageUp, PageDown, LShift, RShift, LControl, RControl,
+        LAlt, RAlt, Comma, Period, Slash, Backslash, Semicolon, Apostrophe, LBracket, RBracket, Minus, Equals, Grave,
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9
+    ]
+);
+
+#[cfg(feature = "gamepad")]
+names!(
+    BUTTONS,
+    Button,
+    [
+        South,
+        East,
+        North,
+        West,
+        C,
+        Z,
+        LeftTrigger,
+        LeftTrigger2,
+        RightTrigger,
+        RightTrigger2,
+        Select,
+        Start,
+        Mode,
+        LeftThumb,
+        RightThumb,
+        DPadUp,
+        DPadDown,
+        DPadLeft,
+        DPadRight
+    ]
+);
+
+#[cfg(feature = "gamepad")]
+names!(
+    AXES,
+    Axis,
+    [
+        LeftStickX,
+        LeftStickY,
+        LeftZ,
+        RightStickX,
+        RightStickY,
+        RightZ,
+        DPadX,
+        DPadY
+    ]
+);
+
+// The keys rustyboy has always used, F1-F4 load a state and shift saves it
+const DEFAULT_BINDINGS: &str = "
+up = Up
+down = Down
+left = Left
+right = Right
+a = Z
+b = X
+select = C
+start = V
+fast_forward = Space
+pause = P
+reset = Ctrl+R
+screenshot = F12
+record_movie = Ctrl+M
+record_audio = Ctrl+A
+record_video = Ctrl+V
+next_palette = F9
+mute_channel_1 = Key1
+mute_channel_2 = Key2
+mute_channel_3 = Key3
+mute_channel_4 = Key4
+solo_channel_1 = Shift+Key1
+solo_channel_2 = Shift+Key2
+solo_channel_3 = Shift+Key3
+solo_channel_4 = Shift+Key4
+visualizer = Tab
+quit = Ctrl+Q
+save_state_1 = Shift+F1
+save_state_2 = Shift+F2
+save_state_3 = Shift+F3
+save_state_4 = Shift+F4
+load_state_1 = F1
+load_state_2 = F2
+load_state_3 = F3
+load_state_4 = F4
+";
+
+// A Nintendo layout, A on the right and B on the bottom
+#[cfg(feature = "gamepad")]
+const DEFAULT_PAD_BINDINGS: &str = "
+up = pad:DPadUp, pad:LeftStickY+
+down = pad:DPadDown, pad:LeftStickY-
+left = pad:DPadLeft, pad:LeftStickX-
+right = pad:DPadRight, pad:LeftStickX+
+a = pad:East
+b = pad:South
+select = pad:Select
+start = pad:Start
+fast_forward = pad:RightTrigger2
+pause = pad:Mode
+";
+
+impl Action {
+    pub fn is_held(self) -> bool {
+        matches!(self, Action::Joypad(_) | Action::FastForward)
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        if let Some(key_type) = KeyType::from_name(name) {
+            return Some(Action::Joypad(key_type));
+        }
+
+        let numbered = |prefix: &str, count: u8| {
+            name.strip_prefix(prefix)
+                .and_then(|number| number.parse::<u8>().ok())
+                .filter(|number| (1..=count).contains(number))
+        };
+        if let Some(slot) = numbered("save_state_", STATE_SLOTS) {
+            return Some(Action::SaveState(slot));
+        }
+        if let Some(slot) = numbered("load_state_", STATE_SLOTS) {
+            return Some(Action::LoadState(slot));
+        }
+        if let Some(channel) = numbered("mute_channel_", 4) {
+            return Some(Action::MuteChannel(usize::from(channel - 1)));
+        }
+        if let Some(channel) = numbered("solo_channel_", 4) {
+            return Some(Action::SoloChannel(usize::from(channel - 1)));
+        }
+
+        match name {
+            "fast_forward" => Some(Action::FastForward),
+            "pause" => Some(Action::Pause),
+            "reset" => Some(Action::Reset),
+            "screenshot" => Some(Action::Screenshot),
+            "record_movie" => Some(Action::RecordMovie),
+            "record_audio" => Some(Action::RecordAudio),
+            "record_video" => Some(Action::RecordVideo),
+            "next_palette" => Some(Action::NextPalette),
+            "visualizer" => Some(Action::Visualizer),
+            "quit" => Some(Action::Quit),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Action::Joypad(key_type) => write!(f, "{}", key_type.to_string().to_lowercase()),
+            Action::FastForward => write!(f, "fast_forward"),
+            Action::Pause => write!(f, "pause"),
+            Action::Reset => write!(f, "reset"),
+            Action::SaveState(slot) => write!(f, "save_state_{}", slot),
+            Action::LoadState(slot) => write!(f, "load_state_{}", slot),
+            Action::Screenshot => write!(f, "screenshot"),
+            Action::RecordMovie => write!(f, "record_movie"),
+            Action::RecordAudio => write!(f, "record_audio"),
+            Action::RecordVideo => write!(f, "record_video"),
+            Action::NextPalette => write!(f, "next_palette"),
+            Action::MuteChannel(channel) => write!(f, "mute_channel_{}", channel + 1),
+            Action::SoloChannel(channel) => write!(f, "solo_channel_{}", channel + 1),
+            Action::Visualizer => write!(f, "visualizer"),
+            Action::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+impl Binding {
+    // Keys like Ctrl+Shift+F1, pad buttons and axes like pad:South or pad:LeftStickX+
+    fn from_name(name: &str) -> Option<Self> {
+        if let Some(pad_name) = name.strip_prefix("pad:") {
+            return Self::pad_from_name(pad_name);
+        }
+
+        let mut ctrl = false;
+        let mut shift = false;
+        let mut parts: Vec<&str> = name.split('+').collect();
+        let key = parts.pop()?;
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" => ctrl = true,
+                "shift" => shift = true,
+                _ => return None,
+            }
+        }
+
+        find_by_name(KEYS, key).map(|code| Binding::Key { code, ctrl, shift })
+    }
+
+    #[cfg(feature = "gamepad")]
+    fn pad_from_name(name: &str) -> Option<Self> {
+        if let Some(axis) = name.strip_suffix('+') {
+            return find_by_name(AXES, axis).map(|axis| Binding::PadAxis(axis, true));
+        }
+        if let Some(axis) = name.strip_suffix('-') {
+            return find_by_name(AXES, axis).map(|axis| Binding::PadAxis(axis, false));
+        }
+        find_by_name(BUTTONS, name).map(Binding::PadButton)
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    fn pad_from_name(_name: &str) -> Option<Self> {
+        None
+    }
+
+    fn modifier_count(self) -> u8 {
+        match self {
+            Binding::Key { ctrl, shift, .. } => ctrl as u8 + shift as u8,
+            #[cfg(feature = "gamepad")]
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Binding::Key { code, ctrl, shift } => {
+                if ctrl {
+                    write!(f, "Ctrl+")?;
+                }
+                if shift {
+                    write!(f, "Shift+")?;
+                }
+                write!(f, "{}", find_name(KEYS, code))
+            }
+            #[cfg(feature = "gamepad")]
+            Binding::PadButton(button) => write!(f, "pad:{}", find_name(BUTTONS, button)),
+            #[cfg(feature = "gamepad")]
+            Binding::PadAxis(axis, positive) => {
+                write!(f, "pad:{}{}", find_name(AXES, axis), if positive { "+" } else { "-" })
+            }
+        }
+    }
+}
+
+impl Bindings {
+    pub fn defaults() -> Self {
+        let mut bindings = Self { bindings: vec![] };
+        bindings.apply(DEFAULT_BINDINGS, "default bindings");
+        #[cfg(feature = "gamepad")]
+        bindings.apply(DEFAULT_PAD_BINDINGS, "default pad bindings");
+        bindings
+    }
+
+    // Lines of `action = binding, binding`, an action listed here replaces its default bindings
+    // and listing it with nothing after the = unbinds it
+    pub fn load(path: &str) -> Self {
+        let config = match fs::read_to_string(path) {
+            Ok(config) => config,
+            Err(e) => panic!("Failed to read bindings from {}: {}", path, e),
+        };
+
+        let mut bindings = Self::defaults();
+        let mut replaced: Vec<Action> = vec![];
+        for (action, binding) in parse(&config, path) {
+            if !replaced.contains(&action) {
+                bindings.bindings.retain(|&(bound_action, _)| bound_action != action);
+                replaced.push(action);
+            }
+            if let Some(binding) = binding {
+                bindings.bindings.push((action, binding));
+            }
+        }
+        bindings
+    }
+
+    fn apply(&mut self, config: &str, source: &str) {
+        for (action, binding) in parse(config, source) {
+            if let Some(binding) = binding {
+                self.bindings.push((action, binding));
+            }
+        }
+    }
+
+    // Printed in the config format, so it can be used as a starting point
+    pub fn print(&self) {
+        let mut actions: Vec<Action> = vec![];
+        for &(action, _) in &self.bindings {
+            if !actions.contains(&action) {
+                actions.push(action);
+            }
+        }
+
+        for action in actions {
+            let bindings: Vec<String> = self
+                .bindings
+                .iter()
+                .filter(|&&(bound_action, _)| bound_action == action)
+                .map(|&(_, binding)| binding.to_string())
+                .collect();
+            println!("{} = {}", action, bindings.join(", "));
+        }
+    }
+
+    // With Shift+F1 and F1 both bound, holding shift only triggers the first
+    pub fn key_pressed(&self, pressed: VirtualKeyCode, ctrl_down: bool, shift_down: bool) -> Vec<Action> {
+        let matching: Vec<(Action, Binding)> = self
+            .bindings
+            .iter()
+            .cloned()
+            .filter(|&(_, binding)| match binding {
+                Binding::Key { code, ctrl, shift } => code == pressed && (!ctrl || ctrl_down) && (!shift || shift_down),
+                #[cfg(feature = "gamepad")]
+                _ => false,
+            })
+            .collect();
+
+        let most_specific = matching
+            .iter()
+            .map(|&(_, binding)| binding.modifier_count())
+            .max()
+            .unwrap_or(0);
+        matching
+            .into_iter()
+            .filter(|&(_, binding)| binding.modifier_count() == most_specific)
+            .map(|(action, _)| action)
+            .collect()
+    }
+
+    // Modifiers may have changed since the key went down, so anything held on the key is let go
+    pub fn key_released(&self, released: VirtualKeyCode) -> Vec<Action> {
+        self.actions_where(|binding| match binding {
+            Binding::Key { code, .. } => code == released,
+            #[cfg(feature = "gamepad")]
+            _ => false,
+        })
+        .into_iter()
+        .filter(|action| action.is_held())
+        .collect()
+    }
+
+    #[cfg(feature = "gamepad")]
+    pub fn button(&self, button: Button) -> Vec<Action> {
+        self.actions_where(|binding| binding == Binding::PadButton(button))
+    }
+
+    #[cfg(feature = "gamepad")]
+    pub fn axis(&self, axis: Axis, positive: bool) -> Vec<Action> {
+        self.actions_where(|binding| binding == Binding::PadAxis(axis, positive))
+    }
+
+    fn actions_where<F: Fn(Binding) -> bool>(&self, matches: F) -> Vec<Action> {
+        self.bindings
+            .iter()
+            .filter(|&&(_, binding)| matches(binding))
+            .map(|&(action, _)| action)
+            .collect()
+    }
+}
+
+// Each line's action with each of its bindings, or None for a line that unbinds the action
+fn parse(config: &str, source: &str) -> Vec<(Action, Option<Binding>)> {
+    let mut result = vec![];
+    for (line_number, line) in config.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (action_name, binding_names) = match line.find('=') {
+            Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+            None => panic!(
+                "{}:{}: expected `action = binding`, got {}",
+                source,
+                line_number + 1,
+                line
+            ),
+        };
+        let action = match Action::from_name(action_name) {
+            Some(action) => action,
+            None => panic!("{}:{}: unknown action {}", source, line_number + 1, action_name),
+        };
+
+        if binding_names.is_empty() {
+            result.push((action, None));
+            continue;
+        }
+        for binding_name in binding_names.split(',').map(str::trim) {
+            match Binding::from_name(binding_name) {
+                Some(binding) => result.push((action, Some(binding))),
+                None => panic!("{}:{}: unknown binding {}", source, line_number + 1, binding_name),
+            }
+        }
+    }
+    result
+}
+
+fn find_by_name<T: Copy>(list: &[(&str, T)], name: &str) -> Option<T> {
+    list.iter()
+        .find(|&&(item_name, _)| item_name.eq_ignore_ascii_case(name))
+        .map(|&(_, item)| item)
+}
+
+fn find_name<T: PartialEq>(list: &[(&'static str, T)], item: T) -> &'static str {
+    list.iter()
+        .find(|(_, list_item)| *list_item == item)
+        .map(|&(name, _)| name)
+        .unwrap_or("Unknown")
+}
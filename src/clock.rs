@@ -1,84 +1,152 @@
-use cpu::CPU;
+use state::{StateReader, StateResult, StateWriter};
+
+// https://gbdev.io/pandocs/Timer_Obscure_Behaviour.html
+// DIV is the top byte of a 16 bit counter running at the clock speed. TIMA and the APU's frame
+// sequencer are clocked whenever the bit of the counter they're connected to falls from 1 to 0.
+const APU_BIT: u16 = 0x1000; // bit 4 of DIV, 512 Hz
 
 pub struct Clock {
-    divider: u8,
-    divider_increment_timer: u32,
+    system_counter: u16,
     counter: u8,
-    counter_increment_timer: u32,
     modulo: u8,
     control: u8,
+    counter_state: CounterState,
     pub interrupt: u8,
+    pub frame_sequencer_ticks: u8,
 }
 
-impl Clock {
-    const DIVIDER_INC_SPEED: u32 = 0x4000_u32; // 16_384
-    const DIVIDER_TIMER_LIMIT: u32 = CPU::CYCLE_SPEED / Self::DIVIDER_INC_SPEED; // 64
+// TIMA reads 0 for an M-cycle after overflowing, then gets TMA and raises the interrupt
+#[derive(Copy, Clone, PartialEq)]
+enum CounterState {
+    Counting,
+    Overflowed,
+    Reloaded,
+}
 
+impl Clock {
     pub fn new() -> Self {
         Self {
-            divider: 0,
-            divider_increment_timer: 0,
+            system_counter: 0xABCC, // where the DMG boot ROM leaves it
             counter: 0,
-            counter_increment_timer: 0,
             modulo: 0,
             control: 0,
+            counter_state: CounterState::Counting,
             interrupt: 0,
+            frame_sequencer_ticks: 0,
         }
     }
 
+    // Ticks once per M-cycle. The MMU runs a cycle ahead of each memory access the CPU makes, then
+    // whatever's left of the instruction once it's done.
     pub fn run_cycle(&mut self, cpu_cycles: u8) {
-        self.divider_increment_timer += u32::from(cpu_cycles);
-        while self.divider_increment_timer >= Self::DIVIDER_TIMER_LIMIT {
-            self.divider = self.divider.wrapping_add(1);
-            self.divider_increment_timer -= Self::DIVIDER_TIMER_LIMIT;
-        }
-
-        if self.counter_stopped() {
-            return;
-        }
+        for _ in 0..cpu_cycles {
+            self.counter_state = match self.counter_state {
+                CounterState::Overflowed => {
+                    self.counter = self.modulo;
+                    self.interrupt |= 0x04;
+                    CounterState::Reloaded
+                }
+                _ => CounterState::Counting,
+            };
 
-        self.counter_increment_timer += u32::from(cpu_cycles);
-        let counter_timer_limit = CPU::CYCLE_SPEED / self.counter_speed();
-        while self.counter_increment_timer >= counter_timer_limit {
-            self.counter = self.counter.wrapping_add(1);
-            self.counter_increment_timer -= counter_timer_limit;
-            if self.counter == 0 {
-                self.interrupt = 0x04;
-                self.counter = self.modulo;
-            }
+            let system_counter = self.system_counter.wrapping_add(4);
+            self.set_system_counter(system_counter);
         }
     }
 
     pub fn read_byte(&self, addr: u16) -> u8 {
         match addr {
-            0xFF04 => self.divider,
+            0xFF04 => (self.system_counter >> 8) as u8,
             0xFF05 => self.counter,
             0xFF06 => self.modulo,
-            0xFF07 => self.control,
+            0xFF07 => self.control | 0xF8,
             _ => unreachable!("Tried to read non-existent clock address"),
         }
     }
 
     pub fn write_byte(&mut self, addr: u16, value: u8) {
         match addr {
-            0xFF04 => self.divider = 0,
-            0xFF05 => self.counter = value,
-            0xFF06 => self.modulo = value,
-            0xFF07 => self.control = value,
+            0xFF04 => self.set_system_counter(0),
+            0xFF05 => {
+                // writing while overflowed cancels the reload, while reloading TMA wins
+                if self.counter_state != CounterState::Reloaded {
+                    self.counter = value;
+                    self.counter_state = CounterState::Counting;
+                }
+            }
+            0xFF06 => {
+                self.modulo = value;
+                if self.counter_state == CounterState::Reloaded {
+                    self.counter = value;
+                }
+            }
+            0xFF07 => {
+                // turning the timer off or switching bits can look like a falling edge
+                let was_high = self.timer_input();
+                self.control = value & 0x07;
+                if was_high && !self.timer_input() {
+                    self.increment_counter();
+                }
+            }
             _ => unreachable!("Tried to write non-existent clock address"),
         }
     }
 
-    fn counter_stopped(&self) -> bool {
-        self.control & 0x04 == 0
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.system_counter);
+        state.write_u8(self.counter);
+        state.write_u8(self.modulo);
+        state.write_u8(self.control);
+        state.write_u8(self.counter_state as u8);
+        state.write_u8(self.frame_sequencer_ticks);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.system_counter = state.read_u16()?;
+        self.counter = state.read_u8()?;
+        self.modulo = state.read_u8()?;
+        self.control = state.read_u8()?;
+        self.counter_state = match state.read_u8()? {
+            0 => CounterState::Counting,
+            1 => CounterState::Overflowed,
+            _ => CounterState::Reloaded,
+        };
+        self.frame_sequencer_ticks = state.read_u8()?;
+        Ok(())
+    }
+
+    fn set_system_counter(&mut self, value: u16) {
+        let timer_was_high = self.timer_input();
+        let apu_was_high = self.system_counter & APU_BIT > 0;
+        self.system_counter = value;
+
+        if timer_was_high && !self.timer_input() {
+            self.increment_counter();
+        }
+        if apu_was_high && self.system_counter & APU_BIT == 0 {
+            self.frame_sequencer_ticks += 1;
+        }
+    }
+
+    fn increment_counter(&mut self) {
+        let (counter, overflowed) = self.counter.overflowing_add(1);
+        self.counter = counter;
+        if overflowed {
+            self.counter_state = CounterState::Overflowed;
+        }
+    }
+
+    // The timer enable bit is ANDed with the selected counter bit before the edge detector
+    fn timer_input(&self) -> bool {
+        self.control & 0x04 > 0 && self.system_counter & self.counter_bit() > 0
     }
 
-    fn counter_speed(&self) -> u32 {
+    fn counter_bit(&self) -> u16 {
         match self.control & 0x03 {
-            0x00 => 0x1_000,
-            0x01 => 0x40_000,
-            0x02 => 0x10_000,
-            _ => 0x4_000,
+            0x00 => 0x200, // 4_096 Hz
+            0x01 => 0x08,  // 262_144 Hz
+            0x02 => 0x20,  // 65_536 Hz
+            _ => 0x80,     // 16_384 Hz
         }
     }
 }
@@ -1,11 +1,23 @@
 mod ops;
 
+#[cfg(feature = "debugger")]
+use debugger::call_stack::CallStack;
 use input::Key;
+use mbc;
 use mmu;
+use movie::{self, Movie, Recorder, Start};
+use palette::Palettes;
 use register;
+use sound::scope::Visualization;
+use sound::{self, wav::WavFormat};
+use state::{StateReader, StateResult, StateWriter};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
 use std::sync::mpsc;
 use std::thread;
 use std::time::{Duration, Instant};
+use video::{self, VideoFormat, VideoRecorder};
 
 pub struct CPU {
     pub reg: register::Registers,
@@ -15,8 +27,38 @@ pub struct CPU {
     interrupts_enabled: bool,
     halting: bool,
     screen_exit_receiver: mpsc::Receiver<()>,
-    throttled_state_receiver: mpsc::Receiver<bool>,
+    control_receiver: mpsc::Receiver<Control>,
     throttled: bool,
+    paused: bool,
+    audio_sync: bool,
+    audio_format: WavFormat,
+    audio_stems: bool,
+    video_format: VideoFormat,
+    video_frame_skip: u32,
+    // the audio recording going is the one record_video started, so it stops with the video
+    video_audio: bool,
+    palettes: Palettes,
+    cart_path: String,
+    power_on_state: Vec<u8>,
+    #[cfg(feature = "debugger")]
+    pub call_stack: CallStack,
+}
+
+// Sent by the screen thread, picked up between batches of cycles
+pub enum Control {
+    Throttle(bool),
+    Pause,
+    Reset,
+    SaveState(u8),
+    LoadState(u8),
+    RecordMovie,
+    RecordAudio,
+    RecordVideo,
+    NextPalette,
+    // the channels are numbered from 0: square 1, square 2, wave and noise
+    ToggleMute(usize),
+    ToggleSolo(usize),
+    Visualize(Option<mpsc::Sender<Visualization>>),
 }
 
 impl CPU {
@@ -26,22 +68,55 @@ impl CPU {
 
     pub fn new(
         cart_path: &str,
+        audio: bool,
         screen_data_sender: mpsc::SyncSender<Vec<u8>>,
         key_data_receiver: mpsc::Receiver<Key>,
-        throttled_state_receiver: mpsc::Receiver<bool>,
+        control_receiver: mpsc::Receiver<Control>,
         screen_exit_receiver: mpsc::Receiver<()>,
     ) -> Self {
-        Self {
+        let mut cpu = Self {
             reg: register::Registers::new(),
-            mmu: mmu::MMU::new(cart_path, screen_data_sender, key_data_receiver),
+            mmu: mmu::MMU::new(cart_path, audio, screen_data_sender, key_data_receiver),
             disable_interrupt_after: 0,
             enable_interrupt_after: 0,
             interrupts_enabled: true,
             halting: false,
             screen_exit_receiver,
-            throttled_state_receiver,
+            control_receiver,
             throttled: true,
-        }
+            paused: false,
+            audio_sync: false,
+            audio_format: WavFormat::Pcm16,
+            audio_stems: false,
+            video_format: VideoFormat::Gif,
+            video_frame_skip: 0,
+            video_audio: false,
+            palettes: Palettes::presets(),
+            cart_path: cart_path.to_owned(),
+            power_on_state: vec![],
+            #[cfg(feature = "debugger")]
+            call_stack: CallStack::new(),
+        };
+        cpu.power_on_state = cpu.save_state();
+        cpu
+    }
+
+    // For running without a window, the caller takes the frames and sends the keys
+    pub fn new_headless(cart_path: &str, audio: bool) -> (Self, mpsc::Receiver<Vec<u8>>, mpsc::Sender<Key>) {
+        let (screen_data_sender, screen_data_receiver) = mpsc::sync_channel(1);
+        let (key_data_sender, key_data_receiver) = mpsc::channel();
+        let (_, control_receiver) = mpsc::channel();
+        let (_, screen_exit_receiver) = mpsc::channel();
+
+        let cpu = Self::new(
+            cart_path,
+            audio,
+            screen_data_sender,
+            key_data_receiver,
+            control_receiver,
+            screen_exit_receiver,
+        );
+        (cpu, screen_data_receiver, key_data_sender)
     }
 
     pub fn main_loop(&mut self) {
@@ -59,11 +134,16 @@ impl CPU {
                 if self.screen_exit_receiver.try_recv().is_ok() {
                     break;
                 }
-                if let Ok(v) = self.throttled_state_receiver.try_recv() {
-                    self.throttled = v
+                self.handle_controls();
+                if self.is_paused() {
+                    thread::sleep(Duration::from_millis(10));
+                    start_of_last_n_cycles = Instant::now();
+                    continue;
                 }
 
-                if self.throttled {
+                if self.audio_sync {
+                    self.wait_for_audio();
+                } else if self.throttled {
                     let time_since_last_set_start = Instant::now() - start_of_last_n_cycles;
                     if time_since_last_set_start < time_for_n_cycles {
                         thread::sleep(time_for_n_cycles - time_since_last_set_start);
@@ -88,16 +168,269 @@ impl CPU {
         }
     }
 
+    // Applies whatever the screen has sent since the last call
+    pub fn handle_controls(&mut self) {
+        // picked like any other palette, so the next one carries on from it
+        if let Some(name) = self.mmu.take_picked_palette() {
+            if self.palettes.select(name).is_ok() {
+                self.mmu.set_palette(self.palettes.current().1);
+                println!("Palette {}", name);
+            }
+        }
+        while let Ok(control) = self.control_receiver.try_recv() {
+            self.handle_control(control);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn handle_control(&mut self, control: Control) {
+        match control {
+            Control::Throttle(throttled) => self.throttled = throttled,
+            Control::Pause => {
+                self.paused = !self.paused;
+                println!("{}", if self.paused { "PAUSED" } else { "RESUMED" });
+            }
+            Control::Reset => {
+                self.reset();
+                println!("RESET");
+            }
+            Control::SaveState(slot) => {
+                let path = mbc::build_state_path(&self.cart_path, slot);
+                let state = self.save_state();
+                match File::create(&path).and_then(|mut file| file.write_all(&state)) {
+                    Ok(_) => println!("State saved to {}", path),
+                    Err(e) => println!("Failed to save state to {}: {}", path, e),
+                }
+            }
+            Control::LoadState(slot) => {
+                let path = mbc::build_state_path(&self.cart_path, slot);
+                let mut state = vec![];
+                let result = File::open(&path)
+                    .and_then(|mut file| file.read_to_end(&mut state))
+                    .map_err(|e| e.to_string())
+                    .and_then(|_| self.load_state(&state));
+                match result {
+                    Ok(_) => println!("State loaded from {}", path),
+                    Err(e) => println!("Failed to load state from {}: {}", path, e),
+                }
+            }
+            Control::RecordMovie => {
+                if self.mmu.is_recording() {
+                    self.mmu.stop_recording();
+                } else {
+                    let path = movie::build_movie_path(&self.cart_path);
+                    if let Err(e) = self.record_movie(&path, false) {
+                        println!("Failed to record movie to {}: {}", path, e);
+                    }
+                }
+            }
+            Control::RecordAudio => {
+                if self.mmu.is_recording_audio() {
+                    self.mmu.stop_audio_recording();
+                    self.video_audio = false;
+                } else {
+                    let path = sound::build_audio_path(&self.cart_path);
+                    if let Err(e) = self.record_audio(&path) {
+                        println!("Failed to record audio to {}: {}", path, e);
+                    }
+                }
+            }
+            Control::RecordVideo => {
+                if self.mmu.is_recording_video() {
+                    self.stop_video();
+                } else {
+                    let path = video::build_video_path(&self.cart_path, self.video_format);
+                    if let Err(e) = self.record_video(&path) {
+                        println!("Failed to record video to {}: {}", path, e);
+                    }
+                }
+            }
+            Control::NextPalette => {
+                let (name, palette) = self.palettes.next();
+                self.mmu.set_palette(palette);
+                println!("Palette {}", name);
+            }
+            Control::ToggleMute(channel) => {
+                let muted = !self.mmu.is_channel_muted(channel);
+                self.mmu.set_channel_muted(channel, muted);
+                println!("Channel {} {}", channel + 1, if muted { "muted" } else { "unmuted" });
+            }
+            Control::ToggleSolo(channel) => {
+                let soloed = !self.mmu.is_channel_soloed(channel);
+                self.mmu.set_channel_soloed(channel, soloed);
+                println!("Channel {} {}", channel + 1, if soloed { "soloed" } else { "unsoloed" });
+            }
+            Control::Visualize(sender) => self.mmu.visualize(sender),
+        }
+    }
+
+    // Everything but the host side: the window, audio output and whatever is on the link
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        state.write_u16(self.mmu.rom_checksum());
+        self.reg.save_state(&mut state);
+        state.write_u8(self.disable_interrupt_after);
+        state.write_u8(self.enable_interrupt_after);
+        state.write_bool(self.interrupts_enabled);
+        state.write_bool(self.halting);
+        self.mmu.save_state(&mut state);
+        state.into_bytes()
+    }
+
+    // A state that fails part way through loading is undone, leaving the machine as it was
+    pub fn load_state(&mut self, data: &[u8]) -> StateResult<()> {
+        let backup = self.save_state();
+        // stopped before the frame count jumps, a movie's frames can only go forwards
+        self.mmu.stop_movie();
+        let result = self.load_state_unchecked(data);
+        if result.is_err() {
+            self.load_state_unchecked(&backup)
+                .expect("Failed to restore state after a bad load");
+        }
+        result
+    }
+
+    fn load_state_unchecked(&mut self, data: &[u8]) -> StateResult<()> {
+        let mut state = StateReader::new(data)?;
+        if state.read_u16()? != self.mmu.rom_checksum() {
+            return Err(String::from("Save state is for a different cart"));
+        }
+        self.reg.load_state(&mut state)?;
+        self.disable_interrupt_after = state.read_u8()?;
+        self.enable_interrupt_after = state.read_u8()?;
+        self.interrupts_enabled = state.read_bool()?;
+        self.halting = state.read_bool()?;
+        self.mmu.load_state(&mut state)
+    }
+
+    // Back to power on, but like the real thing the cart RAM survives
+    pub fn reset(&mut self) {
+        let cart_ram = self.mmu.cart_ram().to_vec();
+        let power_on_state = self.power_on_state.clone();
+        self.load_state(&power_on_state)
+            .expect("Failed to load the power on state");
+        self.mmu.cart_ram_mut().copy_from_slice(&cart_ram);
+    }
+
+    // Paces emulation off the audio queue rather than the wall clock, so the sound never runs dry
+    // or builds up
+    pub fn sync_to_audio(&mut self) {
+        self.audio_sync = true;
+    }
+
+    // With audio sync on, holds the next batch of cycles until the player gets through enough of
+    // what's queued
+    pub fn wait_for_audio(&self) {
+        while self.audio_sync && self.throttled && self.mmu.is_audio_ahead() {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // Sets how audio recordings are written, whether started here or from the hotkey
+    pub fn set_audio_recording(&mut self, format: WavFormat, stems: bool) {
+        self.audio_format = format;
+        self.audio_stems = stems;
+    }
+
+    pub fn record_audio(&mut self, path: &str) -> io::Result<()> {
+        self.mmu
+            .start_audio_recording(path, self.audio_format, self.audio_stems)
+    }
+
+    // Sets how video recordings started from the hotkey are written, and how many frames are
+    // skipped between each one kept
+    pub fn set_video_recording(&mut self, format: VideoFormat, frame_skip: u32) {
+        self.video_format = format;
+        self.video_frame_skip = frame_skip;
+    }
+
+    pub fn set_palettes(&mut self, mut palettes: Palettes) {
+        palettes.select_for_cart(&self.mmu.cart_header());
+        self.mmu.set_palette(palettes.current().1);
+        self.palettes = palettes;
+    }
+
+    // The format comes from the extension when there's one that matches. The audio is recorded to a
+    // WAV next to it, unless an audio recording is already going, which is left to carry on.
+    pub fn record_video(&mut self, path: &str) -> io::Result<()> {
+        let format = VideoFormat::from_path(path).unwrap_or(self.video_format);
+        let recorder = VideoRecorder::create(path, format, self.video_frame_skip)?;
+        if self.mmu.is_recording_audio() {
+            println!("Audio is already being recorded, the video won't get its own WAV");
+        } else {
+            let audio_path = Path::new(path).with_extension("wav");
+            self.record_audio(&audio_path.to_string_lossy())?;
+            self.video_audio = true;
+        }
+        self.mmu.start_video_recording(recorder);
+        println!("Recording video to {}", path);
+        Ok(())
+    }
+
+    fn stop_video(&mut self) {
+        self.mmu.stop_video_recording();
+        if self.video_audio {
+            self.mmu.stop_audio_recording();
+            self.video_audio = false;
+        }
+    }
+
+    // Records from power on, or from here with the current state embedded in the movie
+    pub fn record_movie(&mut self, path: &str, from_power_on: bool) -> io::Result<()> {
+        let start = if from_power_on {
+            self.reset();
+            Start::PowerOn(self.mmu.cart_ram().to_vec())
+        } else {
+            Start::State(self.save_state())
+        };
+        let recorder = Recorder::create(path, self.mmu.rom_checksum(), &start)?;
+        self.mmu.start_recording(recorder);
+        Ok(())
+    }
+
+    // Puts the machine where the movie starts, its keys replace the host's until it runs out
+    pub fn play_movie(&mut self, movie: Movie) -> StateResult<()> {
+        if movie.rom_checksum != self.mmu.rom_checksum() {
+            return Err(String::from("Movie is for a different cart"));
+        }
+        match movie.start {
+            Start::PowerOn(ref cart_ram) => {
+                if cart_ram.len() != self.mmu.cart_ram().len() {
+                    return Err(String::from("Movie's cart RAM doesn't match the cart"));
+                }
+                self.reset();
+                self.mmu.cart_ram_mut().copy_from_slice(cart_ram);
+            }
+            Start::State(ref state) => self.load_state(state)?,
+        }
+        self.mmu.start_playback(movie.into_playback());
+        Ok(())
+    }
+
     pub fn run_cycle(&mut self) -> u8 {
+        self.mmu.start_instruction();
         let cycles = self.run_cpu_cycle();
         self.mmu.run_cycle(cycles);
         cycles
     }
 
+    #[cfg(not(feature = "debugger"))]
     pub fn step(&mut self) -> u8 {
         self.call_reg_op()
     }
 
+    #[cfg(feature = "debugger")]
+    pub fn step(&mut self) -> u8 {
+        let (opcode, pc, sp) = (self.mmu.peek_byte(self.reg.pc), self.reg.pc, self.reg.sp);
+        let cycles = self.call_reg_op();
+        self.call_stack
+            .track_instruction(opcode, pc, sp, self.reg.pc, self.reg.sp);
+        cycles
+    }
+
     fn run_cpu_cycle(&mut self) -> u8 {
         self.update_interrupt_counters();
         let interrupt_cycles = self.jump_on_interrupt();
@@ -112,6 +445,11 @@ impl CPU {
         }
     }
 
+    #[cfg(feature = "debugger")]
+    pub fn is_halting(&self) -> bool {
+        self.halting
+    }
+
     fn update_interrupt_counters(&mut self) {
         if self.disable_interrupt_after > 0 {
             self.disable_interrupt_after -= 1;
@@ -153,6 +491,9 @@ impl CPU {
                 let old_pc = self.reg.pc;
                 self.push_stack(old_pc);
                 self.reg.pc = *interrupt_jump_address;
+                #[cfg(feature = "debugger")]
+                self.call_stack
+                    .enter_interrupt(old_pc, *interrupt_jump_address, self.reg.sp);
                 return 4;
             }
         }
@@ -166,6 +507,12 @@ impl CPU {
         byte
     }
 
+    fn get_opcode(&mut self) -> u8 {
+        let opcode = self.mmu.fetch_opcode(self.reg.pc);
+        self.reg.pc += 1;
+        opcode
+    }
+
     fn get_signed_byte(&mut self) -> i8 {
         self.get_byte() as i8
     }
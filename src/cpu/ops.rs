@@ -4,7 +4,7 @@ use register::Flags;
 impl CPU {
     pub fn call_reg_op(&mut self) -> u8 {
         let read_regs = self.reg;
-        let code = self.get_byte();
+        let code = self.get_opcode();
 
         match code {
             0x00 => {
@@ -0,0 +1,40 @@
+use debugger::expression::Expression;
+use std::fmt;
+use watches::Access;
+
+#[derive(PartialEq)]
+pub enum Trigger {
+    Exec { bank: Option<u16>, addr: u16 },
+    Watch { access: Access, start: u16, end: u16 },
+}
+
+pub struct Breakpoint {
+    pub id: u32,
+    pub trigger: Trigger,
+    pub condition: Option<(String, Expression)>,
+    pub enabled: bool,
+    pub hits: u32,
+}
+
+impl Access {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "r" | "read" => Some(Access::Read),
+            "w" | "write" => Some(Access::Write),
+            "rw" | "access" => Some(Access::ReadWrite),
+            "x" | "exec" => Some(Access::Exec),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Access::Read => write!(f, "read"),
+            Access::Write => write!(f, "write"),
+            Access::ReadWrite => write!(f, "read/write"),
+            Access::Exec => write!(f, "exec"),
+        }
+    }
+}
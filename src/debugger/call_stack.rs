@@ -0,0 +1,96 @@
+use std::fmt;
+
+// Frames are tracked from CALL/RST/interrupt entries and dropped on RET/RETI,
+// matched by stack pointer so frames abandoned by stack manipulation are cleaned up too
+const MAX_DEPTH: usize = 1024;
+
+#[derive(Copy, Clone)]
+pub enum FrameKind {
+    Call,
+    Rst,
+    Interrupt,
+}
+
+pub struct Frame {
+    pub kind: FrameKind,
+    pub call_site: u16,
+    pub target: u16,
+    pub return_addr: u16,
+    pub sp: u16,
+}
+
+pub struct CallStack {
+    frames: Vec<Frame>,
+}
+
+impl CallStack {
+    pub fn new() -> Self {
+        Self { frames: vec![] }
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn track_instruction(&mut self, opcode: u8, pc: u16, sp_before: u16, pc_after: u16, sp_after: u16) {
+        if let Some((kind, length)) = call_kind(opcode) {
+            if sp_after == sp_before.wrapping_sub(2) {
+                self.push(Frame {
+                    kind,
+                    call_site: pc,
+                    target: pc_after,
+                    return_addr: pc.wrapping_add(length),
+                    sp: sp_after,
+                });
+            }
+        } else if is_return(opcode) && sp_after == sp_before.wrapping_add(2) {
+            while self.frames.last().is_some_and(|frame| frame.sp <= sp_before) {
+                self.frames.pop();
+            }
+        }
+    }
+
+    pub fn enter_interrupt(&mut self, return_addr: u16, target: u16, sp: u16) {
+        self.push(Frame {
+            kind: FrameKind::Interrupt,
+            call_site: return_addr,
+            target,
+            return_addr,
+            sp,
+        });
+    }
+
+    fn push(&mut self, frame: Frame) {
+        if self.frames.len() >= MAX_DEPTH {
+            self.frames.remove(0);
+        }
+        self.frames.push(frame);
+    }
+}
+
+impl fmt::Display for FrameKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FrameKind::Call => write!(f, "call"),
+            FrameKind::Rst => write!(f, "rst"),
+            FrameKind::Interrupt => write!(f, "interrupt"),
+        }
+    }
+}
+
+// Kind and instruction length of CALL and RST opcodes
+pub fn call_kind(opcode: u8) -> Option<(FrameKind, u16)> {
+    match opcode {
+        0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC => Some((FrameKind::Call, 3)),
+        0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => Some((FrameKind::Rst, 1)),
+        _ => None,
+    }
+}
+
+fn is_return(opcode: u8) -> bool {
+    matches!(opcode, 0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9)
+}
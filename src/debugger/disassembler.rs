@@ -0,0 +1,134 @@
+use mmu::MMU;
+
+// http://www.z80.info/decoding.htm, adjusted for the Gameboy's instruction set
+const R: [&str; 8] = ["b", "c", "d", "e", "h", "l", "(hl)", "a"];
+const RP: [&str; 4] = ["bc", "de", "hl", "sp"];
+const RP2: [&str; 4] = ["bc", "de", "hl", "af"];
+const CC: [&str; 4] = ["nz", "z", "nc", "c"];
+const ALU: [&str; 8] = ["add a,", "adc a,", "sub", "sbc a,", "and", "xor", "or", "cp"];
+const ROT: [&str; 8] = ["rlc", "rrc", "rl", "rr", "sla", "sra", "swap", "srl"];
+const ACC: [&str; 8] = ["rlca", "rrca", "rla", "rra", "daa", "cpl", "scf", "ccf"];
+
+pub struct Instruction {
+    pub text: String,
+    pub length: u16,
+}
+
+// Decodes the instruction at addr. label_for is used to name jump, call and load targets.
+pub fn disassemble<F>(mmu: &MMU, addr: u16, label_for: F) -> Instruction
+where
+    F: Fn(u16) -> Option<String>,
+{
+    let opcode = mmu.peek_byte(addr);
+    let n = mmu.peek_byte(addr.wrapping_add(1));
+    let nn = u16::from(n) | u16::from(mmu.peek_byte(addr.wrapping_add(2))) << 8;
+    let d = n as i8;
+
+    let x = opcode >> 6;
+    let y = (opcode >> 3) & 0x07;
+    let z = opcode & 0x07;
+    let p = (y >> 1) as usize;
+    let q = y & 0x01;
+    let (y, z) = (y as usize, z as usize);
+
+    let target = |addr: u16| label_for(addr).unwrap_or_else(|| format!("0x{:X}", addr));
+    let relative = addr.wrapping_add(2).wrapping_add(d as u16);
+
+    let (text, length) = match (x, z) {
+        (0, 0) => match y {
+            0 => ("nop".to_owned(), 1),
+            1 => (format!("ld ({}), sp", target(nn)), 3),
+            2 => ("stop".to_owned(), 2),
+            3 => (format!("jr {}", target(relative)), 2),
+            _ => (format!("jr {}, {}", CC[y - 4], target(relative)), 2),
+        },
+        (0, 1) => {
+            if q == 0 {
+                (format!("ld {}, 0x{:X}", RP[p], nn), 3)
+            } else {
+                (format!("add hl, {}", RP[p]), 1)
+            }
+        }
+        (0, 2) => {
+            let pointer = ["(bc)", "(de)", "(hl+)", "(hl-)"][p];
+            if q == 0 {
+                (format!("ld {}, a", pointer), 1)
+            } else {
+                (format!("ld a, {}", pointer), 1)
+            }
+        }
+        (0, 3) => (format!("{} {}", if q == 0 { "inc" } else { "dec" }, RP[p]), 1),
+        (0, 4) => (format!("inc {}", R[y]), 1),
+        (0, 5) => (format!("dec {}", R[y]), 1),
+        (0, 6) => (format!("ld {}, 0x{:X}", R[y], n), 2),
+        (0, _) => (ACC[y].to_owned(), 1),
+        (1, _) => {
+            if y == 6 && z == 6 {
+                ("halt".to_owned(), 1)
+            } else {
+                (format!("ld {}, {}", R[y], R[z]), 1)
+            }
+        }
+        (2, _) => (format!("{} {}", ALU[y], R[z]), 1),
+        (_, 0) => match y {
+            0...3 => (format!("ret {}", CC[y]), 1),
+            4 => (format!("ldh ({}), a", target(0xFF00 | u16::from(n))), 2),
+            5 => (format!("add sp, {}", d), 2),
+            6 => (format!("ldh a, ({})", target(0xFF00 | u16::from(n))), 2),
+            _ => (format!("ld hl, sp{:+}", d), 2),
+        },
+        (_, 1) => {
+            if q == 0 {
+                (format!("pop {}", RP2[p]), 1)
+            } else {
+                (["ret", "reti", "jp hl", "ld sp, hl"][p].to_owned(), 1)
+            }
+        }
+        (_, 2) => match y {
+            0...3 => (format!("jp {}, {}", CC[y], target(nn)), 3),
+            4 => ("ld (c), a".to_owned(), 1),
+            5 => (format!("ld ({}), a", target(nn)), 3),
+            6 => ("ld a, (c)".to_owned(), 1),
+            _ => (format!("ld a, ({})", target(nn)), 3),
+        },
+        (_, 3) => match y {
+            0 => (format!("jp {}", target(nn)), 3),
+            1 => (disassemble_cb(n), 2),
+            6 => ("di".to_owned(), 1),
+            7 => ("ei".to_owned(), 1),
+            _ => (format!("db 0x{:X}", opcode), 1),
+        },
+        (_, 4) => {
+            if y < 4 {
+                (format!("call {}, {}", CC[y], target(nn)), 3)
+            } else {
+                (format!("db 0x{:X}", opcode), 1)
+            }
+        }
+        (_, 5) => {
+            if q == 0 {
+                (format!("push {}", RP2[p]), 1)
+            } else if p == 0 {
+                (format!("call {}", target(nn)), 3)
+            } else {
+                (format!("db 0x{:X}", opcode), 1)
+            }
+        }
+        (_, 6) => (format!("{} 0x{:X}", ALU[y], n), 2),
+        (_, _) => (format!("rst {}", target((y as u16) * 8)), 1),
+    };
+
+    Instruction { text, length }
+}
+
+fn disassemble_cb(opcode: u8) -> String {
+    let y = ((opcode >> 3) & 0x07) as usize;
+    let z = (opcode & 0x07) as usize;
+
+    match opcode >> 6 {
+        0 => format!("{} {}", ROT[y], R[z]),
+        1 => format!("bit {}, {}", y, R[z]),
+        2 => format!("res {}, {}", y, R[z]),
+        _ => format!("set {}, {}", y, R[z]),
+    }
+}
@@ -0,0 +1,276 @@
+use cpu::CPU;
+
+// Conditions attached to breakpoints, e.g. `a == 3 && [0xC000] > 2`
+// `[addr]` reads a byte, `value` is the byte a watchpoint saw being read or written
+pub enum Expression {
+    Number(u32),
+    Register(Register),
+    Memory(Box<Expression>),
+    AccessedValue,
+    Not(Box<Expression>),
+    Compare(Box<Expression>, Comparison, Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+}
+
+#[derive(Copy, Clone)]
+pub enum Register {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+#[derive(Copy, Clone)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+enum Token {
+    Number(u32),
+    Identifier(String),
+    Operator(&'static str),
+}
+
+impl Expression {
+    pub fn parse<F>(source: &str, resolve_label: F) -> Result<Self, String>
+    where
+        F: Fn(&str) -> Option<u32>,
+    {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            resolve_label: &resolve_label,
+        };
+        let expression = parser.parse_or()?;
+        if parser.position < parser.tokens.len() {
+            return Err(format!("Unexpected input in condition: {}", source));
+        }
+        Ok(expression)
+    }
+
+    pub fn evaluate(&self, cpu: &CPU, accessed_value: Option<u8>) -> u32 {
+        match *self {
+            Expression::Number(value) => value,
+            Expression::Register(register) => register.read(cpu),
+            Expression::Memory(ref addr) => u32::from(cpu.mmu.peek_byte(addr.evaluate(cpu, accessed_value) as u16)),
+            Expression::AccessedValue => u32::from(accessed_value.unwrap_or(0)),
+            Expression::Not(ref inner) => u32::from(inner.evaluate(cpu, accessed_value) == 0),
+            Expression::Compare(ref left, comparison, ref right) => {
+                let (left, right) = (left.evaluate(cpu, accessed_value), right.evaluate(cpu, accessed_value));
+                u32::from(match comparison {
+                    Comparison::Equal => left == right,
+                    Comparison::NotEqual => left != right,
+                    Comparison::Less => left < right,
+                    Comparison::LessOrEqual => left <= right,
+                    Comparison::Greater => left > right,
+                    Comparison::GreaterOrEqual => left >= right,
+                })
+            }
+            Expression::And(ref left, ref right) => {
+                u32::from(left.evaluate(cpu, accessed_value) != 0 && right.evaluate(cpu, accessed_value) != 0)
+            }
+            Expression::Or(ref left, ref right) => {
+                u32::from(left.evaluate(cpu, accessed_value) != 0 || right.evaluate(cpu, accessed_value) != 0)
+            }
+        }
+    }
+
+    pub fn is_true(&self, cpu: &CPU, accessed_value: Option<u8>) -> bool {
+        self.evaluate(cpu, accessed_value) != 0
+    }
+}
+
+impl Register {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "a" => Some(Register::A),
+            "b" => Some(Register::B),
+            "c" => Some(Register::C),
+            "d" => Some(Register::D),
+            "e" => Some(Register::E),
+            "f" => Some(Register::F),
+            "h" => Some(Register::H),
+            "l" => Some(Register::L),
+            "af" => Some(Register::AF),
+            "bc" => Some(Register::BC),
+            "de" => Some(Register::DE),
+            "hl" => Some(Register::HL),
+            "sp" => Some(Register::SP),
+            "pc" => Some(Register::PC),
+            _ => None,
+        }
+    }
+
+    pub fn read(self, cpu: &CPU) -> u32 {
+        let register = cpu.reg;
+        u32::from(match self {
+            Register::A => u16::from(register.a),
+            Register::B => u16::from(register.b),
+            Register::C => u16::from(register.c),
+            Register::D => u16::from(register.d),
+            Register::E => u16::from(register.e),
+            Register::F => register.get_af() & 0xFF,
+            Register::H => u16::from(register.h),
+            Register::L => u16::from(register.l),
+            Register::AF => register.get_af(),
+            Register::BC => register.get_bc(),
+            Register::DE => register.get_de(),
+            Register::HL => register.get_hl(),
+            Register::SP => register.sp,
+            Register::PC => register.pc,
+        })
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    resolve_label: &'a dyn Fn(&str) -> Option<u32>,
+}
+
+impl<'a> Parser<'a> {
+    fn parse_or(&mut self) -> Result<Expression, String> {
+        let mut left = self.parse_and()?;
+        while self.accept("||") {
+            let right = self.parse_and()?;
+            left = Expression::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, String> {
+        let mut left = self.parse_comparison()?;
+        while self.accept("&&") {
+            let right = self.parse_comparison()?;
+            left = Expression::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, String> {
+        let left = self.parse_operand()?;
+        let comparison = match self.tokens.get(self.position) {
+            Some(&Token::Operator("==")) => Comparison::Equal,
+            Some(&Token::Operator("!=")) => Comparison::NotEqual,
+            Some(&Token::Operator("<")) => Comparison::Less,
+            Some(&Token::Operator("<=")) => Comparison::LessOrEqual,
+            Some(&Token::Operator(">")) => Comparison::Greater,
+            Some(&Token::Operator(">=")) => Comparison::GreaterOrEqual,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+
+        let right = self.parse_operand()?;
+        Ok(Expression::Compare(Box::new(left), comparison, Box::new(right)))
+    }
+
+    fn parse_operand(&mut self) -> Result<Expression, String> {
+        let token = match self.tokens.get(self.position) {
+            Some(token) => token,
+            None => return Err("Condition ended unexpectedly".to_owned()),
+        };
+        self.position += 1;
+
+        match *token {
+            Token::Number(value) => Ok(Expression::Number(value)),
+            Token::Identifier(ref name) => {
+                if name == "value" {
+                    Ok(Expression::AccessedValue)
+                } else if let Some(register) = Register::from_name(name) {
+                    Ok(Expression::Register(register))
+                } else if let Some(value) = (self.resolve_label)(name) {
+                    Ok(Expression::Number(value))
+                } else {
+                    Err(format!("Unknown register or label: {}", name))
+                }
+            }
+            Token::Operator("!") => Ok(Expression::Not(Box::new(self.parse_operand()?))),
+            Token::Operator("(") => {
+                let inner = self.parse_or()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Operator("[") => {
+                let addr = self.parse_or()?;
+                self.expect("]")?;
+                Ok(Expression::Memory(Box::new(addr)))
+            }
+            Token::Operator(operator) => Err(format!("Unexpected {} in condition", operator)),
+        }
+    }
+
+    fn accept(&mut self, operator: &str) -> bool {
+        match self.tokens.get(self.position) {
+            Some(&Token::Operator(found)) if found == operator => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, operator: &str) -> Result<(), String> {
+        if self.accept(operator) {
+            Ok(())
+        } else {
+            Err(format!("Expected {} in condition", operator))
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    const OPERATORS: [&str; 13] = ["==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")", "[", "]"];
+
+    let mut tokens = vec![];
+    let mut rest = source.trim_start();
+    while !rest.is_empty() {
+        if let Some(operator) = OPERATORS.iter().cloned().find(|operator| rest.starts_with(operator)) {
+            tokens.push(Token::Operator(operator));
+            rest = &rest[operator.len()..];
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == '$'))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("Unexpected character in condition: {}", rest));
+            }
+
+            let word = &rest[..end];
+            tokens.push(match parse_number(word) {
+                Some(value) => Token::Number(value),
+                None => Token::Identifier(word.to_owned()),
+            });
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> Option<u32> {
+    if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix('$')) {
+        u32::from_str_radix(hex, 16).ok()
+    } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+        word.parse::<u32>().ok()
+    } else {
+        None
+    }
+}
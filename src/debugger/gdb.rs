@@ -0,0 +1,430 @@
+use debugger::breakpoints::Trigger;
+use debugger::{output, Debugger, StepMode};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use watches::Access;
+
+// https://sourceware.org/gdb/onlinedocs/gdb/Remote-Protocol.html
+// Registers are sent as af, bc, de, hl, sp, pc, each 16 bit little endian, the same order gdb uses for the z80
+const REGISTER_COUNT: usize = 6;
+// Each byte read goes out as two hex digits, so this keeps replies inside the advertised PacketSize
+const MAX_READ_LENGTH: u32 = 0x800;
+const TARGET_XML: &str = "<?xml version=\"1.0\"?>\
+<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+<target version=\"1.0\">\
+<feature name=\"org.gnu.gdb.z80.cpu\">\
+<reg name=\"af\" bitsize=\"16\" type=\"int\"/>\
+<reg name=\"bc\" bitsize=\"16\" type=\"int\"/>\
+<reg name=\"de\" bitsize=\"16\" type=\"int\"/>\
+<reg name=\"hl\" bitsize=\"16\" type=\"int\"/>\
+<reg name=\"sp\" bitsize=\"16\" type=\"data_ptr\"/>\
+<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>\
+</feature>\
+</target>";
+
+pub struct GdbServer {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    // gdb is waiting on a stop reply after a continue or step
+    running: bool,
+    interrupted: bool,
+    // bytes that arrived while polling for an interrupt, read before the stream
+    pending: Vec<u8>,
+}
+
+impl GdbServer {
+    pub fn bind(port: u16) -> Self {
+        let listener = match TcpListener::bind(("127.0.0.1", port)) {
+            Ok(listener) => listener,
+            Err(e) => panic!("Failed to listen for gdb on port {}: {}", port, e),
+        };
+        output(&format!("Waiting for gdb on 127.0.0.1:{}\n", port));
+
+        Self {
+            listener,
+            stream: None,
+            running: false,
+            interrupted: false,
+            pending: vec![],
+        }
+    }
+
+    // Checked periodically while running, a ctrl-c from gdb or a new connection stops the cpu
+    pub fn poll_interrupt(&mut self) -> bool {
+        let mut buffer = [0; 64];
+        let result = match self.stream {
+            Some(ref mut stream) => {
+                stream.set_nonblocking(true).unwrap();
+                let result = stream.read(&mut buffer);
+                stream.set_nonblocking(false).unwrap();
+                result
+            }
+            None => {
+                self.listener.set_nonblocking(true).unwrap();
+                let accepted = self.listener.accept();
+                self.listener.set_nonblocking(false).unwrap();
+                if let Ok((stream, addr)) = accepted {
+                    self.connected(stream, &addr.to_string());
+                    self.interrupted = true;
+                }
+                return self.interrupted;
+            }
+        };
+
+        match result {
+            Ok(0) => self.disconnect(),
+            Ok(count) => {
+                self.interrupted = buffer[..count].contains(&0x03);
+                self.pending
+                    .extend(buffer[..count].iter().filter(|&&byte| byte != 0x03));
+            }
+            Err(_) => (),
+        }
+        self.interrupted
+    }
+
+    fn stream(&mut self) -> &mut TcpStream {
+        if self.stream.is_none() {
+            let (stream, addr) = self.listener.accept().unwrap();
+            self.connected(stream, &addr.to_string());
+        }
+        self.stream.as_mut().unwrap()
+    }
+
+    fn connected(&mut self, stream: TcpStream, addr: &str) {
+        stream.set_nonblocking(false).unwrap();
+        stream.set_nodelay(true).unwrap();
+        output(&format!("gdb connected from {}\n", addr));
+        self.stream = Some(stream);
+    }
+
+    fn disconnect(&mut self) {
+        if self.stream.take().is_some() {
+            output("gdb disconnected\n");
+        }
+        self.running = false;
+        self.pending.clear();
+    }
+
+    // Blocks until a packet with a valid checksum arrives, None if gdb went away
+    fn read_packet(&mut self) -> Option<String> {
+        let mut packet = vec![];
+        let mut in_packet = false;
+
+        loop {
+            let byte = self.read_byte()?;
+            match byte {
+                b'$' => {
+                    packet.clear();
+                    in_packet = true;
+                }
+                b'#' if in_packet => {
+                    let checksum = [self.read_byte()?, self.read_byte()?];
+                    let expected = String::from_utf8_lossy(&checksum);
+                    if u8::from_str_radix(&expected, 16).ok() == Some(checksum_of(&packet)) {
+                        self.write(b"+");
+                        return Some(String::from_utf8_lossy(&packet).into_owned());
+                    }
+                    self.write(b"-");
+                    in_packet = false;
+                }
+                _ if in_packet => packet.push(byte),
+                // acks, and interrupts which mean nothing while stopped
+                _ => (),
+            }
+        }
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        if !self.pending.is_empty() {
+            return Some(self.pending.remove(0));
+        }
+
+        let mut buffer = [0; 1];
+        match self.stream().read(&mut buffer) {
+            Ok(1) => Some(buffer[0]),
+            _ => {
+                self.disconnect();
+                None
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.write(packet.as_bytes());
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        if self.stream().write_all(data).is_err() {
+            self.disconnect();
+        }
+    }
+}
+
+impl Debugger {
+    pub fn poll_gdb(&mut self) -> bool {
+        self.gdb.as_mut().is_some_and(|gdb| gdb.poll_interrupt())
+    }
+
+    // Sends the reply gdb has been waiting on since it continued or stepped
+    pub fn report_gdb_stop(&mut self) {
+        if self.gdb.as_ref().is_some_and(|gdb| gdb.running) {
+            let reply = self.gdb_stop_reply();
+            let gdb = self.gdb.as_mut().unwrap();
+            gdb.running = false;
+            gdb.send(&reply);
+        }
+    }
+
+    pub fn process_gdb_packet(&mut self) {
+        let packet = match self.gdb.as_mut().unwrap().read_packet() {
+            Some(packet) => packet,
+            None => return,
+        };
+
+        if let Some(response) = self.gdb_response(&packet) {
+            self.gdb.as_mut().unwrap().send(&response);
+        }
+    }
+
+    // None when gdb expects no immediate reply, that is when the cpu is resumed
+    fn gdb_response(&mut self, packet: &str) -> Option<String> {
+        if packet.is_empty() {
+            return Some(String::new());
+        }
+
+        let (command, args) = packet.split_at(1);
+        let response = match command {
+            "?" => self.gdb_stop_reply(),
+            "g" => (0..REGISTER_COUNT)
+                .map(|index| encode_word(self.read_register(index).unwrap()))
+                .collect(),
+            "G" => {
+                let words = (0..REGISTER_COUNT).map(|index| args.get(index * 4..index * 4 + 4).and_then(decode_word));
+                for (index, word) in words.enumerate() {
+                    if let Some(word) = word {
+                        self.write_register(index, word);
+                    }
+                }
+                String::from("OK")
+            }
+            "p" => match parse_hex(args).and_then(|index| self.read_register(index as usize)) {
+                Some(value) => encode_word(value),
+                None => String::from("E01"),
+            },
+            "P" => {
+                let mut parts = args.splitn(2, '=');
+                match (
+                    parse_hex(parts.next().unwrap_or("")),
+                    parts.next().and_then(decode_word),
+                ) {
+                    (Some(index), Some(value)) if (index as usize) < REGISTER_COUNT => {
+                        self.write_register(index as usize, value);
+                        String::from("OK")
+                    }
+                    _ => String::from("E01"),
+                }
+            }
+            // gdb asks again for whatever's left of a short read
+            "m" => match parse_range(args) {
+                Some((addr, length)) => (0..length.min(MAX_READ_LENGTH))
+                    .map(|offset| format!("{:02x}", self.cpu.mmu.peek_byte(addr.wrapping_add(offset as u16))))
+                    .collect(),
+                None => String::from("E01"),
+            },
+            "M" => self.gdb_write_memory(args),
+            "c" | "s" => {
+                if let Some(addr) = parse_hex(args) {
+                    self.cpu.reg.pc = addr as u16;
+                }
+                if command == "s" {
+                    self.resume(StepMode::Instructions(1));
+                } else {
+                    self.debug_after_cycles_enabled = false;
+                    self.debugging = false;
+                }
+                let gdb = self.gdb.as_mut().unwrap();
+                gdb.running = true;
+                gdb.interrupted = false;
+                return None;
+            }
+            "Z" | "z" => self.gdb_breakpoint(command == "Z", args),
+            // the session ends here, any later stop falls back to the prompt instead of waiting on a new gdb
+            "D" => {
+                let mut gdb = self.gdb.take().unwrap();
+                gdb.send("OK");
+                gdb.disconnect();
+                output("gdb detached, the debugger prompt takes over\n");
+                self.debug_after_cycles_enabled = false;
+                self.debugging = false;
+                return None;
+            }
+            "k" => process::exit(0),
+            "H" => String::from("OK"),
+            "q" => self.gdb_query(packet),
+            // everything else, including vCont, is unsupported and gdb falls back to the basic packets
+            _ => String::new(),
+        };
+
+        Some(response)
+    }
+
+    fn gdb_query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return String::from("PacketSize=1000;qXfer:features:read+");
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            return match parse_range(range) {
+                Some((offset, length)) => {
+                    let offset = (offset as usize).min(TARGET_XML.len());
+                    let end = (offset + length as usize).min(TARGET_XML.len());
+                    let marker = if end == TARGET_XML.len() { "l" } else { "m" };
+                    format!("{}{}", marker, &TARGET_XML[offset..end])
+                }
+                None => String::from("E01"),
+            };
+        }
+
+        match packet {
+            "qAttached" => String::from("1"),
+            "qC" => String::from("QC1"),
+            "qfThreadInfo" => String::from("m1"),
+            "qsThreadInfo" => String::from("l"),
+            "qOffsets" => String::from("Text=0;Data=0;Bss=0"),
+            "qSymbol::" => String::from("OK"),
+            _ => String::new(),
+        }
+    }
+
+    fn gdb_stop_reply(&self) -> String {
+        if self.gdb.as_ref().is_some_and(|gdb| gdb.interrupted) {
+            return String::from("S02");
+        }
+
+        match self.last_watch_hit {
+            Some((access, addr)) => {
+                let kind = match access {
+                    Access::Write => "watch",
+                    Access::Read => "rwatch",
+                    _ => "awatch",
+                };
+                format!("T05{}:{:x};", kind, addr)
+            }
+            None => String::from("S05"),
+        }
+    }
+
+    // M<addr>,<length>:<bytes>, written through the mmu as if the cpu had done it
+    fn gdb_write_memory(&mut self, args: &str) -> String {
+        let mut parts = args.splitn(2, ':');
+        let (addr, length) = match parts.next().and_then(parse_range) {
+            Some(range) => range,
+            None => return String::from("E01"),
+        };
+        let data = parts.next().unwrap_or("");
+        // the hex is sliced two bytes at a time, which only lines up with characters in ASCII
+        if !data.is_ascii() || data.len() < length as usize * 2 {
+            return String::from("E01");
+        }
+
+        for offset in 0..length as usize {
+            match u8::from_str_radix(&data[offset * 2..offset * 2 + 2], 16) {
+                Ok(value) => self.cpu.mmu.write_byte(addr.wrapping_add(offset as u16), value),
+                Err(_) => return String::from("E01"),
+            }
+        }
+        self.cpu.mmu.watches.take_hits();
+        String::from("OK")
+    }
+
+    // Z<type>,<addr>,<kind> inserts and z removes: 0 and 1 are breakpoints, 2 write, 3 read and 4 access watchpoints
+    fn gdb_breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let mut parts = args.split([',', ';']);
+        let kind = parts.next().unwrap_or("");
+        let (addr, length) = match (parts.next().and_then(parse_hex), parts.next().and_then(parse_hex)) {
+            (Some(addr), Some(length)) => (addr as u16, length.clamp(1, 0x10000)),
+            _ => return String::from("E01"),
+        };
+
+        let watch = |access| Trigger::Watch {
+            access,
+            start: addr,
+            // watches don't wrap around, so a range past the top of memory stops there
+            end: (u32::from(addr) + length - 1).min(0xFFFF) as u16,
+        };
+        let trigger = match kind {
+            "0" | "1" => Trigger::Exec { bank: None, addr },
+            "2" => watch(Access::Write),
+            "3" => watch(Access::Read),
+            "4" => watch(Access::ReadWrite),
+            _ => return String::new(),
+        };
+
+        if insert {
+            self.push_breakpoint(trigger, None);
+        } else if let Some(index) = self
+            .breakpoints
+            .iter()
+            .position(|breakpoint| breakpoint.trigger == trigger && breakpoint.condition.is_none())
+        {
+            self.breakpoints.remove(index);
+            self.sync_watches();
+        }
+        String::from("OK")
+    }
+
+    fn read_register(&self, index: usize) -> Option<u16> {
+        let register = &self.cpu.reg;
+        match index {
+            0 => Some(register.get_af()),
+            1 => Some(register.get_bc()),
+            2 => Some(register.get_de()),
+            3 => Some(register.get_hl()),
+            4 => Some(register.sp),
+            5 => Some(register.pc),
+            _ => None,
+        }
+    }
+
+    fn write_register(&mut self, index: usize, value: u16) {
+        let register = &mut self.cpu.reg;
+        match index {
+            0 => register.set_af(value),
+            1 => register.set_bc(value),
+            2 => register.set_de(value),
+            3 => register.set_hl(value),
+            4 => register.sp = value,
+            5 => register.pc = value,
+            _ => (),
+        }
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum: u8, &byte| sum.wrapping_add(byte))
+}
+
+fn parse_hex(hex: &str) -> Option<u32> {
+    u32::from_str_radix(hex, 16).ok()
+}
+
+// <addr>,<length>
+fn parse_range(args: &str) -> Option<(u16, u32)> {
+    let mut parts = args.splitn(2, ',');
+    match (parts.next().and_then(parse_hex), parts.next().and_then(parse_hex)) {
+        (Some(addr), Some(length)) => Some((addr as u16, length)),
+        _ => None,
+    }
+}
+
+fn encode_word(value: u16) -> String {
+    format!("{:02x}{:02x}", value & 0xFF, value >> 8)
+}
+
+fn decode_word(hex: &str) -> Option<u16> {
+    let low = u8::from_str_radix(hex.get(0..2)?, 16).ok()?;
+    let high = u8::from_str_radix(hex.get(2..4)?, 16).ok()?;
+    Some(u16::from(low) | u16::from(high) << 8)
+}
@@ -0,0 +1,250 @@
+use mmu::MMU;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+const INTERRUPTS: [(u8, &str); 5] = [
+    (0x01, "V-Blank"),
+    (0x02, "LCD STAT"),
+    (0x04, "Timer"),
+    (0x08, "Serial"),
+    (0x10, "Joypad"),
+];
+
+const SOUND_REGISTERS: [(u16, &str); 21] = [
+    (0xFF10, "NR10"),
+    (0xFF11, "NR11"),
+    (0xFF12, "NR12"),
+    (0xFF13, "NR13"),
+    (0xFF14, "NR14"),
+    (0xFF16, "NR21"),
+    (0xFF17, "NR22"),
+    (0xFF18, "NR23"),
+    (0xFF19, "NR24"),
+    (0xFF1A, "NR30"),
+    (0xFF1B, "NR31"),
+    (0xFF1C, "NR32"),
+    (0xFF1D, "NR33"),
+    (0xFF1E, "NR34"),
+    (0xFF20, "NR41"),
+    (0xFF21, "NR42"),
+    (0xFF22, "NR43"),
+    (0xFF23, "NR44"),
+    (0xFF24, "NR50"),
+    (0xFF25, "NR51"),
+    (0xFF26, "NR52"),
+];
+
+// 16 bytes per line, with the printable characters alongside
+pub fn hexdump(mmu: &MMU, start: u16, length: u32) -> String {
+    let mut result = String::new();
+    let end = (u32::from(start) + length).min(0x10000);
+
+    for line_start in (u32::from(start)..end).step_by(16) {
+        let bytes: Vec<u8> = (line_start..end.min(line_start + 16))
+            .map(|addr| mmu.peek_byte(addr as u16))
+            .collect();
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let text: String = bytes
+            .iter()
+            .map(|&byte| {
+                if (0x20..0x7F).contains(&byte) {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        result.push_str(&format!("0x{:04X}  {:<47}  {}\n", line_start, hex.join(" "), text));
+    }
+
+    result
+}
+
+pub fn io_registers(mmu: &MMU) -> String {
+    let mut result = String::new();
+    let mut line = |name: &str, addr: u16, description: String| {
+        result.push_str(&format!(
+            "{:<5} 0x{:04X} = 0x{:02X}  {}\n",
+            name,
+            addr,
+            mmu.peek_byte(addr),
+            description
+        ));
+    };
+
+    let joypad = mmu.peek_byte(0xFF00);
+    line("P1", 0xFF00, flags(!joypad, &[(0x10, "directions"), (0x20, "buttons")]));
+
+    let serial_control = mmu.peek_byte(0xFF02);
+    line("SB", 0xFF01, String::new());
+    line(
+        "SC",
+        0xFF02,
+        format!(
+            "[{}, {} clock]",
+            if serial_control & 0x80 > 0 {
+                "transferring"
+            } else {
+                "idle"
+            },
+            if serial_control & 0x01 > 0 {
+                "internal"
+            } else {
+                "external"
+            }
+        ),
+    );
+
+    let timer_control = mmu.peek_byte(0xFF07);
+    line("DIV", 0xFF04, String::new());
+    line("TIMA", 0xFF05, String::new());
+    line("TMA", 0xFF06, String::new());
+    line(
+        "TAC",
+        0xFF07,
+        format!(
+            "[{}, {} Hz]",
+            if timer_control & 0x04 > 0 { "enabled" } else { "stopped" },
+            match timer_control & 0x03 {
+                0x00 => 4_096,
+                0x01 => 262_144,
+                0x02 => 65_536,
+                _ => 16_384,
+            }
+        ),
+    );
+
+    line("IF", 0xFF0F, flags(mmu.peek_byte(0xFF0F), &INTERRUPTS));
+    line("IE", 0xFFFF, flags(mmu.peek_byte(0xFFFF), &INTERRUPTS));
+
+    let lcd_control = mmu.peek_byte(0xFF40);
+    line(
+        "LCDC",
+        0xFF40,
+        format!(
+            "[LCD {}, window map 0x{:X}, window {}, tiles 0x{:X}, bg map 0x{:X}, obj 8x{}, obj {}, bg {}]",
+            on_off(lcd_control & 0x80),
+            if lcd_control & 0x40 > 0 { 0x9C00 } else { 0x9800 },
+            on_off(lcd_control & 0x20),
+            if lcd_control & 0x10 > 0 { 0x8000 } else { 0x8800 },
+            if lcd_control & 0x08 > 0 { 0x9C00 } else { 0x9800 },
+            if lcd_control & 0x04 > 0 { 16 } else { 8 },
+            on_off(lcd_control & 0x02),
+            on_off(lcd_control & 0x01)
+        ),
+    );
+
+    let stat = mmu.peek_byte(0xFF41);
+    line(
+        "STAT",
+        0xFF41,
+        format!(
+            "{} mode {}",
+            flags(
+                stat,
+                &[
+                    (0x40, "LYC int"),
+                    (0x20, "OAM int"),
+                    (0x10, "V-Blank int"),
+                    (0x08, "H-Blank int"),
+                    (0x04, "LYC=LY"),
+                ]
+            ),
+            stat & 0x03
+        ),
+    );
+    line("SCY", 0xFF42, String::new());
+    line("SCX", 0xFF43, String::new());
+    line("LY", 0xFF44, String::new());
+    line("LYC", 0xFF45, String::new());
+    line("DMA", 0xFF46, String::from("(write only)"));
+    line("BGP", 0xFF47, palette(mmu.peek_byte(0xFF47)));
+    line("OBP0", 0xFF48, palette(mmu.peek_byte(0xFF48)));
+    line("OBP1", 0xFF49, palette(mmu.peek_byte(0xFF49)));
+    line("WY", 0xFF4A, String::new());
+    line("WX", 0xFF4B, String::new());
+
+    for &(addr, name) in SOUND_REGISTERS.iter() {
+        let value = mmu.peek_byte(addr);
+        let description = match addr {
+            0xFF24 => format!("[left volume {}, right volume {}]", (value >> 4) & 0x07, value & 0x07),
+            0xFF25 => format!("[left {}, right {}]", channels(value >> 4), channels(value & 0x0F)),
+            0xFF26 => format!("[sound {}, playing {}]", on_off(value & 0x80), channels(value & 0x0F)),
+            _ => String::new(),
+        };
+        line(name, addr, description);
+    }
+
+    result.push_str(&format!("Wave RAM\n{}", hexdump(mmu, 0xFF30, 0x10)));
+    result
+}
+
+// Writes each memory area to its own file in the folder, returning the written file names
+pub fn dump(mmu: &MMU, folder_name: &str) -> io::Result<Vec<String>> {
+    let folder = Path::new(folder_name);
+    fs::create_dir_all(folder)?;
+
+    let read_range = |start: u16, end: u16| -> Vec<u8> { (start..=end).map(|addr| mmu.peek_byte(addr)).collect() };
+    let files = vec![
+        ("wram.dmp".to_owned(), read_range(0xC000, 0xDFFF)),
+        ("hram.dmp".to_owned(), read_range(0xFF80, 0xFFFE)),
+        ("vram.dmp".to_owned(), read_range(0x8000, 0x9FFF)),
+        ("oam.dmp".to_owned(), read_range(0xFE00, 0xFE9F)),
+        ("io.dmp".to_owned(), read_range(0xFF00, 0xFF7F)),
+        ("cart_ram.dmp".to_owned(), mmu.cart_ram().to_vec()),
+        ("rom_bank_00.dmp".to_owned(), read_range(0x0000, 0x3FFF)),
+        (
+            format!("rom_bank_{:02X}.dmp", mmu.rom_bank()),
+            read_range(0x4000, 0x7FFF),
+        ),
+        ("io.txt".to_owned(), io_registers(mmu).into_bytes()),
+    ];
+
+    let mut written = vec![];
+    for (file_name, data) in files {
+        File::create(folder.join(&file_name))?.write_all(&data)?;
+        written.push(file_name);
+    }
+
+    Ok(written)
+}
+
+fn flags(value: u8, names: &[(u8, &str)]) -> String {
+    let set: Vec<&str> = names
+        .iter()
+        .filter(|&&(mask, _)| value & mask > 0)
+        .map(|&(_, name)| name)
+        .collect();
+    format!("[{}]", set.join(", "))
+}
+
+fn palette(value: u8) -> String {
+    format!(
+        "[{}, {}, {}, {}]",
+        value & 0x03,
+        (value >> 2) & 0x03,
+        (value >> 4) & 0x03,
+        value >> 6
+    )
+}
+
+fn channels(mask: u8) -> String {
+    let names: Vec<String> = (0..4)
+        .filter(|channel| mask & (1 << channel) > 0)
+        .map(|channel| (channel + 1).to_string())
+        .collect();
+    if names.is_empty() {
+        String::from("none")
+    } else {
+        names.join(" ")
+    }
+}
+
+fn on_off(bit: u8) -> &'static str {
+    if bit > 0 {
+        "on"
+    } else {
+        "off"
+    }
+}
@@ -0,0 +1,675 @@
+pub mod breakpoints;
+pub mod call_stack;
+mod disassembler;
+mod expression;
+mod gdb;
+mod memory;
+mod symbols;
+
+use cpu;
+use debugger::breakpoints::{Breakpoint, Trigger};
+use debugger::call_stack::FrameKind;
+use debugger::disassembler::Instruction;
+use debugger::expression::Expression;
+use debugger::gdb::GdbServer;
+use debugger::symbols::Symbols;
+use register::Flags;
+use std::io::{self, Write};
+use std::process;
+use std::thread;
+use std::time::Duration;
+use watches::{Access, Watch};
+
+pub struct Debugger {
+    current_steps: u32,
+    debugging: bool,
+    debug_after_cycles_enabled: bool,
+    debug_after_cycles: u32,
+    output: bool,
+    cpu: cpu::CPU,
+    reg_break_points: Vec<RegBreakPoint>,
+    breakpoints: Vec<Breakpoint>,
+    next_breakpoint_id: u32,
+    stepping: Option<StepMode>,
+    last_ly: u8,
+    symbols: Symbols,
+    last_watch_hit: Option<(Access, u16)>,
+    gdb: Option<GdbServer>,
+}
+
+// How often the gdb connection and the window controls are checked while running
+const GDB_POLL_STEPS: u32 = 0x4000;
+
+struct RegBreakPoint {
+    key: String,
+    value: u32,
+}
+
+pub enum StepMode {
+    Instructions(u32),
+    Over { return_addr: u16, sp: u16 },
+    Out { depth: usize },
+    Frame,
+}
+
+impl Debugger {
+    pub fn new(debug_after_cycles: Option<u32>, gdb_port: Option<u16>, cart_path: &str, cpu: cpu::CPU) -> Debugger {
+        Debugger {
+            current_steps: 0,
+            debugging: false,
+            debug_after_cycles_enabled: debug_after_cycles.is_some(),
+            debug_after_cycles: debug_after_cycles.unwrap_or(0),
+            // tracing every instruction would drown out the gdb session
+            output: gdb_port.is_none(),
+            cpu,
+            reg_break_points: vec![],
+            breakpoints: vec![],
+            next_breakpoint_id: 1,
+            stepping: None,
+            last_ly: 0,
+            symbols: Symbols::load_for_cart(cart_path),
+            last_watch_hit: None,
+            gdb: gdb_port.map(GdbServer::bind),
+        }
+    }
+
+    pub fn run(&mut self) {
+        // hold the cpu at the entry point until gdb has attached
+        if self.gdb.is_some() && self.current_steps == 0 {
+            self.debug();
+        }
+
+        loop {
+            if self.output {
+                print!("{} ", self.current_steps);
+                let addr = self.cpu.reg.pc;
+                println!(
+                    "instr: 0x{:X}{} -- opcode: 0x{:X} -- {}",
+                    addr,
+                    self.describe_location(addr),
+                    self.cpu.mmu.peek_byte(addr),
+                    self.disassemble(addr).text
+                );
+            }
+            self.cpu.run_cycle();
+            self.current_steps += 1;
+            if self.current_steps.is_multiple_of(GDB_POLL_STEPS) {
+                self.handle_controls();
+            }
+            if self.should_stop() {
+                self.debug();
+            }
+        }
+    }
+
+    // Pausing from the window holds the cpu here, the debugger prompt isn't involved
+    fn handle_controls(&mut self) {
+        self.cpu.handle_controls();
+        self.cpu.wait_for_audio();
+        while self.cpu.is_paused() {
+            thread::sleep(Duration::from_millis(10));
+            self.cpu.handle_controls();
+        }
+    }
+
+    fn should_stop(&mut self) -> bool {
+        let mut stop = self.check_breakpoints();
+
+        if let Some(index) = self.stop_and_remove_breakon() {
+            self.reg_break_points.remove(index);
+            stop = true;
+        }
+
+        if self.step_finished() {
+            stop = true;
+        }
+
+        if self.current_steps.is_multiple_of(GDB_POLL_STEPS) && self.poll_gdb() {
+            stop = true;
+        }
+
+        stop || self.debug_after_cycles_enabled && self.current_steps >= self.debug_after_cycles
+    }
+
+    fn step_finished(&mut self) -> bool {
+        let ly = self.cpu.mmu.peek_byte(0xFF44);
+        let entered_v_blank = ly == 144 && self.last_ly != 144;
+        self.last_ly = ly;
+
+        match self.stepping {
+            Some(StepMode::Instructions(ref mut remaining)) => {
+                *remaining -= 1;
+                *remaining == 0
+            }
+            Some(StepMode::Over { return_addr, sp }) => self.cpu.reg.pc == return_addr && self.cpu.reg.sp >= sp,
+            Some(StepMode::Out { depth }) => self.cpu.call_stack.depth() < depth,
+            Some(StepMode::Frame) => entered_v_blank,
+            None => false,
+        }
+    }
+
+    fn check_breakpoints(&mut self) -> bool {
+        // always drain the watch hits so they don't pile up between breakpoints
+        let hits = self.cpu.mmu.watches.take_hits();
+        let pc = self.cpu.reg.pc;
+        let pc_bank = self.bank_for(pc);
+        let mut stop = false;
+
+        for index in 0..self.breakpoints.len() {
+            let message = {
+                let breakpoint = &self.breakpoints[index];
+                if !breakpoint.enabled {
+                    continue;
+                }
+
+                match breakpoint.trigger {
+                    Trigger::Exec { bank, addr } => {
+                        // a halted cpu sits on the same pc, only break when first arriving there
+                        let at_breakpoint =
+                            addr == pc && !self.cpu.is_halting() && bank.is_none_or(|bank| bank == pc_bank);
+                        if at_breakpoint && self.condition_met(breakpoint, None) {
+                            Some((format!("Breakpoint #{} hit", breakpoint.id), None))
+                        } else {
+                            None
+                        }
+                    }
+                    Trigger::Watch { access, .. } => hits
+                        .iter()
+                        .filter(|hit| hit.id == breakpoint.id)
+                        .find(|hit| self.condition_met(breakpoint, Some(hit.value)))
+                        .map(|hit| {
+                            let message = format!(
+                                "Watchpoint #{} hit: {} of 0x{:X} at 0x{:X}{}",
+                                breakpoint.id,
+                                hit.access,
+                                hit.value,
+                                hit.addr,
+                                self.describe_location(hit.addr)
+                            );
+                            (message, Some((access, hit.addr)))
+                        }),
+                }
+            };
+
+            if let Some((message, watch_hit)) = message {
+                if watch_hit.is_some() {
+                    self.last_watch_hit = watch_hit;
+                }
+                self.breakpoints[index].hits += 1;
+                output(&format!("{}\n", message));
+                stop = true;
+            }
+        }
+
+        stop
+    }
+
+    fn condition_met(&self, breakpoint: &Breakpoint, accessed_value: Option<u8>) -> bool {
+        breakpoint
+            .condition
+            .as_ref()
+            .is_none_or(|(_, condition)| condition.is_true(&self.cpu, accessed_value))
+    }
+
+    fn stop_and_remove_breakon(&self) -> Option<usize> {
+        let register = self.cpu.reg;
+        let break_points = &self.reg_break_points;
+
+        for (i, break_point) in break_points.iter().enumerate() {
+            let key = break_point.key.as_str();
+            let reg_value = match key {
+                "a" => register.a as u16,
+                "b" => register.b as u16,
+                "c" => register.c as u16,
+                "d" => register.d as u16,
+                "e" => register.e as u16,
+                "h" => register.h as u16,
+                "l" => register.l as u16,
+                "pc" => register.pc,
+                "sp" => register.sp,
+                _ => self.cpu.mmu.peek_byte(self.read_value(key) as u16) as u16,
+            } as u32;
+
+            if reg_value == break_point.value {
+                output(&format!("Breaking as {} is {}\n", key, reg_value));
+                return Some(i);
+            }
+        }
+
+        None
+    }
+
+    fn debug(&mut self) {
+        let pc = self.cpu.reg.pc;
+        output(&format!(
+            "Stopped at 0x{:X}{} -- {}\n",
+            pc,
+            self.describe_location(pc),
+            self.disassemble(pc).text
+        ));
+        self.stepping = None;
+        self.debugging = true;
+        self.report_gdb_stop();
+        while self.debugging {
+            if self.gdb.is_some() {
+                self.process_gdb_packet()
+            } else {
+                self.read_input_and_process()
+            }
+        }
+        self.last_watch_hit = None;
+    }
+
+    fn read_input_and_process(&mut self) {
+        let line = read_line();
+        let mut words = line.trim().split(" ");
+        match words.next() {
+            Some("c") | Some("continue") => {
+                self.debug_after_cycles_enabled = false;
+                self.debugging = false;
+            }
+            Some("n") | Some("next") => {
+                let jump = read_num(words.next().unwrap_or("0"));
+                self.debug_after_cycles = self.current_steps + jump;
+                self.debug_after_cycles_enabled = true;
+                self.debugging = false;
+            }
+            Some("s") | Some("step") => {
+                let count = read_num(words.next().unwrap_or("1")).max(1);
+                self.resume(StepMode::Instructions(count));
+            }
+            Some("over") => {
+                let pc = self.cpu.reg.pc;
+                match call_stack::call_kind(self.cpu.mmu.peek_byte(pc)) {
+                    Some((_, length)) => {
+                        let (return_addr, sp) = (pc.wrapping_add(length), self.cpu.reg.sp);
+                        self.resume(StepMode::Over { return_addr, sp });
+                    }
+                    None => self.resume(StepMode::Instructions(1)),
+                }
+            }
+            Some("out") => {
+                let depth = self.cpu.call_stack.depth();
+                if depth == 0 {
+                    // `out` used to turn tracing on and off, that's `trace` now
+                    output("Not inside a tracked function, use `trace on|off` to toggle tracing\n");
+                } else {
+                    self.resume(StepMode::Out { depth });
+                }
+            }
+            Some("frame") => self.resume(StepMode::Frame),
+            Some("bt") | Some("backtrace") => self.print_backtrace(),
+            Some("bo") | Some("breakon") => {
+                let key = words.next().unwrap().to_owned();
+                let value = self.read_value(words.next().unwrap_or("0"));
+                self.reg_break_points.push(RegBreakPoint { key, value });
+                self.debug_after_cycles_enabled = false;
+                self.debugging = false;
+            }
+            Some("b") | Some("break") => {
+                let args = words.collect::<Vec<&str>>().join(" ");
+                self.add_breakpoint(&args);
+            }
+            Some("w") | Some("watch") => {
+                let args = words.collect::<Vec<&str>>().join(" ");
+                self.add_watchpoint(&args);
+            }
+            Some("bl") | Some("breakpoints") => self.list_breakpoints(),
+            Some("enable") => self.set_breakpoint_enabled(words.next(), true),
+            Some("disable") => self.set_breakpoint_enabled(words.next(), false),
+            Some("delete") => {
+                match words.next().map(parse_num) {
+                    Some(Ok(id)) => self.breakpoints.retain(|breakpoint| breakpoint.id != id),
+                    Some(Err(e)) => return output(&format!("{}\n", e)),
+                    None => self.breakpoints.clear(),
+                }
+                self.sync_watches();
+            }
+            Some("reg") | Some("registers") => {
+                let action = words.next();
+
+                if action.is_some() && action.unwrap() == "write" {
+                    let reg_key = words.next().unwrap();
+                    let reg_value = self.read_value(words.next().unwrap());
+
+                    match reg_key {
+                        "a" => self.cpu.reg.a = reg_value as u8,
+                        "b" => self.cpu.reg.b = reg_value as u8,
+                        "c" => self.cpu.reg.c = reg_value as u8,
+                        "d" => self.cpu.reg.d = reg_value as u8,
+                        "e" => self.cpu.reg.e = reg_value as u8,
+                        "h" => self.cpu.reg.h = reg_value as u8,
+                        "l" => self.cpu.reg.l = reg_value as u8,
+                        "pc" => self.cpu.reg.pc = reg_value as u16,
+                        "sp" => self.cpu.reg.sp = reg_value as u16,
+                        _ => panic!("Unknown register {}\n", reg_key),
+                    };
+                } else {
+                    let register = self.cpu.reg;
+                    output("8 bit registers:\n");
+                    output(&format!("a: 0x{:X}\n", register.a));
+                    output(&format!("b: 0x{:X}\n", register.b));
+                    output(&format!("c: 0x{:X}\n", register.c));
+                    output(&format!("d: 0x{:X}\n", register.d));
+                    output(&format!("e: 0x{:X}\n", register.e));
+                    output(&format!("h: 0x{:X}\n", register.h));
+                    output(&format!("l: 0x{:X}\n", register.l));
+
+                    output("\n16 bit registers:\n");
+                    output(&format!("pc: 0x{:X}\n", register.pc));
+                    output(&format!("sp: 0x{:X}\n", register.sp));
+
+                    output("\nFlags:\n");
+                    output(&format!("Z: {}\n", register.get_flag(Flags::Z)));
+                    output(&format!("N: {}\n", register.get_flag(Flags::N)));
+                    output(&format!("H: {}\n", register.get_flag(Flags::H)));
+                    output(&format!("C: {}\n", register.get_flag(Flags::C)));
+                }
+            }
+            Some("lastbyte") => {
+                output(&format!("0x{:X}\n", self.cpu.mmu.peek_byte(self.cpu.reg.pc - 1)));
+            }
+            Some("rb") => {
+                let addr = self.read_value(words.next().unwrap_or("0")) as u16;
+                output(&format!("0x{:X}\n", self.cpu.mmu.peek_byte(addr)));
+            }
+            Some("rw") => {
+                let addr = self.read_value(words.next().unwrap_or("0")) as u16;
+                let word = u16::from(self.cpu.mmu.peek_byte(addr))
+                    | u16::from(self.cpu.mmu.peek_byte(addr.wrapping_add(1))) << 8;
+                output(&format!("0x{:X}\n", word));
+            }
+            Some("dis") | Some("disassemble") => {
+                let mut addr = words
+                    .next()
+                    .map_or(self.cpu.reg.pc, |word| self.read_value(word) as u16);
+                let count = read_num(words.next().unwrap_or("10"));
+                for _ in 0..count {
+                    if let Some(label) = self.label_for(addr) {
+                        output(&format!("{}:\n", label));
+                    }
+                    let instruction = self.disassemble(addr);
+                    output(&format!("  0x{:04X}  {}\n", addr, instruction.text));
+                    addr = addr.wrapping_add(instruction.length);
+                }
+            }
+            Some("where") => {
+                let pc = self.cpu.reg.pc;
+                output(&format!("0x{:X}{}\n", pc, self.describe_location(pc)));
+            }
+            Some("trace") => {
+                self.output = words.next().unwrap_or("on") == "on";
+            }
+            Some("wb") => {
+                let addr = self.read_value(words.next().unwrap_or("0")) as u16;
+                let value = self.read_value(words.next().unwrap_or("0")) as u8;
+                self.cpu.mmu.write_byte(addr, value);
+                // the debugger's own write shouldn't trip a watchpoint
+                self.cpu.mmu.watches.take_hits();
+            }
+            Some("hd") | Some("hexdump") => {
+                let addr = self.read_value(words.next().unwrap_or("0")) as u16;
+                let length = read_num(words.next().unwrap_or("0x100"));
+                output(&memory::hexdump(&self.cpu.mmu, addr, length));
+            }
+            Some("io") => output(&memory::io_registers(&self.cpu.mmu)),
+            Some("dump") => self.dump(words.next().unwrap_or("mem_dump")),
+            Some("exit") => process::exit(1),
+            _ => output("Unknown command!\n"),
+        };
+    }
+
+    fn resume(&mut self, mode: StepMode) {
+        self.stepping = Some(mode);
+        self.debug_after_cycles_enabled = false;
+        self.debugging = false;
+    }
+
+    fn print_backtrace(&self) {
+        let pc = self.cpu.reg.pc;
+        output(&format!("#0 0x{:X}{}\n", pc, self.describe_location(pc)));
+
+        for (depth, frame) in self.cpu.call_stack.frames().iter().rev().enumerate() {
+            let origin = match frame.kind {
+                FrameKind::Interrupt => String::from("interrupted"),
+                kind => format!("{} 0x{:X}{}", kind, frame.target, self.describe_location(frame.target)),
+            };
+            output(&format!(
+                "#{} 0x{:X}{} -- {}, returns to 0x{:X} (sp: 0x{:X})\n",
+                depth + 1,
+                frame.call_site,
+                self.describe_location(frame.call_site),
+                origin,
+                frame.return_addr,
+                frame.sp
+            ));
+        }
+    }
+
+    // break <location> [if <condition>], where location is an address, bank:address or label
+    fn add_breakpoint(&mut self, args: &str) {
+        let (location, condition) = split_condition(args);
+        if location.is_empty() {
+            return output("Usage: break <location> [if <condition>]\n");
+        }
+
+        let (bank, addr) = match self.read_location(location) {
+            Ok(location) => location,
+            Err(e) => return output(&format!("{}\n", e)),
+        };
+        self.push_breakpoint(Trigger::Exec { bank, addr }, condition);
+    }
+
+    // watch <r|w|rw|x> <start> [end] [if <condition>], `value` in the condition is the byte accessed
+    fn add_watchpoint(&mut self, args: &str) {
+        let (range, condition) = split_condition(args);
+        let mut range_words = range.split_whitespace();
+        let access = match range_words.next().and_then(Access::from_name) {
+            Some(access) => access,
+            None => return output("Usage: watch <r|w|rw|x> <start> [end] [if <condition>]\n"),
+        };
+        let start = match range_words.next().map(|start| self.parse_value(start)) {
+            Some(Ok(start)) => start as u16,
+            Some(Err(e)) => return output(&format!("{}\n", e)),
+            None => return output("Usage: watch <r|w|rw|x> <start> [end] [if <condition>]\n"),
+        };
+        let end = match range_words.next().map(|end| self.parse_value(end)) {
+            Some(Ok(end)) => end as u16,
+            Some(Err(e)) => return output(&format!("{}\n", e)),
+            None => start,
+        };
+
+        self.push_breakpoint(Trigger::Watch { access, start, end }, condition);
+    }
+
+    fn push_breakpoint(&mut self, trigger: Trigger, condition: Option<&str>) {
+        let condition = match condition {
+            Some(source) => {
+                let parsed = Expression::parse(source, |name| {
+                    self.symbols.lookup(name).map(|(_, addr)| u32::from(addr))
+                });
+                match parsed {
+                    Ok(expression) => Some((source.to_owned(), expression)),
+                    Err(e) => return output(&format!("{}\n", e)),
+                }
+            }
+            None => None,
+        };
+
+        let id = self.next_breakpoint_id;
+        self.next_breakpoint_id += 1;
+        self.breakpoints.push(Breakpoint {
+            id,
+            trigger,
+            condition,
+            enabled: true,
+            hits: 0,
+        });
+        self.sync_watches();
+        output(&format!("Added #{}\n", id));
+    }
+
+    fn list_breakpoints(&self) {
+        if self.breakpoints.is_empty() {
+            return output("No breakpoints\n");
+        }
+
+        for breakpoint in &self.breakpoints {
+            let location = match breakpoint.trigger {
+                Trigger::Exec { bank: Some(bank), addr } => {
+                    format!("break 0x{:X}:0x{:X}{}", bank, addr, self.describe_location(addr))
+                }
+                Trigger::Exec { bank: None, addr } => format!("break 0x{:X}{}", addr, self.describe_location(addr)),
+                Trigger::Watch { access, start, end } if start == end => {
+                    format!("watch {} 0x{:X}{}", access, start, self.describe_location(start))
+                }
+                Trigger::Watch { access, start, end } => format!("watch {} 0x{:X}-0x{:X}", access, start, end),
+            };
+            let condition = match breakpoint.condition {
+                Some((ref source, _)) => format!(" if {}", source),
+                None => String::new(),
+            };
+
+            output(&format!(
+                "#{} {} {}{} (hits: {})\n",
+                breakpoint.id,
+                if breakpoint.enabled { "enabled " } else { "disabled" },
+                location,
+                condition,
+                breakpoint.hits
+            ));
+        }
+    }
+
+    fn set_breakpoint_enabled(&mut self, id: Option<&str>, enabled: bool) {
+        let id = match id.map(parse_num) {
+            Some(Ok(id)) => Some(id),
+            Some(Err(e)) => return output(&format!("{}\n", e)),
+            None => None,
+        };
+        for breakpoint in &mut self.breakpoints {
+            if id.is_none_or(|id| id == breakpoint.id) {
+                breakpoint.enabled = enabled;
+            }
+        }
+        self.sync_watches();
+    }
+
+    // The MMU only needs to know about enabled watchpoints, conditions are checked here
+    fn sync_watches(&mut self) {
+        let watches = self
+            .breakpoints
+            .iter()
+            .filter(|breakpoint| breakpoint.enabled)
+            .filter_map(|breakpoint| match breakpoint.trigger {
+                Trigger::Watch { access, start, end } => Some(Watch {
+                    id: breakpoint.id,
+                    access,
+                    start,
+                    end,
+                }),
+                Trigger::Exec { .. } => None,
+            })
+            .collect();
+        self.cpu.mmu.watches.set(watches);
+    }
+
+    fn read_location(&self, location_str: &str) -> Result<(Option<u16>, u16), String> {
+        if let Some((bank, addr)) = self.symbols.lookup(location_str) {
+            // only switchable rom needs a bank to tell locations apart
+            let bank = if (0x4000..0x8000).contains(&addr) {
+                Some(bank)
+            } else {
+                None
+            };
+            return Ok((bank, addr));
+        }
+
+        let mut parts = location_str.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(bank), Some(addr)) => Ok((Some(parse_num(bank)? as u16), parse_num(addr)? as u16)),
+            _ => Ok((None, parse_num(location_str)? as u16)),
+        }
+    }
+
+    // Like read_value, but for commands that report bad input rather than panicking
+    fn parse_value(&self, value_str: &str) -> Result<u32, String> {
+        match self.symbols.lookup(value_str) {
+            Some((_, addr)) => Ok(u32::from(addr)),
+            None => parse_num(value_str),
+        }
+    }
+
+    fn read_value(&self, value_str: &str) -> u32 {
+        match self.symbols.lookup(value_str) {
+            Some((_, addr)) => u32::from(addr),
+            None => read_num(value_str),
+        }
+    }
+
+    fn bank_for(&self, addr: u16) -> u16 {
+        symbols::bank_for(addr, self.cpu.mmu.rom_bank(), self.cpu.mmu.ram_bank())
+    }
+
+    fn label_for(&self, addr: u16) -> Option<String> {
+        let bank = self.bank_for(addr);
+        self.symbols.label_at(bank, addr).map(|label| label.to_owned())
+    }
+
+    // Nearest label and offset for an address, ready to append to output
+    fn describe_location(&self, addr: u16) -> String {
+        let bank = self.bank_for(addr);
+        match self.symbols.nearest(bank, addr) {
+            Some((label, 0)) => format!(" <{}>", label),
+            Some((label, offset)) => format!(" <{}+0x{:X}>", label, offset),
+            None => String::new(),
+        }
+    }
+
+    fn disassemble(&self, addr: u16) -> Instruction {
+        disassembler::disassemble(&self.cpu.mmu, addr, |target| self.label_for(target))
+    }
+
+    fn dump(&self, folder_name: &str) {
+        match memory::dump(&self.cpu.mmu, folder_name) {
+            Ok(files) => output(&format!("Dumped {} to {}\n", files.join(", "), folder_name)),
+            Err(e) => output(&format!("Failed to dump memory to {}: {}\n", folder_name, e)),
+        }
+    }
+}
+
+fn split_condition(args: &str) -> (&str, Option<&str>) {
+    let mut parts = args.splitn(2, " if ");
+    let head = parts.next().unwrap_or("").trim();
+    (head, parts.next().map(|condition| condition.trim()))
+}
+
+fn read_num(num_str: &str) -> u32 {
+    if num_str.starts_with("0x") {
+        u32::from_str_radix(&num_str[2..], 16).unwrap()
+    } else {
+        num_str.parse::<u32>().unwrap()
+    }
+}
+
+fn parse_num(num_str: &str) -> Result<u32, String> {
+    let parsed = match num_str.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => num_str.parse::<u32>(),
+    };
+    parsed.map_err(|_| format!("Invalid number {}", num_str))
+}
+
+fn read_line() -> String {
+    output("What do you want?\n");
+    let mut buffer = String::new();
+    io::stdin().read_line(&mut buffer).unwrap();
+    buffer
+}
+
+pub fn output(line: &str) {
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    handle.write(line.as_bytes()).unwrap();
+    handle.flush();
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+// RGBDS symbol files list one label per line as `bank:addr label`, with `;` starting a comment
+// https://rgbds.gbdev.io/sym/
+pub struct Symbols {
+    labels: BTreeMap<(u16, u16), String>,
+    addresses: HashMap<String, (u16, u16)>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self {
+            labels: BTreeMap::new(),
+            addresses: HashMap::new(),
+        }
+    }
+
+    pub fn load_for_cart(cart_path: &str) -> Self {
+        let path = Path::new(cart_path).with_extension("sym");
+        let mut symbols = Self::new();
+        if !path.exists() {
+            return symbols;
+        }
+
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines().map_while(Result::ok) {
+                    symbols.parse_line(&line);
+                }
+                println!("Symbols loaded from {}", path.to_string_lossy());
+            }
+            Err(e) => println!("Failed to read symbols from {}: {}", path.to_string_lossy(), e),
+        };
+
+        symbols
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<(u16, u16)> {
+        self.addresses.get(name).cloned()
+    }
+
+    pub fn label_at(&self, bank: u16, addr: u16) -> Option<&str> {
+        self.labels.get(&(bank, addr)).map(|name| name.as_str())
+    }
+
+    // Closest label at or before the address, without crossing into another memory region
+    pub fn nearest(&self, bank: u16, addr: u16) -> Option<(&str, u16)> {
+        self.labels
+            .range(..=(bank, addr))
+            .next_back()
+            .filter(|&(&(label_bank, label_addr), _)| label_bank == bank && region(label_addr) == region(addr))
+            .map(|(&(_, label_addr), name)| (name.as_str(), addr - label_addr))
+    }
+
+    fn parse_line(&mut self, line: &str) {
+        let line = line.split(';').next().unwrap_or("").trim();
+        let mut parts = line.split_whitespace();
+        let (location, name) = match (parts.next(), parts.next()) {
+            (Some(location), Some(name)) => (location, name),
+            _ => return,
+        };
+
+        let mut location_parts = location.split(':');
+        let (bank, addr) = match (location_parts.next(), location_parts.next()) {
+            (Some(bank), Some(addr)) => (bank, addr),
+            _ => return,
+        };
+
+        if let (Ok(bank), Ok(addr)) = (u16::from_str_radix(bank, 16), u16::from_str_radix(addr, 16)) {
+            // first label wins when several share an address, usually the global one
+            self.labels.entry((bank, addr)).or_insert_with(|| name.to_owned());
+            self.addresses.insert(name.to_owned(), (bank, addr));
+        }
+    }
+}
+
+// Bank RGBDS assigns to an address, given the currently mapped ROM and RAM banks
+pub fn bank_for(addr: u16, rom_bank: u16, ram_bank: u16) -> u16 {
+    match addr {
+        0x4000...0x7FFF => rom_bank,
+        0xA000...0xBFFF => ram_bank,
+        0xD000...0xDFFF => 1,
+        _ => 0,
+    }
+}
+
+fn region(addr: u16) -> u8 {
+    match addr {
+        0x0000...0x3FFF => 0, // ROM0
+        0x4000...0x7FFF => 1, // ROMX
+        0x8000...0x9FFF => 2, // VRAM
+        0xA000...0xBFFF => 3, // SRAM
+        0xC000...0xCFFF => 4, // WRAM0
+        0xD000...0xDFFF => 5, // WRAMX
+        0xFE00...0xFE9F => 6, // OAM
+        0xFF80...0xFFFE => 7, // HRAM
+        _ => 8,
+    }
+}
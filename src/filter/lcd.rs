@@ -0,0 +1,22 @@
+use filter::Image;
+
+const SCALE: u32 = 3;
+
+// Draws each pixel 3x3 with its bottom row and right column at 3/4 brightness, so there's a grid
+// between the pixels like the gaps in the DMG's dot matrix
+pub fn grid(image: &Image) -> Image {
+    let mut out = Image::new(image.width * SCALE, image.height * SCALE);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let colour = image.get(x as i32, y as i32);
+            let darker = [colour[0] / 4 * 3, colour[1] / 4 * 3, colour[2] / 4 * 3];
+            for dy in 0..SCALE {
+                for dx in 0..SCALE {
+                    let edge = dx == SCALE - 1 || dy == SCALE - 1;
+                    out.set(x * SCALE + dx, y * SCALE + dy, if edge { darker } else { colour });
+                }
+            }
+        }
+    }
+    out
+}
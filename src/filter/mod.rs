@@ -0,0 +1,167 @@
+mod lcd;
+mod scale;
+mod xbr;
+
+use screen::Screen;
+use std::str::FromStr;
+
+// Post-processing done on the frame before it's drawn to the window. Each filter takes the output
+// of the one before, so they're picked as a list like `blend,scale2x`.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Filter {
+    Scale2x,
+    Scale3x,
+    Xbr,
+    // each pixel drawn 3x3 with a darker line along its bottom and right, best on its own or last
+    Lcd,
+    // each frame averaged with the one before, like the DMG's slow LCD. Some games flicker sprites
+    // every other frame and count on it to make them look see-through.
+    Blend,
+}
+
+impl Filter {
+    fn scale(self) -> u32 {
+        match self {
+            Filter::Scale2x | Filter::Xbr => 2,
+            Filter::Scale3x | Filter::Lcd => 3,
+            Filter::Blend => 1,
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scale2x" => Ok(Filter::Scale2x),
+            "scale3x" => Ok(Filter::Scale3x),
+            "xbr" => Ok(Filter::Xbr),
+            "lcd" => Ok(Filter::Lcd),
+            "blend" => Ok(Filter::Blend),
+            _ => Err(format!(
+                "Unknown filter {}, expected scale2x, scale3x, xbr, lcd or blend",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Filters {
+    filters: Vec<Filter>,
+    // what each filter was given last, only kept for blending
+    previous: Vec<Option<Image>>,
+}
+
+impl Filters {
+    pub fn none() -> Self {
+        Self {
+            filters: vec![],
+            previous: vec![],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    // How many times bigger than the Game Boy's screen the frames come out
+    pub fn scale(&self) -> u32 {
+        self.filters.iter().map(|filter| filter.scale()).product()
+    }
+
+    // Takes a 160x144 RGB frame
+    pub fn apply(&mut self, frame: &[u8]) -> Vec<u8> {
+        let mut image = Image {
+            data: frame.to_vec(),
+            width: Screen::WIDTH,
+            height: Screen::HEIGHT,
+        };
+        for (filter, previous) in self.filters.iter().zip(self.previous.iter_mut()) {
+            image = match *filter {
+                Filter::Scale2x => scale::scale2x(&image),
+                Filter::Scale3x => scale::scale3x(&image),
+                Filter::Xbr => xbr::xbr2x(&image),
+                Filter::Lcd => lcd::grid(&image),
+                Filter::Blend => {
+                    let blended = match *previous {
+                        Some(ref previous) => blend(previous, &image),
+                        None => image.clone(),
+                    };
+                    *previous = Some(image);
+                    blended
+                }
+            };
+        }
+        image.data
+    }
+}
+
+impl FromStr for Filters {
+    type Err = String;
+
+    // Names split by commas, or none
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "none" {
+            return Ok(Self::none());
+        }
+        let filters = s
+            .split(',')
+            .map(|name| name.trim().parse::<Filter>())
+            .collect::<Result<Vec<Filter>, String>>()?;
+        Ok(Self {
+            previous: filters.iter().map(|_| None).collect(),
+            filters,
+        })
+    }
+}
+
+// An RGB frame at whatever size the filters before have made it
+#[derive(Clone)]
+struct Image {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+impl Image {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            data: vec![0; (width * height * 3) as usize],
+            width,
+            height,
+        }
+    }
+
+    // Pixels past the edges repeat the edge
+    fn get(&self, x: i32, y: i32) -> [u8; 3] {
+        let x = x.max(0).min(self.width as i32 - 1) as usize;
+        let y = y.max(0).min(self.height as i32 - 1) as usize;
+        let index = (y * self.width as usize + x) * 3;
+        [self.data[index], self.data[index + 1], self.data[index + 2]]
+    }
+
+    fn set(&mut self, x: u32, y: u32, colour: [u8; 3]) {
+        let index = ((y * self.width + x) * 3) as usize;
+        self.data[index..index + 3].copy_from_slice(&colour);
+    }
+}
+
+fn blend(previous: &Image, image: &Image) -> Image {
+    let data = previous
+        .data
+        .iter()
+        .zip(image.data.iter())
+        .map(|(&a, &b)| average(a, b))
+        .collect();
+    Image {
+        data,
+        width: image.width,
+        height: image.height,
+    }
+}
+
+fn average(a: u8, b: u8) -> u8 {
+    (u16::from(a) + u16::from(b)).div_ceil(2) as u8
+}
@@ -0,0 +1,92 @@
+use filter::Image;
+
+// https://www.scale2x.it/algorithm
+// Around each pixel E:
+//   A B C
+//   D E F
+//   G H I
+
+pub fn scale2x(image: &Image) -> Image {
+    let mut out = Image::new(image.width * 2, image.height * 2);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let (xi, yi) = (x as i32, y as i32);
+            let b = image.get(xi, yi - 1);
+            let d = image.get(xi - 1, yi);
+            let e = image.get(xi, yi);
+            let f = image.get(xi + 1, yi);
+            let h = image.get(xi, yi + 1);
+
+            let (mut e0, mut e1, mut e2, mut e3) = (e, e, e, e);
+            if b != h && d != f {
+                if d == b {
+                    e0 = d;
+                }
+                if b == f {
+                    e1 = f;
+                }
+                if d == h {
+                    e2 = d;
+                }
+                if h == f {
+                    e3 = f;
+                }
+            }
+            out.set(x * 2, y * 2, e0);
+            out.set(x * 2 + 1, y * 2, e1);
+            out.set(x * 2, y * 2 + 1, e2);
+            out.set(x * 2 + 1, y * 2 + 1, e3);
+        }
+    }
+    out
+}
+
+pub fn scale3x(image: &Image) -> Image {
+    let mut out = Image::new(image.width * 3, image.height * 3);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let (xi, yi) = (x as i32, y as i32);
+            let a = image.get(xi - 1, yi - 1);
+            let b = image.get(xi, yi - 1);
+            let c = image.get(xi + 1, yi - 1);
+            let d = image.get(xi - 1, yi);
+            let e = image.get(xi, yi);
+            let f = image.get(xi + 1, yi);
+            let g = image.get(xi - 1, yi + 1);
+            let h = image.get(xi, yi + 1);
+            let i = image.get(xi + 1, yi + 1);
+
+            let mut block = [e; 9];
+            if b != h && d != f {
+                if d == b {
+                    block[0] = d;
+                }
+                if (d == b && e != c) || (b == f && e != a) {
+                    block[1] = b;
+                }
+                if b == f {
+                    block[2] = f;
+                }
+                if (d == b && e != g) || (d == h && e != a) {
+                    block[3] = d;
+                }
+                if (b == f && e != i) || (h == f && e != c) {
+                    block[5] = f;
+                }
+                if d == h {
+                    block[6] = d;
+                }
+                if (d == h && e != i) || (h == f && e != g) {
+                    block[7] = h;
+                }
+                if h == f {
+                    block[8] = f;
+                }
+            }
+            for (index, &colour) in block.iter().enumerate() {
+                out.set(x * 3 + index as u32 % 3, y * 3 + index as u32 / 3, colour);
+            }
+        }
+    }
+    out
+}
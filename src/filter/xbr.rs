@@ -0,0 +1,61 @@
+use filter::{average, Image};
+
+// 2xBR, the first level of Hyllian's xBR. Each corner of a pixel E is looked at as if it were the
+// bottom right, with the pixels around it:
+//       A1 B1 C1
+//    A0 A  B  C  C4
+//    D0 D  E  F  F4
+//    G0 G  H  I  I4
+//       G5 H5 I5
+// When the pixels along H-F are more alike than the ones along E-I there's an edge across the
+// corner, so it's blended halfway towards whichever of F and H is closer to E.
+pub fn xbr2x(image: &Image) -> Image {
+    let mut out = Image::new(image.width * 2, image.height * 2);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            for &(sx, sy) in &[(-1, -1), (1, -1), (-1, 1), (1, 1)] {
+                // mirrored so the corner being filled is always the bottom right
+                let colour = corner(|dx, dy| image.get(x as i32 + dx * sx, y as i32 + dy * sy));
+                out.set(x * 2 + (sx + 1) as u32 / 2, y * 2 + (sy + 1) as u32 / 2, colour);
+            }
+        }
+    }
+    out
+}
+
+// Gets the pixels around from their offset to E
+fn corner<P: Fn(i32, i32) -> [u8; 3]>(pixel: P) -> [u8; 3] {
+    let (b, c) = (pixel(0, -1), pixel(1, -1));
+    let (d, e, f, f4) = (pixel(-1, 0), pixel(0, 0), pixel(1, 0), pixel(2, 0));
+    let (g, h, i, i4) = (pixel(-1, 1), pixel(0, 1), pixel(1, 1), pixel(2, 1));
+    let (h5, i5) = (pixel(0, 2), pixel(1, 2));
+    if e == f || e == h {
+        return e;
+    }
+
+    let along_h_f = distance(e, c) + distance(e, g) + distance(i, f4) + distance(i, h5) + 4 * distance(h, f);
+    let along_e_i = distance(h, d) + distance(h, i5) + distance(f, i4) + distance(f, b) + 4 * distance(e, i);
+    if along_h_f >= along_e_i {
+        return e;
+    }
+
+    let towards = if distance(e, f) <= distance(e, h) { f } else { h };
+    [
+        average(e[0], towards[0]),
+        average(e[1], towards[1]),
+        average(e[2], towards[2]),
+    ]
+}
+
+// How different two colours look, weighted towards brightness in YUV
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    let (r, g, b) = (
+        i32::from(a[0]) - i32::from(b[0]),
+        i32::from(a[1]) - i32::from(b[1]),
+        i32::from(a[2]) - i32::from(b[2]),
+    );
+    let y = (299 * r + 587 * g + 114 * b).unsigned_abs();
+    let u = (-169 * r - 331 * g + 500 * b).unsigned_abs();
+    let v = (500 * r - 419 * g - 81 * b).unsigned_abs();
+    (48 * y + 7 * u + 6 * v) / 1000
+}
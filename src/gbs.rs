@@ -0,0 +1,159 @@
+use cpu::CPU;
+use mbc::gbs::GbsHeader;
+use sound::build_audio_path;
+use sound::wav::WavFormat;
+use std::fs;
+use std::io::{self, BufRead};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// M-cycles in a frame of the LCD, play is called this often when the rip doesn't use the timer
+const CYCLES_PER_FRAME: u32 = 17_556;
+const VBLANK_INTERRUPT: u8 = 0x01;
+
+enum PlayerCommand {
+    Next,
+    Previous,
+    Song(u8),
+    Quit,
+}
+
+// Plays a GBS rip through the CPU and sound with the screen left off. With a length in seconds it
+// renders the song to a WAV as fast as it can, otherwise it plays it with songs picked from stdin.
+pub fn run(
+    cart_path: &str,
+    song: Option<u8>,
+    seconds: Option<u32>,
+    record_audio_path: Option<&str>,
+    audio_format: WavFormat,
+    audio_stems: bool,
+) {
+    let cart_data = match fs::read(cart_path) {
+        Ok(cart_data) => cart_data,
+        Err(e) => panic!("Failed to read {}: {}", cart_path, e),
+    };
+    let header = match GbsHeader::parse(&cart_data) {
+        Ok(header) => header,
+        Err(e) => panic!("{}", e),
+    };
+    println!("{}", header.title);
+    println!("{}", header.author);
+    println!("{}", header.copyright);
+
+    let song = song.unwrap_or(header.first_song).clamp(1, header.song_count);
+    let (mut cpu, screen_data_receiver, _) = CPU::new_headless(cart_path, seconds.is_none());
+    cpu.set_audio_recording(audio_format, audio_stems);
+
+    if let Some(seconds) = seconds {
+        let record_audio_path = record_audio_path.map_or_else(|| build_audio_path(cart_path), String::from);
+        if let Err(e) = cpu.record_audio(&record_audio_path) {
+            panic!("Failed to record audio to {}: {}", record_audio_path, e);
+        }
+        start_song(&mut cpu, &header, song);
+        let length_cycles = u64::from(seconds) * u64::from(CPU::CYCLE_SPEED);
+        let mut cycles = 0;
+        while cycles < length_cycles {
+            cycles += u64::from(run_frame(&mut cpu, &header));
+            while screen_data_receiver.try_recv().is_ok() {}
+        }
+        cpu.mmu.stop_audio_recording();
+        return;
+    }
+
+    if let Some(record_audio_path) = record_audio_path {
+        if let Err(e) = cpu.record_audio(record_audio_path) {
+            panic!("Failed to record audio to {}: {}", record_audio_path, e);
+        }
+    }
+    println!("Enter for the next song, p for the previous, a number to jump to it or q to quit");
+    let commands = read_commands();
+
+    let mut song = song;
+    start_song(&mut cpu, &header, song);
+    let frame_duration =
+        Duration::from_nanos(1_000_000_000 * u64::from(CYCLES_PER_FRAME) / u64::from(CPU::CYCLE_SPEED));
+    let mut next_frame = Instant::now();
+    loop {
+        let next_song = match commands.try_recv() {
+            Ok(PlayerCommand::Next) if song < header.song_count => Some(song + 1),
+            Ok(PlayerCommand::Next) => Some(1),
+            Ok(PlayerCommand::Previous) if song > 1 => Some(song - 1),
+            Ok(PlayerCommand::Previous) => Some(header.song_count),
+            Ok(PlayerCommand::Song(next_song)) if (1..=header.song_count).contains(&next_song) => Some(next_song),
+            Ok(PlayerCommand::Song(next_song)) => {
+                println!("No song {}, there are {}", next_song, header.song_count);
+                None
+            }
+            Ok(PlayerCommand::Quit) | Err(mpsc::TryRecvError::Disconnected) => break,
+            Err(mpsc::TryRecvError::Empty) => None,
+        };
+        if let Some(next_song) = next_song {
+            song = next_song;
+            start_song(&mut cpu, &header, song);
+        }
+
+        run_frame(&mut cpu, &header);
+        while screen_data_receiver.try_recv().is_ok() {}
+
+        next_frame += frame_duration;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
+    }
+    cpu.mmu.stop_audio_recording();
+}
+
+// Starts from power on each time, the driver at 0x100 passes A to init as the song from 0
+fn start_song(cpu: &mut CPU, header: &GbsHeader, song: u8) {
+    cpu.reset();
+    for byte in cpu.mmu.cart_ram_mut() {
+        *byte = 0;
+    }
+    cpu.reg.a = song - 1;
+    println!("Playing {}/{}", song, header.song_count);
+}
+
+// Runs a frame's worth of cycles, raising V-Blank at the end of it for rips that don't use the timer
+fn run_frame(cpu: &mut CPU, header: &GbsHeader) -> u32 {
+    let mut cycles = 0;
+    while cycles < CYCLES_PER_FRAME {
+        cycles += u32::from(cpu.run_cycle());
+    }
+    if !header.uses_timer() {
+        cpu.mmu.request_interrupt(VBLANK_INTERRUPT);
+    }
+    cycles
+}
+
+fn read_commands() -> mpsc::Receiver<PlayerCommand> {
+    let (command_sender, command_receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let command = match line.trim() {
+                "" | "n" => PlayerCommand::Next,
+                "p" => PlayerCommand::Previous,
+                "q" => PlayerCommand::Quit,
+                number => match number.parse::<u8>() {
+                    Ok(song) => PlayerCommand::Song(song),
+                    Err(_) => {
+                        println!("Unknown command {}", number);
+                        continue;
+                    }
+                },
+            };
+            if command_sender.send(command).is_err() {
+                break;
+            }
+        }
+    });
+    command_receiver
+}
@@ -1,5 +1,8 @@
+use palette::{self, Colour, Palette};
 use screen::Screen;
+use state::{StateReader, StateResult, StateWriter};
 use std::sync::mpsc;
+use video::VideoRecorder;
 
 const VIDEO_RAM_SIZE: usize = 0x2000;
 const SCREEN_PIXELS: usize = (Screen::WIDTH * Screen::HEIGHT) as usize;
@@ -10,12 +13,13 @@ pub struct GPU {
     next_screen_buffer: [u8; SCREEN_BUFFER],
     video_ram: [u8; VIDEO_RAM_SIZE],
     bg_palette: u8,
-    bg_palette_map: [(u8, u8, u8); 4],
+    bg_palette_map: [Colour; 4],
     obj_palette_0: u8,
-    obj_palette_0_map: [(u8, u8, u8); 4],
+    obj_palette_0_map: [Colour; 4],
     obj_palette_1: u8,
-    obj_palette_1_map: [(u8, u8, u8); 4],
+    obj_palette_1_map: [Colour; 4],
     oam: [u8; GPU::OAM_SIZE], // Sprite attribute table
+    palette: Palette,
     lcd_control: u8,
     stat: u8,
     scy: u8,
@@ -26,6 +30,7 @@ pub struct GPU {
     lyc: u8,
     render_clock: u32,
     screen_data_sender: mpsc::SyncSender<Vec<u8>>,
+    video: Option<VideoRecorder>,
     pub interrupt: u8,
 }
 
@@ -38,12 +43,13 @@ impl GPU {
             next_screen_buffer: [0_u8; SCREEN_BUFFER],
             video_ram: [0_u8; VIDEO_RAM_SIZE],
             bg_palette: 0,
-            bg_palette_map: build_palette_map(0),
+            bg_palette_map: build_palette_map(0, &palette::DEFAULT.bg),
             obj_palette_0: 0,
-            obj_palette_0_map: build_palette_map(0),
+            obj_palette_0_map: build_palette_map(0, &palette::DEFAULT.obj0),
             obj_palette_1: 0,
-            obj_palette_1_map: build_palette_map(0),
+            obj_palette_1_map: build_palette_map(0, &palette::DEFAULT.obj1),
             oam: [0_u8; 160],
+            palette: palette::DEFAULT,
             lcd_control: 0x91,
             stat: 0,
             scy: 0,
@@ -54,6 +60,7 @@ impl GPU {
             lyc: 0,
             render_clock: 0,
             screen_data_sender,
+            video: None,
             interrupt: 0,
         }
     }
@@ -111,15 +118,15 @@ impl GPU {
             0xFF46 => unreachable!("DMA write handled in mmu.rs"),
             0xFF47 => {
                 self.bg_palette = value;
-                self.bg_palette_map = build_palette_map(value);
+                self.bg_palette_map = build_palette_map(value, &self.palette.bg);
             }
             0xFF48 => {
                 self.obj_palette_0 = value;
-                self.obj_palette_0_map = build_palette_map(value);
+                self.obj_palette_0_map = build_palette_map(value, &self.palette.obj0);
             }
             0xFF49 => {
                 self.obj_palette_1 = value;
-                self.obj_palette_1_map = build_palette_map(value);
+                self.obj_palette_1_map = build_palette_map(value, &self.palette.obj1);
             }
             0xFF4A => self.win_y = value,
             0xFF4B => self.win_x = value,
@@ -127,6 +134,47 @@ impl GPU {
         }
     }
 
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.next_screen_pixel_palette);
+        state.write_bytes(&self.next_screen_buffer);
+        state.write_bytes(&self.video_ram);
+        state.write_bytes(&self.oam);
+        state.write_u8(self.bg_palette);
+        state.write_u8(self.obj_palette_0);
+        state.write_u8(self.obj_palette_1);
+        state.write_u8(self.lcd_control);
+        state.write_u8(self.stat);
+        state.write_u8(self.scy);
+        state.write_u8(self.scx);
+        state.write_u8(self.win_y);
+        state.write_u8(self.win_x);
+        state.write_u8(self.ly);
+        state.write_u8(self.lyc);
+        state.write_u32(self.render_clock);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        state.read_bytes(&mut self.next_screen_pixel_palette)?;
+        state.read_bytes(&mut self.next_screen_buffer)?;
+        state.read_bytes(&mut self.video_ram)?;
+        state.read_bytes(&mut self.oam)?;
+        // going through the registers rebuilds the palette maps
+        for addr in 0xFF47..=0xFF49 {
+            let value = state.read_u8()?;
+            self.write_control(addr, value);
+        }
+        self.lcd_control = state.read_u8()?;
+        self.stat = state.read_u8()?;
+        self.scy = state.read_u8()?;
+        self.scx = state.read_u8()?;
+        self.win_y = state.read_u8()?;
+        self.win_x = state.read_u8()?;
+        self.ly = state.read_u8()?;
+        self.lyc = state.read_u8()?;
+        self.render_clock = state.read_u32()?;
+        Ok(())
+    }
+
     fn process_cycles(&mut self, cycles: u8) {
         let cycles_u32 = u32::from(cycles);
         if self.render_clock + cycles_u32 >= 114 {
@@ -349,12 +397,7 @@ impl GPU {
         self.next_screen_pixel_palette[pixel_addr]
     }
 
-    fn set_pixel_color_next_screen_buffer(
-        &mut self,
-        x_pixel: u32,
-        palette_color_id: u8,
-        palette_map: &[(u8, u8, u8); 4],
-    ) {
+    fn set_pixel_color_next_screen_buffer(&mut self, x_pixel: u32, palette_color_id: u8, palette_map: &[Colour; 4]) {
         let pixel_addr = (u32::from(self.ly) * Screen::WIDTH + x_pixel) as usize;
         self.next_screen_pixel_palette[pixel_addr] = palette_color_id;
 
@@ -365,7 +408,31 @@ impl GPU {
         self.next_screen_buffer[base_buffer_addr + 2] = c3;
     }
 
-    fn render_screen(&self) {
+    // Takes effect from the next line drawn
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.bg_palette_map = build_palette_map(self.bg_palette, &palette.bg);
+        self.obj_palette_0_map = build_palette_map(self.obj_palette_0, &palette.obj0);
+        self.obj_palette_1_map = build_palette_map(self.obj_palette_1, &palette.obj1);
+    }
+
+    pub fn start_video_recording(&mut self, recorder: VideoRecorder) {
+        self.video = Some(recorder);
+    }
+
+    // Dropping the recorder finishes the file
+    pub fn stop_video_recording(&mut self) {
+        self.video = None;
+    }
+
+    pub fn is_recording_video(&self) -> bool {
+        self.video.is_some()
+    }
+
+    fn render_screen(&mut self) {
+        if let Some(ref mut video) = self.video {
+            video.frame(&self.next_screen_buffer);
+        }
         match self.screen_data_sender.send(self.next_screen_buffer.to_vec()) {
             Ok(_) => (),
             Err(e) => println!("Failed to send screen data: {}", e),
@@ -373,41 +440,11 @@ impl GPU {
     }
 }
 
-fn build_palette_map(palette_layout: u8) -> [(u8, u8, u8); 4] {
+fn build_palette_map(palette_layout: u8, shades: &[Colour; 4]) -> [Colour; 4] {
     [
-        color_from_dot_data(palette_layout & 0b11),
-        color_from_dot_data((palette_layout >> 2) & 0b11),
-        color_from_dot_data((palette_layout >> 4) & 0b11),
-        color_from_dot_data(palette_layout >> 6),
+        shades[usize::from(palette_layout & 0b11)],
+        shades[usize::from((palette_layout >> 2) & 0b11)],
+        shades[usize::from((palette_layout >> 4) & 0b11)],
+        shades[usize::from(palette_layout >> 6)],
     ]
 }
-
-// Black and white
-fn color_from_dot_data(dot_data: u8) -> (u8, u8, u8) {
-    match dot_data {
-        0b00 => (255, 255, 255), // 255
-        0b01 => (192, 192, 192), // 192
-        0b10 => (105, 106, 106), // 96
-        _ => (7, 9, 9),          // 0
-    }
-}
-
-// Attempt at original gameboy colors
-//fn color_from_dot_data(dot_data: u8) -> (u8, u8, u8) {
-//    match dot_data {
-//        0b00 => (245, 250, 239), // 255
-//        0b01 => (134, 194, 112), // 192
-//        0b10 => (47, 105, 87), // 96
-//        _ => (11, 25, 32), // 0
-//    }
-//}
-
-// Orange palette
-//fn color_from_dot_data(dot_data: u8) -> (u8, u8, u8) {
-//    match dot_data {
-//        0b00 => (252, 232, 140), // 255
-//        0b01 => (220, 180, 92), // 192
-//        0b10 => (152, 124, 60), // 96
-//        _ => (76, 60, 28), // 0
-//    }
-//}
@@ -1,3 +1,5 @@
+use movie::{Playback, Recorder};
+use state::{StateReader, StateResult, StateWriter};
 use std::fmt;
 use std::sync::mpsc;
 
@@ -13,13 +15,19 @@ pub struct Input {
     io_register: u8,
     pub interrupt: u8,
     key_data_receiver: mpsc::Receiver<Key>,
+    frame_clock: u32,
+    frame: u64,
+    recorder: Option<Recorder>,
+    playback: Option<Playback>,
 }
 
+#[derive(Copy, Clone)]
 pub struct Key {
     pub key_type: KeyType,
     pub is_down: bool,
 }
 
+#[derive(Copy, Clone, PartialEq)]
 pub enum KeyType {
     Right,
     Left,
@@ -69,9 +77,16 @@ impl Input {
             io_register: 0,
             interrupt: 0,
             key_data_receiver,
+            frame_clock: 0,
+            frame: 0,
+            recorder: None,
+            playback: None,
         }
     }
 
+    // 154 lines of 114 cycles, keys are latched once a frame whether or not the LCD is on
+    const CYCLES_PER_FRAME: u32 = 17_556;
+
     pub fn read(&self) -> u8 {
         self.io_register
     }
@@ -81,83 +96,168 @@ impl Input {
         self.update_io_register();
     }
 
-    pub fn run_cycle(&mut self) {
-        if let Ok(key) = self.key_data_receiver.try_recv() {
-            let changed = match key.key_type {
-                KeyType::Up => {
-                    if self.up.is_down == key.is_down {
-                        false
-                    } else {
-                        self.up.is_down = key.is_down;
-                        true
-                    }
-                }
-                KeyType::Down => {
-                    if self.down.is_down == key.is_down {
-                        false
-                    } else {
-                        self.down.is_down = key.is_down;
-                        true
-                    }
+    // Held keys are kept so a movie starting from a state plays back the same
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.io_register);
+        for key in self.col_0_keys().iter().chain(self.col_1_keys().iter()) {
+            state.write_bool(key.is_down);
+        }
+        state.write_u32(self.frame_clock);
+        state.write_u64(self.frame);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.io_register = state.read_u8()?;
+        for key in self.all_keys_mut().iter_mut() {
+            key.is_down = state.read_bool()?;
+        }
+        self.frame_clock = state.read_u32()?;
+        self.frame = state.read_u64()?;
+        Ok(())
+    }
+
+    pub fn start_recording(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    // Dropping the recorder writes out the rest of the movie
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    // Ends whatever movie is recording or playing
+    pub fn stop_movie(&mut self) {
+        if self.recorder.take().is_some() {
+            println!("Movie recording stopped on frame {}", self.frame);
+        }
+        if self.playback.take().is_some() {
+            println!("Movie playback stopped on frame {}", self.frame);
+        }
+    }
+
+    pub fn start_playback(&mut self, playback: Playback) {
+        self.playback = Some(playback);
+    }
+
+    pub fn is_down(&self, key_type: &KeyType) -> bool {
+        match *key_type {
+            KeyType::Up => self.up.is_down,
+            KeyType::Down => self.down.is_down,
+            KeyType::Left => self.left.is_down,
+            KeyType::Right => self.right.is_down,
+            KeyType::A => self.a.is_down,
+            KeyType::B => self.b.is_down,
+            KeyType::Select => self.select.is_down,
+            KeyType::Start => self.start.is_down,
+        }
+    }
+
+    pub fn run_cycle(&mut self, cpu_cycles: u8) {
+        self.frame_clock += u32::from(cpu_cycles);
+        if self.frame_clock < Self::CYCLES_PER_FRAME {
+            return;
+        }
+        self.frame_clock -= Self::CYCLES_PER_FRAME;
+        self.frame += 1;
+
+        // the host's keys are ignored while a movie plays
+        let host_keys: Vec<Key> = self.key_data_receiver.try_iter().collect();
+        let keys = match self.playback {
+            Some(ref mut playback) => playback.keys_for(self.frame),
+            None => host_keys,
+        };
+        if self.playback.as_ref().is_some_and(|playback| playback.is_finished()) {
+            println!("Movie finished on frame {}", self.frame);
+            self.playback = None;
+        }
+
+        for key in keys {
+            if let Some(ref mut recorder) = self.recorder {
+                recorder.record(self.frame, &key);
+            }
+            self.apply_key(key);
+        }
+    }
+
+    fn apply_key(&mut self, key: Key) {
+        let changed = match key.key_type {
+            KeyType::Up => {
+                if self.up.is_down == key.is_down {
+                    false
+                } else {
+                    self.up.is_down = key.is_down;
+                    true
                 }
-                KeyType::Left => {
-                    if self.left.is_down == key.is_down {
-                        false
-                    } else {
-                        self.left.is_down = key.is_down;
-                        true
-                    }
+            }
+            KeyType::Down => {
+                if self.down.is_down == key.is_down {
+                    false
+                } else {
+                    self.down.is_down = key.is_down;
+                    true
                 }
-                KeyType::Right => {
-                    if self.right.is_down == key.is_down {
-                        false
-                    } else {
-                        self.right.is_down = key.is_down;
-                        true
-                    }
+            }
+            KeyType::Left => {
+                if self.left.is_down == key.is_down {
+                    false
+                } else {
+                    self.left.is_down = key.is_down;
+                    true
                 }
-                KeyType::A => {
-                    if self.a.is_down == key.is_down {
-                        false
-                    } else {
-                        self.a.is_down = key.is_down;
-                        true
-                    }
+            }
+            KeyType::Right => {
+                if self.right.is_down == key.is_down {
+                    false
+                } else {
+                    self.right.is_down = key.is_down;
+                    true
                 }
-                KeyType::B => {
-                    if self.b.is_down == key.is_down {
-                        false
-                    } else {
-                        self.b.is_down = key.is_down;
-                        true
-                    }
+            }
+            KeyType::A => {
+                if self.a.is_down == key.is_down {
+                    false
+                } else {
+                    self.a.is_down = key.is_down;
+                    true
                 }
-                KeyType::Select => {
-                    if self.select.is_down == key.is_down {
-                        false
-                    } else {
-                        self.select.is_down = key.is_down;
-                        true
-                    }
+            }
+            KeyType::B => {
+                if self.b.is_down == key.is_down {
+                    false
+                } else {
+                    self.b.is_down = key.is_down;
+                    true
                 }
-                KeyType::Start => {
-                    if self.start.is_down == key.is_down {
-                        false
-                    } else {
-                        self.start.is_down = key.is_down;
-                        true
-                    }
+            }
+            KeyType::Select => {
+                if self.select.is_down == key.is_down {
+                    false
+                } else {
+                    self.select.is_down = key.is_down;
+                    true
                 }
-            };
-
-            if changed {
-                if key.is_down {
-                    self.interrupt |= 0x10;
-                    println!("KEY DOWN: {}", key.key_type);
+            }
+            KeyType::Start => {
+                if self.start.is_down == key.is_down {
+                    false
+                } else {
+                    self.start.is_down = key.is_down;
+                    true
                 }
+            }
+        };
 
-                self.update_io_register();
+        if changed {
+            if key.is_down {
+                self.interrupt |= 0x10;
+                println!("KEY DOWN: {}", key.key_type);
             }
+
+            self.update_io_register();
         }
     }
 
@@ -195,6 +295,20 @@ impl Input {
     fn col_1_keys(&self) -> [&Key; 4] {
         [&self.right, &self.left, &self.up, &self.down]
     }
+
+    // in the same order as col_0_keys then col_1_keys
+    fn all_keys_mut(&mut self) -> [&mut Key; 8] {
+        [
+            &mut self.a,
+            &mut self.b,
+            &mut self.select,
+            &mut self.start,
+            &mut self.right,
+            &mut self.left,
+            &mut self.up,
+            &mut self.down,
+        ]
+    }
 }
 
 impl fmt::Display for KeyType {
@@ -213,6 +327,20 @@ impl fmt::Display for KeyType {
 }
 
 impl KeyType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "right" => Some(KeyType::Right),
+            "left" => Some(KeyType::Left),
+            "up" => Some(KeyType::Up),
+            "down" => Some(KeyType::Down),
+            "a" => Some(KeyType::A),
+            "b" => Some(KeyType::B),
+            "select" => Some(KeyType::Select),
+            "start" => Some(KeyType::Start),
+            _ => None,
+        }
+    }
+
     pub fn value(&self) -> u8 {
         // Input values have been incorrectly reordered, this shouldn't work, but it does
         match *self {
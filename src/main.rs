@@ -10,63 +10,220 @@
 //#![allow(print_stdout)]
 
 extern crate cpal;
+#[cfg(feature = "video")]
+extern crate gif;
+#[cfg(feature = "gamepad")]
+extern crate gilrs;
 extern crate glium;
 extern crate glutin;
-#[cfg(feature = "frame-capture")]
+#[cfg(any(
+    feature = "camera",
+    feature = "printer",
+    feature = "scripting",
+    feature = "screenshot"
+))]
 extern crate image;
+#[cfg(feature = "video")]
+extern crate png;
+#[cfg(feature = "scripting")]
+extern crate rhai;
 
+mod bindings;
 mod clock;
 mod cpu;
 #[cfg(feature = "debugger")]
 mod debugger;
+mod filter;
+mod gbs;
 mod gpu;
 mod input;
 mod mbc;
 mod mmu;
+mod movie;
+mod options;
+mod palette;
 mod register;
 mod screen;
+#[cfg(any(feature = "screenshot", feature = "scripting"))]
+mod screenshot;
+#[cfg(feature = "scripting")]
+mod scripting;
 mod serial;
 mod sound;
+mod state;
+mod test_runner;
+mod video;
+mod visualizer;
+#[cfg(any(feature = "debugger", feature = "scripting"))]
+mod watches;
 
+use bindings::Bindings;
 use cpu::CPU;
 #[cfg(feature = "debugger")]
 use debugger::Debugger;
+use movie::Movie;
+use options::{Command, Options};
+use palette::Palettes;
 use screen::Screen;
+use serial::link;
+#[cfg(feature = "printer")]
+use serial::printer::Printer;
+use serial::sink::SerialSink;
 use std::sync::mpsc;
-use std::{env, thread};
+use std::thread;
 
 fn main() {
-    let cart_path = match env::args().nth(1) {
-        Some(v) => v,
-        None => panic!("You must pass a cart path as the first argument!"),
+    let options = Options::from_args();
+
+    let bindings = match options.bindings_path {
+        Some(ref bindings_path) => Bindings::load(bindings_path),
+        None => Bindings::defaults(),
     };
 
+    let mut palettes = Palettes::presets();
+    if let Some(ref palettes_path) = options.palettes_path {
+        palettes.load(palettes_path);
+    }
+    if let Some(ref palette) = options.palette {
+        if let Err(e) = palettes.select(palette) {
+            panic!("{}", e);
+        }
+    }
+
+    match options.command {
+        Command::Test => {
+            return test_runner::run(
+                &options.cart_path,
+                options.timeout_seconds,
+                options.serial_stdout,
+                options.movie_path.as_ref().map(|movie_path| load_movie(movie_path)),
+            );
+        }
+        Command::Gbs => {
+            return gbs::run(
+                &options.cart_path,
+                options.song,
+                options.seconds,
+                options.record_audio_path.as_deref(),
+                options.audio_format,
+                options.audio_stems,
+            );
+        }
+        #[cfg(feature = "screenshot")]
+        Command::Screenshot => {
+            return screenshot::run(
+                &options.cart_path,
+                options.screenshot_frames,
+                options.screenshot_scale,
+                options.movie_path.as_ref().map(|movie_path| load_movie(movie_path)),
+                palettes,
+                &options.screenshot_path,
+            );
+        }
+        Command::PrintBindings => return bindings.print(),
+        Command::Run => (),
+    }
+
+    #[cfg(feature = "scripting")]
+    {
+        if let Some(ref script_path) = options.script_path {
+            return scripting::run(script_path, &options.cart_path);
+        }
+    }
+
     let (screen_data_sender, screen_data_receiver) = mpsc::sync_channel(1);
     let (key_data_sender, key_data_receiver) = mpsc::channel();
     let (screen_exit_sender, screen_exit_receiver) = mpsc::channel();
-    let (throttled_state_sender, throttled_state_receiver) = mpsc::channel();
+    let (control_sender, control_receiver) = mpsc::channel();
 
-    let cpu = CPU::new(
-        &cart_path,
+    let mut cpu = CPU::new(
+        &options.cart_path,
+        true,
         screen_data_sender,
         key_data_receiver,
-        throttled_state_receiver,
+        control_receiver,
         screen_exit_receiver,
     );
+    if options.audio_sync {
+        cpu.sync_to_audio();
+    }
+    // holding a direction while the game starts picks a palette like on a CGB, unless one was given
+    if options.palette.is_none() {
+        cpu.mmu.pick_palette_from_buttons(CPU::CYCLE_SPEED * 2);
+    }
+    cpu.set_palettes(palettes);
+    cpu.set_audio_recording(options.audio_format, options.audio_stems);
+    if let Some(ref record_audio_path) = options.record_audio_path {
+        if let Err(e) = cpu.record_audio(record_audio_path) {
+            panic!("Failed to record audio to {}: {}", record_audio_path, e);
+        }
+    }
+    cpu.set_video_recording(options.video_format, options.video_frame_skip);
+    if let Some(ref record_video_path) = options.record_video_path {
+        if let Err(e) = cpu.record_video(record_video_path) {
+            panic!("Failed to record video to {}: {}", record_video_path, e);
+        }
+    }
+    if let Some(ref movie_path) = options.movie_path {
+        if let Err(e) = cpu.play_movie(load_movie(movie_path)) {
+            panic!("Failed to play movie {}: {}", movie_path, e);
+        }
+    }
+    if let Some(ref record_movie_path) = options.record_movie_path {
+        if let Err(e) = cpu.record_movie(record_movie_path, true) {
+            panic!("Failed to record movie to {}: {}", record_movie_path, e);
+        }
+    }
+    if let Some((mode, ref address)) = options.link {
+        cpu.mmu.connect_link(link::open(mode, address));
+    } else if options.serial_stdout {
+        cpu.mmu.connect_link(Box::new(SerialSink::new(true).0));
+    }
+    #[cfg(feature = "camera")]
+    {
+        if let Some(ref camera_path) = options.camera_path {
+            cpu.mmu.connect_camera(mbc::camera::load_frames(camera_path));
+        }
+    }
+    #[cfg(feature = "printer")]
+    {
+        if let Some(ref printer_dir) = options.printer_dir {
+            cpu.mmu.connect_link(Box::new(Printer::new(printer_dir)));
+        }
+    }
+
+    // filtered frames go in a window a whole number of times their size, so every pixel comes out the same
+    let filter_scale = options.filters.scale();
     let screen = Screen::new(
         "Rustyboy",
-        4,
+        filter_scale * 4_u32.div_ceil(filter_scale),
         screen_data_receiver,
         key_data_sender,
-        throttled_state_sender,
+        control_sender,
+        bindings,
         screen_exit_sender,
+    )
+    .with_filters(options.filters.clone());
+
+    #[cfg(feature = "screenshot")]
+    let screen = screen.with_screenshots(
+        &options.cart_path,
+        options.screenshot_dir.clone(),
+        options.screenshot_scale,
     );
 
-    run(cpu, screen);
+    run(options, cpu, screen);
+}
+
+fn load_movie(movie_path: &str) -> Movie {
+    match Movie::load(movie_path) {
+        Ok(movie) => movie,
+        Err(e) => panic!("{}", e),
+    }
 }
 
 #[cfg(not(feature = "debugger"))]
-fn run(mut cpu: CPU, mut screen: Screen) {
+fn run(_options: Options, mut cpu: CPU, mut screen: Screen) {
     let cpu_thread = thread::spawn(move || {
         cpu.main_loop();
     });
@@ -78,9 +235,8 @@ fn run(mut cpu: CPU, mut screen: Screen) {
 }
 
 #[cfg(feature = "debugger")]
-fn run(cpu: CPU, mut screen: Screen) {
-    let debug_after_cycles = env::args().nth(2).map(|item| item.parse::<u32>().unwrap());
-    let mut debugger = Debugger::new(debug_after_cycles, cpu);
+fn run(options: Options, cpu: CPU, mut screen: Screen) {
+    let mut debugger = Debugger::new(options.debug_after_cycles, options.gdb_port, &options.cart_path, cpu);
 
     let cpu_thread = thread::spawn(move || loop {
         debugger.run();
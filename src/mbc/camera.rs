@@ -0,0 +1,385 @@
+#[cfg(feature = "camera")]
+use image;
+#[cfg(feature = "camera")]
+use image::imageops::FilterType;
+use mbc::{self, MBC};
+use state::{StateReader, StateResult, StateWriter};
+#[cfg(feature = "camera")]
+use std::fs;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+// https://gbdev.io/pandocs/Gameboy_Camera.html
+// Like an MBC3 without the RTC, but RAM bank 0x10 swaps the RAM for the camera's registers:
+// 0xA000 starts a capture and reads back whether one is running, 0xA001-0xA005 set up the
+// sensor and 0xA006-0xA035 hold the 4x4 dithering matrix, 3 thresholds per pixel.
+pub const SENSOR_WIDTH: usize = 128;
+pub const SENSOR_HEIGHT: usize = 112;
+
+const REGISTER_COUNT: usize = 0x36;
+const CAMERA_BANK: u8 = 0x10;
+const MATRIX_START: usize = 0x06;
+// captured tiles go into the first RAM bank after a 0x100 byte header
+const CAPTURE_ADDR: usize = 0x0100;
+// every camera has 16 banks of RAM, whatever the header says
+const RAM_SIZE: usize = 0x2_0000;
+
+// gain is the bottom 5 bits of 0xA001, roughly 14dB to 32dB
+const GAINS: [f64; 32] = [
+    0.880_939_0,
+    0.914_914_9,
+    0.945_749_8,
+    0.973_975_8,
+    1.000_000_0,
+    1.024_141_2,
+    1.046_653_7,
+    1.067_743_3,
+    1.087_579_3,
+    1.124_031_0,
+    1.156_891_1,
+    1.186_804_3,
+    1.214_256_1,
+    1.239_620_8,
+    1.274_383_7,
+    1.315_732_3,
+    1.352_519_0,
+    1.385_651_2,
+    1.415_789_7,
+    1.443_430_9,
+    1.468_957_4,
+    1.492_669_7,
+    1.514_808_7,
+    1.535_570_3,
+    1.555_115_9,
+    1.573_580_1,
+    1.591_076_2,
+    1.607_700_8,
+    1.623_536_6,
+    1.638_655_0,
+    1.653_118_3,
+    1.666_980_8,
+];
+const EDGE_RATIOS: [f64; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+pub struct Camera {
+    save_path: String,
+    cart_data: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    registers: [u8; REGISTER_COUNT],
+    capture_cycles: u32,
+    frames: Vec<Vec<u8>>,
+    next_frame: usize,
+    noise: u32,
+}
+
+impl Camera {
+    pub fn new(cart_path: &str, cart_data: Vec<u8>) -> Self {
+        let mut res = Self {
+            save_path: mbc::build_save_path(cart_path),
+            cart_data,
+            ram: vec![0; RAM_SIZE],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            registers: [0_u8; REGISTER_COUNT],
+            capture_cycles: 0,
+            frames: vec![],
+            next_frame: 0,
+            noise: 0x1234_5678,
+        };
+
+        res.load_ram();
+        res
+    }
+
+    // Unlike the MBC1 and MBC3, bank 0 can be mapped into 0x4000-0x7FFF
+    fn adjusted_rom_addr(&self, addr: u16) -> usize {
+        if addr < 0x4000 {
+            addr as usize
+        } else {
+            (addr as usize - 0x4000 + self.rom_bank as usize * 0x4000) % self.cart_data.len()
+        }
+    }
+
+    fn adjusted_ram_addr(&self, addr: u16) -> usize {
+        ((addr as usize & 0x1FFF) + self.ram_bank as usize * 0x2000) % self.ram.len()
+    }
+
+    fn capturing(&self) -> bool {
+        self.capture_cycles > 0
+    }
+
+    fn exposure(&self) -> u32 {
+        u32::from(self.registers[0x02]) << 8 | u32::from(self.registers[0x03])
+    }
+
+    fn read_register(&self, addr: u16) -> u8 {
+        // only the control register can be read back, it's mirrored every 0x80 bytes
+        if addr & 0x7F == 0 {
+            self.registers[0] & 0x06 | if self.capturing() { 0x01 } else { 0x00 }
+        } else {
+            0x00
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        let register = (addr & 0x7F) as usize;
+        if register >= REGISTER_COUNT {
+            return;
+        }
+
+        if register == 0 {
+            self.registers[0] = value & 0x07;
+            if value & 0x01 > 0 && !self.capturing() {
+                // M-cycles, a shorter readout without N set plus the exposure time
+                let readout = if self.registers[0x01] & 0x80 > 0 { 0 } else { 512 };
+                self.capture_cycles = 32_446 + readout + 16 * self.exposure();
+            }
+        } else {
+            self.registers[register] = value;
+        }
+    }
+
+    // Runs the sensor output through gain, exposure, edge enhancement and the dithering matrix,
+    // storing the result as 16x14 tiles
+    fn capture(&mut self) {
+        let sensor = self.read_sensor();
+        let gain = GAINS[(self.registers[0x01] & 0x1F) as usize];
+        let exposure = f64::from(self.exposure()) / f64::from(0x1000);
+        let edge_enhancement = self.registers[0x01] & 0xE0 == 0xE0;
+        let edge_ratio = EDGE_RATIOS[((self.registers[0x04] >> 4) & 0x07) as usize];
+        let invert = self.registers[0x04] & 0x08 > 0;
+
+        let pixel = |x: usize, y: usize| f64::from(sensor[y * SENSOR_WIDTH + x]) * gain * exposure;
+
+        for y in 0..SENSOR_HEIGHT {
+            for x in 0..SENSOR_WIDTH {
+                let mut value = pixel(x, y);
+                if edge_enhancement {
+                    let neighbours = pixel(x.saturating_sub(1), y)
+                        + pixel((x + 1).min(SENSOR_WIDTH - 1), y)
+                        + pixel(x, y.saturating_sub(1))
+                        + pixel(x, (y + 1).min(SENSOR_HEIGHT - 1));
+                    value += (value * 4.0 - neighbours) * edge_ratio;
+                }
+                let mut value = value.clamp(0.0, 255.0) as u8;
+                if invert {
+                    value = 255 - value;
+                }
+
+                let thresholds = MATRIX_START + ((x & 3) + (y & 3) * 4) * 3;
+                let color = if value < self.registers[thresholds] {
+                    3
+                } else if value < self.registers[thresholds + 1] {
+                    2
+                } else if value < self.registers[thresholds + 2] {
+                    1
+                } else {
+                    0
+                };
+
+                let tile = (y / 8) * (SENSOR_WIDTH / 8) + x / 8;
+                let addr = CAPTURE_ADDR + tile * 16 + (y & 7) * 2;
+                let bit = 0x80 >> (x & 7);
+                if color & 0x01 > 0 {
+                    self.ram[addr] |= bit;
+                } else {
+                    self.ram[addr] &= !bit;
+                }
+                if color & 0x02 > 0 {
+                    self.ram[addr + 1] |= bit;
+                } else {
+                    self.ram[addr + 1] &= !bit;
+                }
+            }
+        }
+    }
+
+    // The next frame from the connected images, or noise when there aren't any
+    fn read_sensor(&mut self) -> Vec<u8> {
+        if self.frames.is_empty() {
+            return (0..SENSOR_WIDTH * SENSOR_HEIGHT).map(|_| self.next_noise()).collect();
+        }
+
+        let frame = self.frames[self.next_frame].clone();
+        self.next_frame = (self.next_frame + 1) % self.frames.len();
+        frame
+    }
+
+    fn next_noise(&mut self) -> u8 {
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+        (self.noise >> 24) as u8
+    }
+
+    fn load_ram(&mut self) {
+        let path = Path::new(&self.save_path);
+        if !path.exists() {
+            return;
+        }
+
+        let mut file = File::open(path).expect("Failed to load save data!");
+        let mut new_ram: Vec<u8> = Vec::with_capacity(self.ram.len());
+        file.read_to_end(&mut new_ram).expect("Failed to read ram!");
+        new_ram.resize(self.ram.len(), 0);
+        self.ram = new_ram;
+    }
+}
+
+impl Drop for Camera {
+    fn drop(&mut self) {
+        // Don't bother handling errors here
+        if let Ok(mut file) = File::create(&self.save_path) {
+            let _ = file.write_all(self.ram.as_slice());
+        }
+    }
+}
+
+impl MBC for Camera {
+    fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000...0x7FFF => self.cart_data[self.adjusted_rom_addr(addr)],
+            0xA000...0xBFFF => {
+                if self.ram_bank & CAMERA_BANK > 0 {
+                    self.read_register(addr)
+                } else {
+                    // only writes need the RAM enabled
+                    self.ram[self.adjusted_ram_addr(addr)]
+                }
+            }
+            _ => unreachable!("Tried to read non-existent mbc address"),
+        }
+    }
+
+    fn write_byte(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000...0x1FFF => {
+                self.ram_enabled = value & 0x0F == 0x0A;
+            }
+            0x2000...0x3FFF => {
+                self.rom_bank = value & 0x3F;
+            }
+            0x4000...0x5FFF => {
+                self.ram_bank = value & 0x1F;
+            }
+            0x6000...0x7FFF => (),
+            0xA000...0xBFFF => {
+                if self.ram_bank & CAMERA_BANK > 0 {
+                    self.write_register(addr, value);
+                } else if self.ram_enabled {
+                    let adj_addr = self.adjusted_ram_addr(addr);
+                    self.ram[adj_addr] = value;
+                }
+            }
+            _ => unreachable!("Tried to write non-existent mbc address"),
+        }
+    }
+
+    fn run_cycle(&mut self, cpu_cycles: u8) {
+        if !self.capturing() {
+            return;
+        }
+
+        self.capture_cycles = self.capture_cycles.saturating_sub(u32::from(cpu_cycles));
+        if !self.capturing() {
+            self.capture();
+        }
+    }
+
+    #[cfg(feature = "camera")]
+    fn connect_camera(&mut self, frames: Vec<Vec<u8>>) {
+        self.frames = frames;
+        self.next_frame = 0;
+    }
+
+    #[cfg(feature = "debugger")]
+    fn rom_bank(&self) -> u16 {
+        u16::from(self.rom_bank)
+    }
+
+    #[cfg(feature = "debugger")]
+    fn ram_bank(&self) -> u16 {
+        u16::from(self.ram_bank)
+    }
+
+    #[cfg(any(feature = "debugger", feature = "scripting"))]
+    fn peek_ram(&self, addr: u16) -> u8 {
+        if self.ram_bank & CAMERA_BANK > 0 {
+            self.read_register(addr)
+        } else {
+            self.ram[self.adjusted_ram_addr(addr)]
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.ram_enabled);
+        state.write_u8(self.rom_bank);
+        state.write_u8(self.ram_bank);
+        state.write_bytes(&self.registers);
+        state.write_u32(self.capture_cycles);
+        state.write_u32(self.noise);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.ram_enabled = state.read_bool()?;
+        self.rom_bank = state.read_u8()?;
+        self.ram_bank = state.read_u8()?;
+        state.read_bytes(&mut self.registers)?;
+        self.capture_cycles = state.read_u32()?;
+        self.noise = state.read_u32()?;
+        Ok(())
+    }
+}
+
+// Loads a single image, or every image in a directory to be played back in order, scaled and
+// cropped to fill the sensor as greyscale
+#[cfg(feature = "camera")]
+pub fn load_frames(path: &str) -> Vec<Vec<u8>> {
+    let path = Path::new(path);
+    let mut paths = if path.is_dir() {
+        match fs::read_dir(path) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect(),
+            Err(e) => panic!("Failed to read camera directory {}: {}", path.display(), e),
+        }
+    } else {
+        vec![path.to_path_buf()]
+    };
+    paths.sort();
+
+    let frames: Vec<Vec<u8>> = paths
+        .iter()
+        .filter_map(|path| match image::open(path) {
+            Ok(image) => Some(
+                image
+                    .resize_to_fill(SENSOR_WIDTH as u32, SENSOR_HEIGHT as u32, FilterType::Triangle)
+                    .to_luma8()
+                    .into_raw(),
+            ),
+            Err(e) => {
+                println!("Skipping camera image {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+
+    if frames.is_empty() {
+        panic!("No camera images could be loaded from {}", path.display());
+    }
+    println!("Loaded {} camera image(s) from {}", frames.len(), path.display());
+    frames
+}
@@ -0,0 +1,214 @@
+use mbc::MBC;
+use state::{StateReader, StateResult, StateWriter};
+
+// http://ocremix.org/info/GBS_Format_Specification
+/*
+ 00h  "GBS"             0Ah  Play address
+ 03h  Version (1)       0Ch  Stack pointer
+ 04h  Number of songs   0Eh  Timer modulo
+ 05h  First song        0Fh  Timer control
+ 06h  Load address      10h  Title, author and copyright, 32 bytes each
+ 08h  Init address      70h  Code and data, loaded at the load address
+*/
+
+const HEADER_SIZE: usize = 0x70;
+const RAM_SIZE: usize = 0x2000;
+
+pub struct GbsHeader {
+    pub song_count: u8,
+    pub first_song: u8, // from 1
+    load_address: u16,
+    init_address: u16,
+    play_address: u16,
+    stack_pointer: u16,
+    timer_modulo: u8,
+    timer_control: u8,
+    pub title: String,
+    pub author: String,
+    pub copyright: String,
+}
+
+impl GbsHeader {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < HEADER_SIZE || &data[0..3] != b"GBS" {
+            return Err(String::from("Not a GBS file"));
+        }
+        if data[3] != 1 {
+            return Err(format!("Unsupported GBS version {}", data[3]));
+        }
+
+        let word = |offset: usize| u16::from(data[offset]) | u16::from(data[offset + 1]) << 8;
+        let text = |offset: usize| {
+            let bytes = &data[offset..offset + 32];
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).into_owned()
+        };
+
+        let header = Self {
+            song_count: data[4],
+            first_song: data[5],
+            load_address: word(0x06),
+            init_address: word(0x08),
+            play_address: word(0x0A),
+            stack_pointer: word(0x0C),
+            timer_modulo: data[0x0E],
+            timer_control: data[0x0F],
+            title: text(0x10),
+            author: text(0x30),
+            copyright: text(0x50),
+        };
+        if header.song_count == 0 {
+            return Err(String::from("GBS file has no songs"));
+        }
+        // the driver lives below the load address, and the rip has to fit in the ROM above it
+        if header.load_address < 0x400 || header.load_address >= 0x8000 {
+            return Err(format!(
+                "GBS load address 0x{:X} is outside 0x400-0x7FFF",
+                header.load_address
+            ));
+        }
+        for &(name, addr) in &[("init", header.init_address), ("play", header.play_address)] {
+            if addr < header.load_address || addr >= 0x8000 {
+                return Err(format!("GBS {} address 0x{:X} is outside the rip's ROM", name, addr));
+            }
+        }
+        Ok(header)
+    }
+
+    // Otherwise play is called on V-Blank
+    pub fn uses_timer(&self) -> bool {
+        self.timer_control & 0x04 > 0
+    }
+
+    // Sets up the hardware then calls init with the song in A, from there it halts and leaves the
+    // interrupts to call play. The rst vectors jump to the same offsets from the load address.
+    fn driver(&self) -> Vec<u8> {
+        let mut driver = vec![0_u8; 0x400];
+        for rst in (0x00..0x40).step_by(8) {
+            // jp target
+            let [target_low, target_high] = (self.load_address + rst as u16).to_le_bytes();
+            driver[rst..rst + 3].copy_from_slice(&[0xC3, target_low, target_high]);
+        }
+
+        let [play_low, play_high] = self.play_address.to_le_bytes();
+        let call_play = [0xCD, play_low, play_high, 0xD9]; // call play, reti
+        driver[0x40..0x44].copy_from_slice(&call_play); // V-Blank
+        driver[0x50..0x54].copy_from_slice(&call_play); // timer
+
+        let [sp_low, sp_high] = self.stack_pointer.to_le_bytes();
+        let [init_low, init_high] = self.init_address.to_le_bytes();
+        let interrupt = if self.uses_timer() { 0x04 } else { 0x01 };
+        // A holds the song, so the registers are written through HL
+        #[rustfmt::skip]
+        let entry = [
+            0xF3,                      // di
+            0x31, sp_low, sp_high,     // ld sp, stack pointer
+            0x21, 0x26, 0xFF,          // ld hl, NR52
+            0x36, 0x80,                // ld (hl), sound on
+            0x2E, 0x24,                // ld l, NR50
+            0x36, 0x77,                // ld (hl), full volume
+            0x2E, 0x25,                // ld l, NR51
+            0x36, 0xFF,                // ld (hl), every channel to both sides
+            0x2E, 0x40,                // ld l, LCDC
+            0x36, 0x00,                // ld (hl), LCD off
+            0x2E, 0x06,                // ld l, TMA
+            0x36, self.timer_modulo,   // ld (hl), timer modulo
+            0x2E, 0x07,                // ld l, TAC
+            0x36, self.timer_control,  // ld (hl), timer control
+            0xCD, init_low, init_high, // call init
+            0x21, 0x0F, 0xFF,          // ld hl, IF
+            0x36, 0x00,                // ld (hl), 0
+            0x2E, 0xFF,                // ld l, IE
+            0x36, interrupt,           // ld (hl), V-Blank or timer
+            0xFB,                      // ei
+            0x76,                      // halt
+            0x00,                      // nop
+            0x18, 0xFC,                // jr back to the halt
+        ];
+        driver[0x100..0x100 + entry.len()].copy_from_slice(&entry);
+        driver
+    }
+}
+
+// The rip's data laid out at its load address, with bank switching like MBC1 for anything over 32KB
+pub struct Gbs {
+    rom: Vec<u8>,
+    rom_bank: usize,
+    ram: Vec<u8>,
+}
+
+impl Gbs {
+    pub fn new(_cart_path: &str, cart_data: Vec<u8>) -> Self {
+        let header = match GbsHeader::parse(&cart_data) {
+            Ok(header) => header,
+            Err(e) => panic!("{}", e),
+        };
+
+        let load_address = header.load_address as usize;
+        let data = &cart_data[HEADER_SIZE..];
+        let size = (load_address + data.len()).max(0x8000);
+        let mut rom = vec![0xFF_u8; size.div_ceil(0x4000) * 0x4000];
+        rom[..0x400].copy_from_slice(&header.driver());
+        rom[load_address..load_address + data.len()].copy_from_slice(data);
+
+        Self {
+            rom,
+            rom_bank: 1,
+            ram: vec![0; RAM_SIZE],
+        }
+    }
+}
+
+impl MBC for Gbs {
+    fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000...0x3FFF => self.rom[addr as usize],
+            0x4000...0x7FFF => {
+                let addr = self.rom_bank * 0x4000 + (addr as usize - 0x4000);
+                self.rom.get(addr).cloned().unwrap_or(0xFF)
+            }
+            0xA000...0xBFFF => self.ram[(addr - 0xA000) as usize],
+            _ => unreachable!("Unreachable GBS read operation: 0x{:X}", addr),
+        }
+    }
+
+    fn write_byte(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x2000...0x3FFF => self.rom_bank = usize::from(value).max(1),
+            0xA000...0xBFFF => self.ram[(addr - 0xA000) as usize] = value,
+            _ => (),
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn rom_bank(&self) -> u16 {
+        self.rom_bank as u16
+    }
+
+    #[cfg(feature = "debugger")]
+    fn ram_bank(&self) -> u16 {
+        0
+    }
+
+    #[cfg(any(feature = "debugger", feature = "scripting"))]
+    fn peek_ram(&self, addr: u16) -> u8 {
+        self.ram[(addr - 0xA000) as usize]
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.rom_bank as u16);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.rom_bank = usize::from(state.read_u16()?);
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use mbc::{self, MBC};
+use state::{StateReader, StateResult, StateWriter};
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
@@ -134,4 +135,45 @@ impl MBC for MBC1 {
             _ => unreachable!("Tried to write non-existent mbc address"),
         }
     }
+
+    #[cfg(feature = "debugger")]
+    fn rom_bank(&self) -> u16 {
+        u16::from(self.rom_bank)
+    }
+
+    #[cfg(feature = "debugger")]
+    fn ram_bank(&self) -> u16 {
+        u16::from(self.ram_bank)
+    }
+
+    #[cfg(any(feature = "debugger", feature = "scripting"))]
+    fn peek_ram(&self, addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        self.ram.get(self.adjusted_ram_addr(addr)).cloned().unwrap_or(0xFF)
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.ram_enabled);
+        state.write_u8(self.rom_bank);
+        state.write_u8(self.ram_bank);
+        state.write_bool(self.rom_banking_mode);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.ram_enabled = state.read_bool()?;
+        self.rom_bank = state.read_u8()?;
+        self.ram_bank = state.read_u8()?;
+        self.rom_banking_mode = state.read_bool()?;
+        Ok(())
+    }
 }
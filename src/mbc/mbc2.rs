@@ -1,4 +1,5 @@
 use mbc::{self, MBC};
+use state::{StateReader, StateResult, StateWriter};
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
@@ -103,4 +104,41 @@ impl MBC for MBC2 {
             _ => unreachable!("Tried to write non-existent mbc address"),
         }
     }
+
+    #[cfg(feature = "debugger")]
+    fn rom_bank(&self) -> u16 {
+        u16::from(self.rom_bank)
+    }
+
+    #[cfg(feature = "debugger")]
+    fn ram_bank(&self) -> u16 {
+        0
+    }
+
+    #[cfg(any(feature = "debugger", feature = "scripting"))]
+    fn peek_ram(&self, addr: u16) -> u8 {
+        if !self.ram_enabled || addr > 0xA1FF {
+            return 0xFF;
+        }
+        self.ram[(addr & 0x1FF) as usize]
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.ram_enabled);
+        state.write_u8(self.rom_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.ram_enabled = state.read_bool()?;
+        self.rom_bank = state.read_u8()?;
+        Ok(())
+    }
 }
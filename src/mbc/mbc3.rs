@@ -1,4 +1,5 @@
 use mbc::{self, MBC};
+use state::{StateReader, StateResult, StateWriter};
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
@@ -212,4 +213,53 @@ impl MBC for MBC3 {
             _ => unreachable!("Tried to write non-existent mbc address"),
         }
     }
+
+    #[cfg(feature = "debugger")]
+    fn rom_bank(&self) -> u16 {
+        u16::from(self.rom_bank)
+    }
+
+    #[cfg(feature = "debugger")]
+    fn ram_bank(&self) -> u16 {
+        u16::from(self.ram_bank)
+    }
+
+    #[cfg(any(feature = "debugger", feature = "scripting"))]
+    fn peek_ram(&self, addr: u16) -> u8 {
+        if !self.ram_and_timer_enabled {
+            0xFF
+        } else if self.ram_bank > 0x03 {
+            self.rtc_register[(self.ram_bank - 0x08) as usize]
+        } else {
+            self.ram.get(self.adjusted_ram_addr(addr)).cloned().unwrap_or(0xFF)
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    // the RTC keeps following the host clock, only what the game has latched or written is kept
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.ram_and_timer_enabled);
+        state.write_u8(self.rom_bank);
+        state.write_u8(self.ram_bank);
+        state.write_bytes(&self.rtc_register);
+        state.write_bool(self.primed_to_latch_rtc);
+        state.write_u64(self.rtc_seconds_since_epoch);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.ram_and_timer_enabled = state.read_bool()?;
+        self.rom_bank = state.read_u8()?;
+        self.ram_bank = state.read_u8()?;
+        state.read_bytes(&mut self.rtc_register)?;
+        self.primed_to_latch_rtc = state.read_bool()?;
+        self.rtc_seconds_since_epoch = state.read_u64()?;
+        Ok(())
+    }
 }
@@ -1,12 +1,17 @@
+pub mod camera;
+pub mod gbs;
 mod mbc1;
 mod mbc2;
 mod mbc3;
 mod rom;
 
+use mbc::camera::Camera;
+use mbc::gbs::Gbs;
 use mbc::mbc1::MBC1;
 use mbc::mbc2::MBC2;
 use mbc::mbc3::MBC3;
 use mbc::rom::ROM;
+use state::{StateReader, StateResult, StateWriter};
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
@@ -34,6 +39,10 @@ use std::path::Path;
 pub fn new(cart_path: &str) -> Box<MBC> {
     let mut cart_data: Vec<u8> = Vec::new();
     load_cart(cart_path, &mut cart_data);
+    // GBS music rips don't have a cart header, they're played through a driver of their own
+    if cart_data.starts_with(b"GBS") {
+        return Box::new(Gbs::new(cart_path, cart_data));
+    }
     let cartridge_type = cart_data[0x147];
 
     let ram_size: usize = match cart_data[0x149] {
@@ -56,6 +65,7 @@ pub fn new(cart_path: &str) -> Box<MBC> {
         0x11 => Box::new(MBC3::without_ram(cart_path, cart_data)),
         0x12 => Box::new(MBC3::with_ram(cart_path, cart_data, ram_size)),
         0x13 => Box::new(MBC3::with_ram_and_battery(cart_path, cart_data, ram_size)),
+        0xFC => Box::new(Camera::new(cart_path, cart_data)),
         _ => panic!("Unknown cartridge type: 0x{:X}", cartridge_type),
     }
 }
@@ -64,6 +74,14 @@ pub fn build_save_path(cart_path: &str) -> String {
     String::from(Path::new(cart_path).with_extension("gbsave-rustyboy").to_string_lossy())
 }
 
+pub fn build_state_path(cart_path: &str, slot: u8) -> String {
+    String::from(
+        Path::new(cart_path)
+            .with_extension(format!("state{}", slot))
+            .to_string_lossy(),
+    )
+}
+
 fn load_cart(cart_path: &str, buffer: &mut Vec<u8>) {
     match File::open(cart_path).and_then(|mut file| file.read_to_end(buffer)) {
         Ok(_) => println!("ROM loaded from {}", &cart_path),
@@ -74,4 +92,23 @@ fn load_cart(cart_path: &str, buffer: &mut Vec<u8>) {
 pub trait MBC: Send {
     fn read_byte(&self, addr: u16) -> u8;
     fn write_byte(&mut self, addr: u16, value: u8);
+    // For carts with hardware that runs alongside the cpu
+    fn run_cycle(&mut self, _cpu_cycles: u8) {}
+    // Only the Pocket Camera has anything to feed images into
+    #[cfg(feature = "camera")]
+    fn connect_camera(&mut self, _frames: Vec<Vec<u8>>) {
+        println!("This cart doesn't have a camera, ignoring the camera images");
+    }
+    #[cfg(feature = "debugger")]
+    fn rom_bank(&self) -> u16;
+    #[cfg(feature = "debugger")]
+    fn ram_bank(&self) -> u16;
+    // Reads cart RAM the way the cpu would, but without panicking when it's disabled
+    #[cfg(any(feature = "debugger", feature = "scripting"))]
+    fn peek_ram(&self, addr: u16) -> u8;
+    fn ram(&self) -> &[u8];
+    fn ram_mut(&mut self) -> &mut [u8];
+    // The banking registers, the RAM is saved alongside by the MMU
+    fn save_state(&self, state: &mut StateWriter);
+    fn load_state(&mut self, state: &mut StateReader) -> StateResult<()>;
 }
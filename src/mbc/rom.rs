@@ -1,4 +1,5 @@
 use mbc::MBC;
+use state::{StateReader, StateResult, StateWriter};
 
 pub struct ROM {
     cart_data: Vec<u8>,
@@ -20,4 +21,33 @@ impl MBC for ROM {
     }
 
     fn write_byte(&mut self, _addr: u16, _value: u8) {}
+
+    #[cfg(feature = "debugger")]
+    fn rom_bank(&self) -> u16 {
+        1
+    }
+
+    #[cfg(feature = "debugger")]
+    fn ram_bank(&self) -> u16 {
+        0
+    }
+
+    #[cfg(any(feature = "debugger", feature = "scripting"))]
+    fn peek_ram(&self, _addr: u16) -> u8 {
+        0xFF
+    }
+
+    fn ram(&self) -> &[u8] {
+        &[]
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+
+    fn save_state(&self, _state: &mut StateWriter) {}
+
+    fn load_state(&mut self, _state: &mut StateReader) -> StateResult<()> {
+        Ok(())
+    }
 }
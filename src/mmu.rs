@@ -1,10 +1,20 @@
 use clock::Clock;
 use gpu::GPU;
-use input::{Input, Key};
+use input::{Input, Key, KeyType};
 use mbc::{self, MBC};
+use movie::{Playback, Recorder};
+use palette::{self, Palette};
+use serial::link::Link;
 use serial::Serial;
+use sound::scope::Visualization;
+use sound::wav::WavFormat;
 use sound::Sound;
+use state::{StateReader, StateResult, StateWriter};
+use std::io;
 use std::sync::mpsc;
+use video::VideoRecorder;
+#[cfg(any(feature = "debugger", feature = "scripting"))]
+use watches::{Access, Watches};
 
 // Gameboy only needs 0x2000 working RAM
 // In the future if CGB support is needed,
@@ -24,11 +34,21 @@ pub struct MMU {
     sound: Sound,
     interrupt_flags: u8,
     interrupt_enabled: u8,
+    // set while the CPU runs an instruction, counting the M-cycles its memory accesses have taken
+    in_instruction: bool,
+    access_cycles: u8,
+    // counts down while a palette can be picked by holding buttons, like the CGB boot ROM's logo
+    buttons_palette_cycles: u32,
+    buttons_palette: Option<&'static str>,
+    picked_palette: Option<&'static str>,
+    #[cfg(any(feature = "debugger", feature = "scripting"))]
+    pub watches: Watches,
 }
 
 impl MMU {
     pub fn new(
         cart_path: &str,
+        audio: bool,
         screen_data_sender: mpsc::SyncSender<Vec<u8>>,
         key_data_receiver: mpsc::Receiver<Key>,
     ) -> Self {
@@ -40,9 +60,16 @@ impl MMU {
             serial: Serial::new(),
             clock: Clock::new(),
             input: Input::new(key_data_receiver),
-            sound: Sound::new(),
+            sound: Sound::new(audio),
             interrupt_flags: 0,
             interrupt_enabled: 0,
+            in_instruction: false,
+            access_cycles: 0,
+            buttons_palette_cycles: 0,
+            buttons_palette: None,
+            picked_palette: None,
+            #[cfg(any(feature = "debugger", feature = "scripting"))]
+            watches: Watches::new(),
         }
     }
 
@@ -53,17 +80,116 @@ impl MMU {
 
         self.sound.run_cycle(cpu_cycles);
 
-        self.clock.run_cycle(cpu_cycles);
-        self.interrupt_flags |= self.clock.interrupt;
-        self.clock.interrupt = 0;
+        // the timer has already run for the cycles the instruction's memory accesses took
+        let clock_cycles = cpu_cycles.saturating_sub(self.access_cycles);
+        self.in_instruction = false;
+        self.access_cycles = 0;
+        self.run_clock(clock_cycles);
+        for _ in 0..self.clock.frame_sequencer_ticks {
+            self.sound.step_frame_sequencer();
+        }
+        self.clock.frame_sequencer_ticks = 0;
+
+        self.serial.run_cycle(cpu_cycles);
+        self.interrupt_flags |= self.serial.interrupt;
+        self.serial.interrupt = 0;
 
-        self.input.run_cycle();
+        self.input.run_cycle(cpu_cycles);
         self.interrupt_flags |= self.input.interrupt;
         self.input.interrupt = 0;
+
+        self.mbc.run_cycle(cpu_cycles);
+
+        if self.buttons_palette_cycles > 0 {
+            self.run_buttons_palette(cpu_cycles);
+        }
+    }
+
+    // Each memory access the instruction makes from here on takes an M-cycle, which the timer is run
+    // for before the access, so TIMA, TMA and TAC reads and writes land on the cycle they would on hardware
+    pub fn start_instruction(&mut self) {
+        self.in_instruction = true;
+        self.access_cycles = 0;
+    }
+
+    fn access_cycle(&mut self) {
+        if self.in_instruction {
+            self.access_cycles += 1;
+            self.run_clock(1);
+        }
+    }
+
+    fn run_clock(&mut self, cycles: u8) {
+        self.clock.run_cycle(cycles);
+        self.interrupt_flags |= self.clock.interrupt;
+        self.clock.interrupt = 0;
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.gpu.set_palette(palette);
+    }
+
+    // Watches the keys for a number of cycles then picks the palette for the last direction held,
+    // with A or B if either was held too. The CPU takes the pick and switches to it.
+    pub fn pick_palette_from_buttons(&mut self, cycles: u32) {
+        self.buttons_palette_cycles = cycles;
+        self.buttons_palette = None;
+    }
+
+    pub fn take_picked_palette(&mut self) -> Option<&'static str> {
+        self.picked_palette.take()
+    }
+
+    fn run_buttons_palette(&mut self, cpu_cycles: u8) {
+        let direction = [KeyType::Up, KeyType::Left, KeyType::Down, KeyType::Right]
+            .iter()
+            .find(|key_type| self.input.is_down(key_type));
+        if let Some(&direction) = direction {
+            let button = [KeyType::A, KeyType::B]
+                .iter()
+                .find(|key_type| self.input.is_down(key_type));
+            self.buttons_palette = palette::from_buttons(direction, button.cloned());
+        }
+
+        self.buttons_palette_cycles = self.buttons_palette_cycles.saturating_sub(u32::from(cpu_cycles));
+        if self.buttons_palette_cycles == 0 {
+            self.picked_palette = self.buttons_palette.take();
+        }
+    }
+
+    pub fn read_byte(&mut self, addr: u16) -> u8 {
+        self.access_cycle();
+        let value = self.read_mapped_byte(addr);
+        #[cfg(any(feature = "debugger", feature = "scripting"))]
+        self.watches.check(Access::Read, addr, value);
+        value
+    }
+
+    // Reads the byte an instruction starts at
+    pub fn fetch_opcode(&mut self, addr: u16) -> u8 {
+        self.access_cycle();
+        let value = self.read_mapped_byte(addr);
+        #[cfg(any(feature = "debugger", feature = "scripting"))]
+        self.watches.check(Access::Exec, addr, value);
+        value
+    }
+
+    // Reads without tripping watchpoints, or panicking on memory the cpu shouldn't be reading
+    #[cfg(any(feature = "debugger", feature = "scripting"))]
+    pub fn peek_byte(&self, addr: u16) -> u8 {
+        match addr {
+            0xA000...0xBFFF => self.mbc.peek_ram(addr),
+            0xFF46 | 0xFF4C...0xFF7F => 0xFF, // write only or CGB only
+            _ => self.read_mapped_byte(addr),
+        }
+    }
+
+    pub fn read_word(&mut self, addr: u16) -> u16 {
+        u16::from(self.read_byte(addr)) | (u16::from(self.read_byte(addr + 1)) << 8)
     }
 
     // http://marc.rawer.de/Gameboy/Docs/GBCPUman.pdf
-    pub fn read_byte(&self, addr: u16) -> u8 {
+    fn read_mapped_byte(&self, addr: u16) -> u8 {
         match addr {
             0x0000...0x7FFF | 0xA000...0xBFFF => self.mbc.read_byte(addr), // ROM and cart RAM
             0x8000...0x9FFF => self.gpu.read_video_ram(addr),              // Load from GPU
@@ -73,7 +199,7 @@ impl MMU {
             0xFF01...0xFF02 => self.serial.read(addr),                     // Serial read
             0xFF04...0xFF07 => self.clock.read_byte(addr),                 // read Clock values
             0xFF0F => self.interrupt_flags,                                // Interrupt flags
-            0xFF10...0xFF26 => self.sound.read_byte(addr),                 // Sound control
+            0xFF10...0xFF2F => self.sound.read_byte(addr),                 // Sound control
             0xFF30...0xFF3F => self.sound.read_byte(addr),                 // Sound wave pattern RAM
             0xFF40...0xFF4B => self.gpu.read_control(addr),
             0xFF4C...0xFF7F => panic!("MMU ERROR: Memory mapped I/O (read) (CGB only) not implemented"),
@@ -83,12 +209,12 @@ impl MMU {
         }
     }
 
-    pub fn read_word(&mut self, addr: u16) -> u16 {
-        u16::from(self.read_byte(addr)) | (u16::from(self.read_byte(addr + 1)) << 8)
-    }
-
     // http://marc.rawer.de/Gameboy/Docs/GBCPUman.pdf
     pub fn write_byte(&mut self, addr: u16, value: u8) {
+        self.access_cycle();
+        #[cfg(any(feature = "debugger", feature = "scripting"))]
+        self.watches.check(Access::Write, addr, value);
+
         match addr {
             0x0000...0x7FFF | 0xA000...0xBFFF => self.mbc.write_byte(addr, value), // ROM and cart RAM
             0x8000...0x9FFF => self.gpu.write_video_ram(addr, value),              // Write to GPU
@@ -98,7 +224,7 @@ impl MMU {
             0xFF01...0xFF02 => self.serial.write(addr, value),                     // Serial write
             0xFF04...0xFF07 => self.clock.write_byte(addr, value),                 // write Clock values
             0xFF0F => self.interrupt_flags = value,                                // Interrupt flags
-            0xFF10...0xFF26 => self.sound.write_byte(addr, value),                 // Sound control
+            0xFF10...0xFF2F => self.sound.write_byte(addr, value),                 // Sound control
             0xFF30...0xFF3F => self.sound.write_byte(addr, value),                 // Sound wave pattern RAM
             0xFF46 => self.dma_into_oam(value),
             0xFF40...0xFF45 | 0xFF47...0xFF4B => self.gpu.write_control(addr, value),
@@ -117,6 +243,83 @@ impl MMU {
         self.write_byte(addr + 1, (value >> 8) as u8);
     }
 
+    pub fn connect_link(&mut self, link: Box<dyn Link>) {
+        self.serial.connect(link);
+    }
+
+    #[cfg(feature = "camera")]
+    pub fn connect_camera(&mut self, frames: Vec<Vec<u8>>) {
+        self.mbc.connect_camera(frames);
+    }
+
+    pub fn start_recording(&mut self, recorder: Recorder) {
+        self.input.start_recording(recorder);
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.input.stop_recording();
+    }
+
+    pub fn is_audio_ahead(&self) -> bool {
+        self.sound.is_audio_ahead()
+    }
+
+    pub fn start_audio_recording(&mut self, path: &str, format: WavFormat, stems: bool) -> io::Result<()> {
+        self.sound.start_recording(path, format, stems)
+    }
+
+    pub fn stop_audio_recording(&mut self) {
+        self.sound.stop_recording();
+    }
+
+    pub fn is_recording_audio(&self) -> bool {
+        self.sound.is_recording()
+    }
+
+    pub fn start_video_recording(&mut self, recorder: VideoRecorder) {
+        self.gpu.start_video_recording(recorder);
+    }
+
+    pub fn stop_video_recording(&mut self) {
+        self.gpu.stop_video_recording();
+    }
+
+    pub fn is_recording_video(&self) -> bool {
+        self.gpu.is_recording_video()
+    }
+
+    pub fn set_channel_muted(&mut self, channel: usize, muted: bool) {
+        self.sound.set_channel_muted(channel, muted);
+    }
+
+    pub fn is_channel_muted(&self, channel: usize) -> bool {
+        self.sound.is_channel_muted(channel)
+    }
+
+    pub fn set_channel_soloed(&mut self, channel: usize, soloed: bool) {
+        self.sound.set_channel_soloed(channel, soloed);
+    }
+
+    pub fn is_channel_soloed(&self, channel: usize) -> bool {
+        self.sound.is_channel_soloed(channel)
+    }
+
+    pub fn visualize(&mut self, sender: Option<mpsc::Sender<Visualization>>) {
+        self.sound.visualize(sender);
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.input.is_recording()
+    }
+
+    pub fn stop_movie(&mut self) {
+        self.input.stop_movie();
+    }
+
+    pub fn start_playback(&mut self, playback: Playback) {
+        self.input.start_playback(playback);
+    }
+
     pub fn get_triggered_interrupts(&self) -> u8 {
         self.interrupt_flags & self.interrupt_enabled
     }
@@ -125,6 +328,80 @@ impl MMU {
         self.interrupt_flags &= !flag;
     }
 
+    pub fn request_interrupt(&mut self, flag: u8) {
+        self.interrupt_flags |= flag;
+    }
+
+    #[cfg(feature = "scripting")]
+    pub fn is_key_down(&self, key_type: &KeyType) -> bool {
+        self.input.is_down(key_type)
+    }
+
+    #[cfg(feature = "debugger")]
+    pub fn rom_bank(&self) -> u16 {
+        self.mbc.rom_bank()
+    }
+
+    #[cfg(feature = "debugger")]
+    pub fn ram_bank(&self) -> u16 {
+        self.mbc.ram_bank()
+    }
+
+    pub fn cart_ram(&self) -> &[u8] {
+        self.mbc.ram()
+    }
+
+    pub fn cart_ram_mut(&mut self) -> &mut [u8] {
+        self.mbc.ram_mut()
+    }
+
+    // The cart from the start up to the end of its header
+    pub fn cart_header(&self) -> Vec<u8> {
+        (0x0000..0x0150).map(|addr| self.mbc.read_byte(addr)).collect()
+    }
+
+    // The global checksum from the cart header, enough to tell carts apart
+    pub fn rom_checksum(&self) -> u16 {
+        u16::from(self.mbc.read_byte(0x014E)) << 8 | u16::from(self.mbc.read_byte(0x014F))
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.wram);
+        state.write_bytes(&self.hram);
+        state.write_u8(self.interrupt_flags);
+        state.write_u8(self.interrupt_enabled);
+        self.gpu.save_state(state);
+        self.serial.save_state(state);
+        self.clock.save_state(state);
+        self.input.save_state(state);
+        self.sound.save_state(state);
+        self.mbc.save_state(state);
+        state.write_u32(self.mbc.ram().len() as u32);
+        state.write_bytes(self.mbc.ram());
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        state.read_bytes(&mut self.wram)?;
+        state.read_bytes(&mut self.hram)?;
+        self.interrupt_flags = state.read_u8()?;
+        self.interrupt_enabled = state.read_u8()?;
+        self.gpu.load_state(state)?;
+        self.serial.load_state(state)?;
+        self.clock.load_state(state)?;
+        self.input.load_state(state)?;
+        self.sound.load_state(state)?;
+        self.mbc.load_state(state)?;
+        let ram_size = state.read_u32()? as usize;
+        if ram_size != self.mbc.ram().len() {
+            return Err(format!(
+                "Save state has 0x{:X} bytes of cart RAM, the cart has 0x{:X}",
+                ram_size,
+                self.mbc.ram().len()
+            ));
+        }
+        state.read_bytes(self.mbc.ram_mut())
+    }
+
     fn dma_into_oam(&mut self, dma_start: u8) {
         // DMA start can be addressed as 0x0000, 0x0100, 0x0200, etc
         let actual_dma_start = u16::from(dma_start) << 8; // turns 0x01 to 0x0100
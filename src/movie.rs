@@ -0,0 +1,189 @@
+use input::{Key, KeyType};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// A movie is the cart checksum, where the run starts from, then every key event with the frame it
+// was latched on. Events are appended as they happen, so a movie cut short by a crash still plays.
+const MAGIC: &[u8; 4] = b"RBMV";
+const VERSION: u8 = 1;
+
+const POWER_ON: u8 = 0;
+const SAVE_STATE: u8 = 1;
+const EVENT_SIZE: usize = 10;
+
+// Indexes into this are what's written for each key
+const KEY_TYPES: [KeyType; 8] = [
+    KeyType::Right,
+    KeyType::Left,
+    KeyType::Up,
+    KeyType::Down,
+    KeyType::A,
+    KeyType::B,
+    KeyType::Select,
+    KeyType::Start,
+];
+
+pub enum Start {
+    // power on with the cart RAM as it was, so a different save file can't change the run
+    PowerOn(Vec<u8>),
+    State(Vec<u8>),
+}
+
+pub struct Movie {
+    pub rom_checksum: u16,
+    pub start: Start,
+    events: Vec<(u64, Key)>,
+}
+
+pub struct Recorder {
+    path: String,
+    writer: BufWriter<File>,
+    failed: bool,
+}
+
+pub struct Playback {
+    events: Vec<(u64, Key)>,
+    position: usize,
+}
+
+impl Movie {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|e| format!("Failed to read movie from {}: {}", path, e))?;
+        if data.len() < 12 || &data[0..4] != MAGIC {
+            return Err(format!("{} isn't a rustyboy movie", path));
+        }
+        if data[4] != VERSION {
+            return Err(format!(
+                "Movie version {} isn't supported, expected {}",
+                data[4], VERSION
+            ));
+        }
+
+        let rom_checksum = u16::from_le_bytes([data[5], data[6]]);
+        let start_length = u32::from_le_bytes([data[8], data[9], data[10], data[11]]) as usize;
+        let events_start = 12 + start_length;
+        if events_start > data.len() {
+            return Err(format!("Movie {} is truncated", path));
+        }
+        let start_data = data[12..events_start].to_vec();
+        let start = match data[7] {
+            POWER_ON => Start::PowerOn(start_data),
+            SAVE_STATE => Start::State(start_data),
+            kind => return Err(format!("Movie {} has an unknown start 0x{:X}", path, kind)),
+        };
+
+        // a partly written event at the end is dropped
+        let mut events = vec![];
+        for event in data[events_start..].chunks_exact(EVENT_SIZE) {
+            let mut frame = [0_u8; 8];
+            frame.copy_from_slice(&event[0..8]);
+            let key_type = match KEY_TYPES.get(event[8] as usize) {
+                Some(&key_type) => key_type,
+                None => return Err(format!("Movie {} has an unknown key 0x{:X}", path, event[8])),
+            };
+            events.push((
+                u64::from_le_bytes(frame),
+                Key {
+                    key_type,
+                    is_down: event[9] > 0,
+                },
+            ));
+        }
+
+        Ok(Self {
+            rom_checksum,
+            start,
+            events,
+        })
+    }
+
+    pub fn into_playback(self) -> Playback {
+        Playback {
+            events: self.events,
+            position: 0,
+        }
+    }
+}
+
+impl Recorder {
+    pub fn create(path: &str, rom_checksum: u16, start: &Start) -> io::Result<Self> {
+        let (kind, start_data) = match *start {
+            Start::PowerOn(ref cart_ram) => (POWER_ON, cart_ram),
+            Start::State(ref state) => (SAVE_STATE, state),
+        };
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&rom_checksum.to_le_bytes())?;
+        writer.write_all(&[kind])?;
+        writer.write_all(&(start_data.len() as u32).to_le_bytes())?;
+        writer.write_all(start_data)?;
+
+        println!("Recording movie to {}", path);
+        Ok(Self {
+            path: path.to_owned(),
+            writer,
+            failed: false,
+        })
+    }
+
+    pub fn record(&mut self, frame: u64, key: &Key) {
+        let key_index = KEY_TYPES.iter().position(|&key_type| key_type == key.key_type).unwrap();
+        let mut event = [0_u8; EVENT_SIZE];
+        event[0..8].copy_from_slice(&frame.to_le_bytes());
+        event[8] = key_index as u8;
+        event[9] = key.is_down as u8;
+
+        if let Err(e) = self.writer.write_all(&event) {
+            // only complain once, the rest of the movie is lost either way
+            if !self.failed {
+                println!("Failed to write movie to {}: {}", self.path, e);
+                self.failed = true;
+            }
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        match self.writer.flush() {
+            Ok(_) => println!("Movie saved to {}", self.path),
+            Err(e) => println!("Failed to write movie to {}: {}", self.path, e),
+        }
+    }
+}
+
+impl Playback {
+    // The keys to apply on this frame, frames the movie has already passed are skipped over
+    pub fn keys_for(&mut self, frame: u64) -> Vec<Key> {
+        let mut keys = vec![];
+        while let Some(&(event_frame, key)) = self.events.get(self.position) {
+            if event_frame > frame {
+                break;
+            }
+            if event_frame == frame {
+                keys.push(key);
+            }
+            self.position += 1;
+        }
+        keys
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.events.len()
+    }
+}
+
+// The first of cart.1.rbm, cart.2.rbm, ... that doesn't exist yet
+pub fn build_movie_path(cart_path: &str) -> String {
+    let mut number = 1;
+    loop {
+        let path = Path::new(cart_path).with_extension(format!("{}.rbm", number));
+        if !path.exists() {
+            return String::from(path.to_string_lossy());
+        }
+        number += 1;
+    }
+}
@@ -0,0 +1,248 @@
+use filter::Filters;
+#[cfg(feature = "screenshot")]
+use screenshot::ScreenshotScale;
+use serial::link::LinkMode;
+use sound::wav::WavFormat;
+use std::env;
+use video::VideoFormat;
+
+pub struct Options {
+    pub command: Command,
+    pub cart_path: String,
+    pub link: Option<(LinkMode, String)>,
+    pub serial_stdout: bool,
+    pub timeout_seconds: u32,
+    pub bindings_path: Option<String>,
+    pub movie_path: Option<String>,
+    pub record_movie_path: Option<String>,
+    pub audio_sync: bool,
+    pub record_audio_path: Option<String>,
+    pub audio_format: WavFormat,
+    pub audio_stems: bool,
+    pub record_video_path: Option<String>,
+    pub video_format: VideoFormat,
+    pub video_frame_skip: u32,
+    // a preset or palettes file name, or a list of hex colours
+    pub palette: Option<String>,
+    pub palettes_path: Option<String>,
+    pub filters: Filters,
+    #[cfg(feature = "screenshot")]
+    pub screenshot_dir: Option<String>,
+    #[cfg(feature = "screenshot")]
+    pub screenshot_scale: ScreenshotScale,
+    // where the screenshot command saves to, and how many frames it runs first
+    #[cfg(feature = "screenshot")]
+    pub screenshot_path: String,
+    #[cfg(feature = "screenshot")]
+    pub screenshot_frames: u32,
+    pub song: Option<u8>,
+    pub seconds: Option<u32>,
+    #[cfg(feature = "debugger")]
+    pub debug_after_cycles: Option<u32>,
+    #[cfg(feature = "debugger")]
+    pub gdb_port: Option<u16>,
+    #[cfg(feature = "scripting")]
+    pub script_path: Option<String>,
+    #[cfg(feature = "printer")]
+    pub printer_dir: Option<String>,
+    #[cfg(feature = "camera")]
+    pub camera_path: Option<String>,
+}
+
+pub enum Command {
+    Run,
+    Test,
+    Gbs,
+    #[cfg(feature = "screenshot")]
+    Screenshot,
+    PrintBindings,
+}
+
+impl Options {
+    // rustyboy [--gdb <port>] [--script <path>] [--camera <image or dir>] [--printer <dir>]
+    //     [--link-listen|--link-connect <address>] [--serial-stdout] [--bindings <path>]
+    //     [--play <movie>|--record-movie <movie>] [--audio-sync]
+    //     [--record-audio <wav>] [--audio-format pcm16|float32] [--audio-stems]
+    //     [--screenshot-dir <dir>] [--screenshot-scale native|window|<scale>]
+    //     [--record <gif, png or y4m>] [--video-format gif|apng|y4m] [--record-frame-skip <frames>]
+    //     [--palette <name or colours>] [--palettes <path>] [--filter <filter,...>|none]
+    //     <cart path> [debug after cycles]
+    // rustyboy test [--timeout <seconds>] [--serial-stdout] [--play <movie>] <cart path>
+    // rustyboy gbs [--song <n>] [--seconds <seconds>] [--record-audio <wav>] [--audio-format pcm16|float32]
+    //     [--audio-stems] <gbs path>
+    // rustyboy screenshot [--frames <frames>] [--screenshot-scale native|<scale>] [--play <movie>]
+    //     [--palette <name or colours>] [--palettes <path>] <cart path> <png path>
+    // rustyboy --print-bindings [--bindings <path>]
+    pub fn from_args() -> Self {
+        let mut positional: Vec<String> = vec![];
+        let mut link = None;
+        let mut serial_stdout = false;
+        let mut timeout_seconds = 120;
+        let mut bindings_path = None;
+        let mut print_bindings = false;
+        let mut movie_path = None;
+        let mut record_movie_path = None;
+        let mut audio_sync = false;
+        let mut record_audio_path = None;
+        let mut audio_format = WavFormat::Pcm16;
+        let mut audio_stems = false;
+        let mut record_video_path = None;
+        let mut video_format = VideoFormat::Gif;
+        let mut video_frame_skip = 0;
+        let mut palette = None;
+        let mut palettes_path = None;
+        let mut filters = Filters::none();
+        #[cfg(feature = "screenshot")]
+        let mut screenshot_dir = None;
+        #[cfg(feature = "screenshot")]
+        let mut screenshot_scale = ScreenshotScale::Times(1);
+        #[cfg(feature = "screenshot")]
+        let mut screenshot_frames = 600;
+        let mut song = None;
+        let mut seconds = None;
+        #[cfg(feature = "debugger")]
+        let mut gdb_port = None;
+        #[cfg(feature = "scripting")]
+        let mut script_path = None;
+        #[cfg(feature = "printer")]
+        let mut printer_dir = None;
+        #[cfg(feature = "camera")]
+        let mut camera_path = None;
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                #[cfg(feature = "debugger")]
+                "--gdb" => gdb_port = Some(parse_flag_value(&arg, args.next())),
+                #[cfg(feature = "scripting")]
+                "--script" => script_path = Some(parse_flag_value(&arg, args.next())),
+                #[cfg(feature = "camera")]
+                "--camera" => camera_path = Some(parse_flag_value(&arg, args.next())),
+                #[cfg(feature = "printer")]
+                "--printer" => printer_dir = Some(parse_flag_value(&arg, args.next())),
+                "--link-listen" => link = Some((LinkMode::Listen, parse_flag_value(&arg, args.next()))),
+                "--link-connect" => link = Some((LinkMode::Connect, parse_flag_value(&arg, args.next()))),
+                "--serial-stdout" => serial_stdout = true,
+                "--timeout" => timeout_seconds = parse_flag_value(&arg, args.next()),
+                "--bindings" => bindings_path = Some(parse_flag_value(&arg, args.next())),
+                "--print-bindings" => print_bindings = true,
+                "--play" => movie_path = Some(parse_flag_value(&arg, args.next())),
+                "--record-movie" => record_movie_path = Some(parse_flag_value(&arg, args.next())),
+                "--audio-sync" => audio_sync = true,
+                "--record-audio" => record_audio_path = Some(parse_flag_value(&arg, args.next())),
+                "--audio-format" => audio_format = parse_flag_value(&arg, args.next()),
+                "--audio-stems" => audio_stems = true,
+                "--record" => record_video_path = Some(parse_flag_value(&arg, args.next())),
+                "--video-format" => video_format = parse_flag_value(&arg, args.next()),
+                "--record-frame-skip" => video_frame_skip = parse_flag_value(&arg, args.next()),
+                "--palette" => palette = Some(parse_flag_value(&arg, args.next())),
+                "--palettes" => palettes_path = Some(parse_flag_value(&arg, args.next())),
+                "--filter" => filters = parse_flag_value(&arg, args.next()),
+                #[cfg(feature = "screenshot")]
+                "--screenshot-dir" => screenshot_dir = Some(parse_flag_value(&arg, args.next())),
+                #[cfg(feature = "screenshot")]
+                "--screenshot-scale" => screenshot_scale = parse_flag_value(&arg, args.next()),
+                #[cfg(feature = "screenshot")]
+                "--frames" => screenshot_frames = parse_flag_value(&arg, args.next()),
+                "--song" => song = Some(parse_flag_value(&arg, args.next())),
+                "--seconds" => seconds = Some(parse_flag_value(&arg, args.next())),
+                _ if arg.starts_with("--") => panic!("Unknown flag {}", arg),
+                _ => positional.push(arg),
+            }
+        }
+
+        if movie_path.is_some() && record_movie_path.is_some() {
+            panic!("--play and --record-movie can't be used together");
+        }
+        // only one thing can be plugged into the link port
+        if link.is_some() && serial_stdout {
+            panic!("--link-listen or --link-connect and --serial-stdout can't be used together");
+        }
+        #[cfg(feature = "printer")]
+        {
+            if printer_dir.is_some() && (link.is_some() || serial_stdout) {
+                panic!("--printer can't be used with --link-listen, --link-connect or --serial-stdout");
+            }
+        }
+        // the video records its own audio
+        if record_audio_path.is_some() && record_video_path.is_some() {
+            panic!("--record-audio and --record can't be used together");
+        }
+
+        let command = match positional.first().map(|arg| arg.as_str()) {
+            Some("test") => Command::Test,
+            Some("gbs") => Command::Gbs,
+            #[cfg(feature = "screenshot")]
+            Some("screenshot") => Command::Screenshot,
+            _ if print_bindings => Command::PrintBindings,
+            _ => Command::Run,
+        };
+        if !matches!(command, Command::Run | Command::PrintBindings) {
+            positional.remove(0);
+        }
+
+        let mut positional = positional.into_iter();
+        let cart_path = match positional.next() {
+            Some(v) => v,
+            // printing the bindings doesn't need a cart
+            None if print_bindings => String::new(),
+            None => panic!("You must pass a cart path as the first argument!"),
+        };
+        #[cfg(feature = "screenshot")]
+        let screenshot_path = match command {
+            Command::Screenshot => match positional.next() {
+                Some(v) => v,
+                None => panic!("You must pass a path to save the screenshot to after the cart path!"),
+            },
+            _ => String::new(),
+        };
+
+        Self {
+            command,
+            cart_path,
+            link,
+            serial_stdout,
+            timeout_seconds,
+            bindings_path,
+            movie_path,
+            record_movie_path,
+            audio_sync,
+            record_audio_path,
+            audio_format,
+            audio_stems,
+            record_video_path,
+            video_format,
+            video_frame_skip,
+            palette,
+            palettes_path,
+            filters,
+            #[cfg(feature = "screenshot")]
+            screenshot_dir,
+            #[cfg(feature = "screenshot")]
+            screenshot_scale,
+            #[cfg(feature = "screenshot")]
+            screenshot_path,
+            #[cfg(feature = "screenshot")]
+            screenshot_frames,
+            song,
+            seconds,
+            #[cfg(feature = "debugger")]
+            debug_after_cycles: positional.next().map(|item| item.parse::<u32>().unwrap()),
+            #[cfg(feature = "debugger")]
+            gdb_port,
+            #[cfg(feature = "scripting")]
+            script_path,
+            #[cfg(feature = "printer")]
+            printer_dir,
+            #[cfg(feature = "camera")]
+            camera_path,
+        }
+    }
+}
+
+fn parse_flag_value<T: ::std::str::FromStr>(flag: &str, value: Option<String>) -> T {
+    match value.as_ref().map(|value| value.parse::<T>()) {
+        Some(Ok(value)) => value,
+        _ => panic!("{} expects a value, got {:?}", flag, value),
+    }
+}
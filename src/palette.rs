@@ -0,0 +1,416 @@
+use input::KeyType;
+use std::fs;
+
+pub type Colour = (u8, u8, u8);
+
+// The colours each shade is drawn in, lightest to darkest, for the background and window and for
+// each of the two sprite palettes
+#[derive(Copy, Clone, PartialEq)]
+pub struct Palette {
+    pub bg: [Colour; 4],
+    pub obj0: [Colour; 4],
+    pub obj1: [Colour; 4],
+}
+
+impl Palette {
+    const fn new(bg: [u32; 4], obj0: [u32; 4], obj1: [u32; 4]) -> Self {
+        Self {
+            bg: colours(bg),
+            obj0: colours(obj0),
+            obj1: colours(obj1),
+        }
+    }
+
+    const fn uniform(shades: [u32; 4]) -> Self {
+        Self::new(shades, shades, shades)
+    }
+
+    // Hex colours split by commas or spaces, 4 for everything or 12 for the background then each
+    // sprite palette, like "#E0F8D0, #88C070, #346856, #081820"
+    pub fn from_hex(list: &str) -> Result<Self, String> {
+        let colours = list
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|colour| !colour.is_empty())
+            .map(|colour| {
+                let hex = colour.trim_start_matches('#');
+                match u32::from_str_radix(hex, 16) {
+                    Ok(value) if hex.len() == 6 => Ok(colour_from_hex(value)),
+                    _ => Err(format!("Invalid colour {}, expected a hex colour like #88C070", colour)),
+                }
+            })
+            .collect::<Result<Vec<Colour>, String>>()?;
+
+        let shades = |start: usize| {
+            [
+                colours[start],
+                colours[start + 1],
+                colours[start + 2],
+                colours[start + 3],
+            ]
+        };
+        match colours.len() {
+            4 => Ok(Self {
+                bg: shades(0),
+                obj0: shades(0),
+                obj1: shades(0),
+            }),
+            12 => Ok(Self {
+                bg: shades(0),
+                obj0: shades(4),
+                obj1: shades(8),
+            }),
+            count => Err(format!("Palettes need 4 or 12 colours, got {}", count)),
+        }
+    }
+}
+
+const fn colour_from_hex(hex: u32) -> Colour {
+    ((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+}
+
+const fn colours(shades: [u32; 4]) -> [Colour; 4] {
+    [
+        colour_from_hex(shades[0]),
+        colour_from_hex(shades[1]),
+        colour_from_hex(shades[2]),
+        colour_from_hex(shades[3]),
+    ]
+}
+
+const BROWN: [u32; 4] = [0xFF_FFFF, 0xFF_AD63, 0x84_3100, 0x00_0000];
+const RED: [u32; 4] = [0xFF_FFFF, 0xFF_8584, 0x94_3A3A, 0x00_0000];
+const GREEN: [u32; 4] = [0xFF_FFFF, 0x7B_FF31, 0x00_8400, 0x00_0000];
+const BLUE: [u32; 4] = [0xFF_FFFF, 0x63_A5FF, 0x00_00FF, 0x00_0000];
+
+// The CGB boot ROM's palettes for DMG carts are named after the buttons held to pick them
+// https://gbdev.io/pandocs/Power_Up_Sequence.html#compatibility-palettes
+const PRESETS: [(&str, Palette); 17] = [
+    ("grey", Palette::uniform([0xFF_FFFF, 0xC0_C0C0, 0x69_6A6A, 0x07_0909])),
+    ("green", Palette::uniform([0xF5_FAEF, 0x86_C270, 0x2F_6957, 0x0B_1920])),
+    ("orange", Palette::uniform([0xFC_E88C, 0xDC_B45C, 0x98_7C3C, 0x4C_3C1C])),
+    ("pocket", Palette::uniform([0xC4_CFA1, 0x8B_956D, 0x4D_533C, 0x1F_1F1F])),
+    ("light", Palette::uniform([0x00_B581, 0x00_9A71, 0x00_694A, 0x00_4F3B])),
+    ("up", Palette::uniform(BROWN)),
+    ("up-a", Palette::new(RED, GREEN, BLUE)),
+    (
+        "up-b",
+        Palette::new([0xFF_E6C5, 0xCE_9C84, 0x84_6B29, 0x5A_3108], BROWN, BROWN),
+    ),
+    ("left", Palette::new(BLUE, RED, GREEN)),
+    (
+        "left-a",
+        Palette::new([0xFF_FFFF, 0x8C_8CDE, 0x52_528C, 0x00_0000], RED, BROWN),
+    ),
+    ("left-b", Palette::uniform([0xFF_FFFF, 0xA5_A5A5, 0x52_5252, 0x00_0000])),
+    ("down", Palette::uniform([0xFF_FFA5, 0xFF_9494, 0x94_94FF, 0x00_0000])),
+    ("down-a", Palette::uniform([0xFF_FFFF, 0xFF_FF00, 0xFF_0000, 0x00_0000])),
+    (
+        "down-b",
+        Palette::new([0xFF_FFFF, 0xFF_FF00, 0x7B_4A00, 0x00_0000], BLUE, GREEN),
+    ),
+    ("right", Palette::uniform([0xFF_FFFF, 0x52_FF00, 0xFF_4200, 0x00_0000])),
+    (
+        "right-a",
+        Palette::new([0xFF_FFFF, 0x7B_FF31, 0x00_63C5, 0x00_0000], RED, RED),
+    ),
+    (
+        "right-b",
+        Palette::uniform([0x00_0000, 0x00_8484, 0xFF_DE00, 0xFF_FFFF]),
+    ),
+];
+
+pub const DEFAULT: Palette = PRESETS[0].1;
+
+// The rest of the CGB boot ROM's colourization, for Nintendo's own DMG carts it sums the title and
+// looks the sum up to pick a combination of palettes
+// https://gbdev.io/pandocs/Power_Up_Sequence.html#compatibility-palettes
+const CGB_COLOURS: [[u16; 4]; 30] = [
+    [0x7FFF, 0x32BF, 0x00D0, 0x0000],
+    [0x639F, 0x4279, 0x15B0, 0x04CB],
+    [0x7FFF, 0x6E31, 0x454A, 0x0000],
+    [0x7FFF, 0x1BEF, 0x0200, 0x0000],
+    [0x7FFF, 0x421F, 0x1CF2, 0x0000],
+    [0x7FFF, 0x5294, 0x294A, 0x0000],
+    [0x7FFF, 0x03FF, 0x012F, 0x0000],
+    [0x7FFF, 0x03EF, 0x01D6, 0x0000],
+    [0x7FFF, 0x42B5, 0x3DC8, 0x0000],
+    [0x7E74, 0x03FF, 0x0180, 0x0000],
+    [0x67FF, 0x77AC, 0x1A13, 0x2D6B],
+    [0x7ED6, 0x4BFF, 0x2175, 0x0000],
+    [0x53FF, 0x4A5F, 0x7E52, 0x0000],
+    [0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0],
+    [0x03ED, 0x7FFF, 0x255F, 0x0000],
+    [0x036A, 0x021F, 0x03FF, 0x7FFF],
+    [0x7FFF, 0x01DF, 0x0112, 0x0000],
+    [0x231F, 0x035F, 0x00F2, 0x0009],
+    [0x7FFF, 0x03EA, 0x011F, 0x0000],
+    [0x299F, 0x001A, 0x000C, 0x0000],
+    [0x7FFF, 0x027F, 0x001F, 0x0000],
+    [0x7FFF, 0x03E0, 0x0206, 0x0120],
+    [0x7FFF, 0x7EEB, 0x001F, 0x7C00],
+    [0x7FFF, 0x3FFF, 0x7E00, 0x001F],
+    [0x7FFF, 0x03FF, 0x001F, 0x0000],
+    [0x03FF, 0x001F, 0x000C, 0x0000],
+    [0x7FFF, 0x033F, 0x0193, 0x0000],
+    [0x0000, 0x4200, 0x037F, 0x7FFF],
+    [0x7FFF, 0x7E8C, 0x7C00, 0x0000],
+    [0x7FFF, 0x1BEF, 0x6180, 0x0000],
+];
+
+// Which of the colours above OBJ0, OBJ1 and the background start at. The boot ROM has a few that
+// start a colour before a palette does, so they're offsets into all of them rather than palettes.
+const CGB_COMBINATIONS: [[usize; 3]; 51] = [
+    [4 * 4, 4 * 4, 29 * 4],
+    [18 * 4, 18 * 4, 18 * 4],
+    [20 * 4, 20 * 4, 20 * 4],
+    [24 * 4, 24 * 4, 24 * 4],
+    [9 * 4, 9 * 4, 9 * 4],
+    [0, 0, 0],
+    [27 * 4, 27 * 4, 27 * 4],
+    [5 * 4, 5 * 4, 5 * 4],
+    [12 * 4, 12 * 4, 12 * 4],
+    [26 * 4, 26 * 4, 26 * 4],
+    [16 * 4, 8 * 4, 8 * 4],
+    [4 * 4, 28 * 4, 28 * 4],
+    [4 * 4, 2 * 4, 2 * 4],
+    [3 * 4, 4 * 4, 4 * 4],
+    [4 * 4, 29 * 4, 29 * 4],
+    [28 * 4, 4 * 4, 28 * 4],
+    [2 * 4, 17 * 4, 2 * 4],
+    [16 * 4, 16 * 4, 8 * 4],
+    [4 * 4, 4 * 4, 7 * 4],
+    [4 * 4, 4 * 4, 18 * 4],
+    [4 * 4, 4 * 4, 20 * 4],
+    [19 * 4, 19 * 4, 9 * 4],
+    [4 * 4 - 1, 4 * 4 - 1, 11 * 4],
+    [17 * 4, 17 * 4, 2 * 4],
+    [4 * 4, 4 * 4, 2 * 4],
+    [4 * 4, 4 * 4, 3 * 4],
+    [28 * 4, 28 * 4, 0],
+    [3 * 4, 3 * 4, 0],
+    [0, 0, 4],
+    [18 * 4, 22 * 4, 18 * 4],
+    [20 * 4, 22 * 4, 20 * 4],
+    [24 * 4, 22 * 4, 24 * 4],
+    [16 * 4, 22 * 4, 8 * 4],
+    [17 * 4, 4 * 4, 13 * 4],
+    [28 * 4 - 1, 0, 14 * 4],
+    [28 * 4 - 1, 4 * 4, 15 * 4],
+    [19 * 4, 22 * 4, 9 * 4],
+    [16 * 4, 28 * 4, 10 * 4],
+    [4 * 4, 23 * 4, 28 * 4],
+    [17 * 4, 22 * 4, 2 * 4],
+    [4 * 4, 0, 2 * 4],
+    [4 * 4, 28 * 4, 3 * 4],
+    [28 * 4, 3 * 4, 0],
+    [3 * 4, 28 * 4, 4 * 4],
+    [21 * 4, 28 * 4, 4 * 4],
+    [3 * 4, 28 * 4, 0],
+    [25 * 4, 3 * 4, 28 * 4],
+    [0, 28 * 4, 8 * 4],
+    [4 * 4, 3 * 4, 28 * 4],
+    [28 * 4, 3 * 4, 6 * 4],
+    [4 * 4, 28 * 4, 29 * 4],
+];
+
+// Title sums, the ones from CGB_FIRST_DUPLICATE on are shared by more than one game so the 4th
+// letter of the title has to match as well
+const CGB_TITLE_CHECKSUMS: [u8; 94] = [
+    0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, 0x3D, 0x5C, 0x58, 0xC9, 0x3E, 0x70, 0x1D, 0x59, 0x69,
+    0x19, 0x35, 0xA8, 0x14, 0xAA, 0x75, 0x95, 0x99, 0x34, 0x6F, 0x15, 0xFF, 0x97, 0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7,
+    0xF6, 0xA2, 0x49, 0x4E, 0x43, 0x68, 0xE0, 0x8B, 0xF0, 0xCE, 0x0C, 0x29, 0xE8, 0xB7, 0x86, 0x9A, 0x52, 0x01, 0x9D,
+    0x71, 0x9C, 0xBD, 0x5D, 0x6D, 0x67, 0x3F, 0x6B, 0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A,
+    0xBF, 0x0D, 0xF4, 0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4, 0xB3,
+];
+const CGB_FIRST_DUPLICATE: usize = 65;
+const CGB_FOURTH_LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+// The combination for each title sum above, 0 is also what every other cart gets
+const CGB_TITLE_COMBINATIONS: [usize; 94] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5, 19, 36, 7, 37, 30, 44, 21, 32, 31, 20, 5, 33, 13, 14, 5, 29, 5, 18, 9, 3, 2, 26,
+    25, 25, 41, 42, 26, 45, 42, 45, 36, 38, 26, 42, 30, 41, 34, 34, 5, 42, 6, 5, 33, 25, 42, 42, 40, 2, 16, 25, 42, 42,
+    5, 0, 39, 36, 22, 25, 6, 32, 12, 36, 11, 39, 18, 39, 24, 31, 50, 17, 46, 6, 27, 0, 47, 41, 41, 0, 0, 19, 34, 23,
+    18, 29,
+];
+
+// The palette a CGB would colour a cart with, the header is the cart from 0x0000 to at least
+// 0x014F. Carts made for the CGB colour themselves there, so they don't get one.
+pub fn from_cart_header(header: &[u8]) -> Option<Palette> {
+    if header[0x0143] & 0x80 > 0 {
+        return None;
+    }
+
+    // the old licensee code, or the new one when that's 0x33
+    let nintendo = header[0x014B] == 0x01 || (header[0x014B] == 0x33 && &header[0x0144..0x0146] == b"01");
+    let combination = if nintendo {
+        let checksum = header[0x0134..0x0144]
+            .iter()
+            .fold(0_u8, |sum, &byte| sum.wrapping_add(byte));
+        let fourth_letter = header[0x0137];
+        CGB_TITLE_CHECKSUMS
+            .iter()
+            .enumerate()
+            .position(|(index, &title_checksum)| {
+                title_checksum == checksum
+                    && (index < CGB_FIRST_DUPLICATE || CGB_FOURTH_LETTERS[index - CGB_FIRST_DUPLICATE] == fourth_letter)
+            })
+            .map_or(0, |index| CGB_TITLE_COMBINATIONS[index])
+    } else {
+        0
+    };
+
+    let [obj0, obj1, bg] = CGB_COMBINATIONS[combination];
+    Some(Palette {
+        bg: cgb_shades(bg),
+        obj0: cgb_shades(obj0),
+        obj1: cgb_shades(obj1),
+    })
+}
+
+fn cgb_shades(start: usize) -> [Colour; 4] {
+    let colour = |index: usize| {
+        let rgb555 = CGB_COLOURS[index / 4][index % 4];
+        let channel = |shift: u16| ((u32::from(rgb555 >> shift & 0x1F) * 255 + 15) / 31) as u8;
+        (channel(0), channel(5), channel(10))
+    };
+    [colour(start), colour(start + 1), colour(start + 2), colour(start + 3)]
+}
+
+// The title from the cart header, up to the first byte that isn't text
+pub fn cart_title(header: &[u8]) -> String {
+    header[0x0134..0x0144]
+        .iter()
+        .take_while(|&&byte| byte == b' ' || byte.is_ascii_graphic())
+        .map(|&byte| char::from(byte))
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+// The presets in the order the hotkey goes through them, followed by any from a palettes file.
+// Until one is picked, a cart gets its own from the file or from the CGB's table.
+pub struct Palettes {
+    palettes: Vec<(String, Palette)>,
+    current: usize,
+    picked: bool,
+    // cart titles and the palette name or colours each one gets
+    games: Vec<(String, String)>,
+}
+
+impl Palettes {
+    pub fn presets() -> Self {
+        Self {
+            palettes: PRESETS
+                .iter()
+                .map(|&(name, palette)| (String::from(name), palette))
+                .collect(),
+            current: 0,
+            picked: false,
+            games: vec![],
+        }
+    }
+
+    // Lines of `name = colours`, each added after the presets or replacing the one with that name,
+    // and `game:TITLE = name or colours` for the palette a cart with that title starts with.
+    // Colours can start with # so only whole lines are comments.
+    pub fn load(&mut self, path: &str) {
+        let config = match fs::read_to_string(path) {
+            Ok(config) => config,
+            Err(e) => panic!("Failed to read palettes from {}: {}", path, e),
+        };
+
+        for (line_number, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, colours) = match line.find('=') {
+                Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+                None => panic!("{}:{}: expected `name = colours`, got {}", path, line_number + 1, line),
+            };
+            if let Some(title) = name.strip_prefix("game:") {
+                self.games.push((title.trim().to_owned(), colours.to_owned()));
+                continue;
+            }
+            match Palette::from_hex(colours) {
+                Ok(palette) => drop(self.add(name, palette)),
+                Err(e) => panic!("{}:{}: {}", path, line_number + 1, e),
+            }
+        }
+    }
+
+    pub fn current(&self) -> (&str, Palette) {
+        let (ref name, palette) = self.palettes[self.current];
+        (name, palette)
+    }
+
+    pub fn next(&mut self) -> (&str, Palette) {
+        self.current = (self.current + 1) % self.palettes.len();
+        self.current()
+    }
+
+    // Picks a palette by name, or from a list of hex colours
+    pub fn select(&mut self, name_or_colours: &str) -> Result<(), String> {
+        self.picked = true;
+        if let Some(index) = self.palettes.iter().position(|(name, _)| name == name_or_colours) {
+            self.current = index;
+            return Ok(());
+        }
+        let palette = Palette::from_hex(name_or_colours)
+            .map_err(|e| format!("Unknown palette {}, and not a list of colours: {}", name_or_colours, e))?;
+        self.current = self.add("custom", palette);
+        Ok(())
+    }
+
+    // Starts on the palette for this cart, unless one's already been picked
+    pub fn select_for_cart(&mut self, header: &[u8]) {
+        if self.picked {
+            return;
+        }
+
+        let title = cart_title(header);
+        let game = self
+            .games
+            .iter()
+            .find(|(game_title, _)| *game_title == title)
+            .map(|(_, palette)| palette.clone());
+        if let Some(palette) = game {
+            if let Err(e) = self.select(&palette) {
+                panic!("Palette for {}: {}", title, e);
+            }
+        } else if let Some(palette) = from_cart_header(header) {
+            self.current = self.add("cgb", palette);
+        }
+    }
+
+    fn add(&mut self, name: &str, palette: Palette) -> usize {
+        match self.palettes.iter().position(|(existing, _)| existing == name) {
+            Some(index) => {
+                self.palettes[index].1 = palette;
+                index
+            }
+            None => {
+                self.palettes.push((String::from(name), palette));
+                self.palettes.len() - 1
+            }
+        }
+    }
+}
+
+// Like the CGB boot ROM, a direction held on its own or with A or B picks one of its palettes
+pub fn from_buttons(direction: KeyType, button: Option<KeyType>) -> Option<&'static str> {
+    let direction = match direction {
+        KeyType::Up => "up",
+        KeyType::Left => "left",
+        KeyType::Down => "down",
+        KeyType::Right => "right",
+        _ => return None,
+    };
+    let name = match button {
+        Some(KeyType::A) => format!("{}-a", direction),
+        Some(KeyType::B) => format!("{}-b", direction),
+        _ => String::from(direction),
+    };
+    PRESETS.iter().map(|&(preset, _)| preset).find(|&preset| preset == name)
+}
@@ -1,5 +1,7 @@
 mod alu;
 
+use state::{StateReader, StateResult, StateWriter};
+
 #[derive(Copy, Clone)]
 pub struct Registers {
     // 8 bit registers
@@ -107,6 +109,25 @@ impl Registers {
         result
     }
 
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.get_af());
+        state.write_u16(self.get_bc());
+        state.write_u16(self.get_de());
+        state.write_u16(self.get_hl());
+        state.write_u16(self.sp);
+        state.write_u16(self.pc);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.set_af(state.read_u16()?);
+        self.set_bc(state.read_u16()?);
+        self.set_de(state.read_u16()?);
+        self.set_hl(state.read_u16()?);
+        self.sp = state.read_u16()?;
+        self.pc = state.read_u16()?;
+        Ok(())
+    }
+
     pub fn get_flag(&self, flag: Flags) -> bool {
         let flag_byte = flag as u8;
         self.f & flag_byte > 0
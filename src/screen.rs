@@ -1,16 +1,23 @@
+use bindings::{Action, Bindings};
+use cpu::Control;
+use filter::Filters;
+#[cfg(feature = "gamepad")]
+use gilrs;
 use glium::{self, glutin, texture, Surface};
 use glutin::dpi::LogicalSize;
-#[cfg(feature = "frame-capture")]
-use image;
-use input::{Key, KeyType};
+use input::Key;
+#[cfg(feature = "screenshot")]
+use screenshot::{self, ScreenshotScale};
+use sound::scope::Visualization;
 use std::borrow::Cow;
-#[cfg(feature = "frame-capture")]
-use std::fs::File;
-#[cfg(feature = "frame-capture")]
-use std::path::Path;
 use std::sync::mpsc;
 use std::thread;
 use std::time::{Duration, Instant};
+use visualizer;
+
+// how far a stick has to be pushed before it counts as pressed
+#[cfg(feature = "gamepad")]
+const AXIS_THRESHOLD: f32 = 0.5;
 
 pub struct Screen {
     display: glium::Display,
@@ -21,10 +28,24 @@ pub struct Screen {
     screen_exit_sender: mpsc::Sender<()>,
     last_screen_render: Instant,
     min_render_space: Duration,
-    throttled_state_sender: mpsc::Sender<bool>,
+    control_sender: mpsc::Sender<Control>,
     throttled: bool,
-    #[cfg(feature = "frame-capture")]
-    frame_id: u64,
+    bindings: Bindings,
+    held_keys: Vec<glutin::VirtualKeyCode>,
+    // the channel the sound sends to while the visualizer's shown, and the last frame of it
+    visualizer: Option<(mpsc::Receiver<Visualization>, Option<Visualization>)>,
+    filters: Filters,
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+    #[cfg(feature = "gamepad")]
+    pushed_axes: Vec<(gilrs::Axis, bool)>,
+    #[cfg(feature = "screenshot")]
+    last_frame: Vec<u8>,
+    // the largest whole scale the frame fits in the window at, as of the last draw
+    #[cfg(feature = "screenshot")]
+    window_scale: u32,
+    #[cfg(feature = "screenshot")]
+    screenshots: Option<(String, Option<String>, ScreenshotScale)>,
 }
 
 impl Screen {
@@ -36,7 +57,8 @@ impl Screen {
         scale: u32,
         screen_data_receiver: mpsc::Receiver<Vec<u8>>,
         key_data_sender: mpsc::Sender<Key>,
-        throttled_state_sender: mpsc::Sender<bool>,
+        control_sender: mpsc::Sender<Control>,
+        bindings: Bindings,
         screen_exit_sender: mpsc::Sender<()>,
     ) -> Self {
         let events_loop = glutin::EventsLoop::new();
@@ -52,16 +74,7 @@ impl Screen {
             Ok(d) => d,
             Err(e) => panic!("Failed to create display: {}", e),
         };
-        let texture = match texture::texture2d::Texture2d::empty_with_format(
-            &display,
-            texture::UncompressedFloatFormat::U8U8U8,
-            texture::MipmapsOption::NoMipmap,
-            Self::WIDTH,
-            Self::HEIGHT,
-        ) {
-            Ok(t) => t,
-            Err(e) => panic!("Failed to create texture: {}", e),
-        };
+        let texture = create_texture(&display, 1);
 
         Self {
             display,
@@ -73,12 +86,44 @@ impl Screen {
             last_screen_render: Instant::now(),
             min_render_space: Duration::new(0, 8_333_333), // 120 fps
             throttled: true,
-            throttled_state_sender,
-            #[cfg(feature = "frame-capture")]
-            frame_id: 0,
+            control_sender,
+            bindings,
+            held_keys: vec![],
+            visualizer: None,
+            filters: Filters::none(),
+            #[cfg(feature = "gamepad")]
+            gilrs: match gilrs::Gilrs::new() {
+                Ok(gilrs) => Some(gilrs),
+                Err(e) => {
+                    println!("Gamepads unavailable: {}", e);
+                    None
+                }
+            },
+            #[cfg(feature = "gamepad")]
+            pushed_axes: vec![],
+            #[cfg(feature = "screenshot")]
+            last_frame: vec![],
+            #[cfg(feature = "screenshot")]
+            window_scale: scale,
+            #[cfg(feature = "screenshot")]
+            screenshots: None,
         }
     }
 
+    // Screenshots are named after the cart and saved next to it unless there's a directory for them
+    #[cfg(feature = "screenshot")]
+    pub fn with_screenshots(mut self, cart_path: &str, dir: Option<String>, scale: ScreenshotScale) -> Self {
+        self.screenshots = Some((cart_path.to_owned(), dir, scale));
+        self
+    }
+
+    // The frames are filtered before they're drawn, so the texture's made as big as they come out
+    pub fn with_filters(mut self, filters: Filters) -> Self {
+        self.texture = create_texture(&self.display, filters.scale());
+        self.filters = filters;
+        self
+    }
+
     pub fn start_loop(&mut self) {
         self.main_screen_loop();
         let _ = self.screen_exit_sender.send(());
@@ -108,74 +153,34 @@ impl Screen {
 
     fn poll_for_window_events(&mut self) -> bool {
         let mut closed = false;
-        let mut throttled = self.throttled;
-        let key_sender = self.key_data_sender.clone();
+        let mut actions: Vec<(Action, bool)> = vec![];
+        let bindings = &self.bindings;
+        let held_keys = &mut self.held_keys;
 
         self.events_loop.poll_events(|ev| {
             if let glutin::Event::WindowEvent { event, .. } = ev {
                 match event {
                     glutin::WindowEvent::CloseRequested => closed = true,
                     glutin::WindowEvent::KeyboardInput { input, .. } => {
-                        let is_down = input.state == glutin::ElementState::Pressed;
-
-                        match input.virtual_keycode {
-                            Some(glutin::VirtualKeyCode::Up) => {
-                                let _ = key_sender.send(Key {
-                                    key_type: KeyType::Up,
-                                    is_down,
-                                });
-                            }
-                            Some(glutin::VirtualKeyCode::Down) => {
-                                let _ = key_sender.send(Key {
-                                    key_type: KeyType::Down,
-                                    is_down,
-                                });
-                            }
-                            Some(glutin::VirtualKeyCode::Left) => {
-                                let _ = key_sender.send(Key {
-                                    key_type: KeyType::Left,
-                                    is_down,
-                                });
-                            }
-                            Some(glutin::VirtualKeyCode::Right) => {
-                                let _ = key_sender.send(Key {
-                                    key_type: KeyType::Right,
-                                    is_down,
-                                });
-                            }
-                            Some(glutin::VirtualKeyCode::Z) => {
-                                let _ = key_sender.send(Key {
-                                    key_type: KeyType::A,
-                                    is_down,
-                                });
-                            }
-                            Some(glutin::VirtualKeyCode::X) => {
-                                let _ = key_sender.send(Key {
-                                    key_type: KeyType::B,
-                                    is_down,
-                                });
-                            }
-                            Some(glutin::VirtualKeyCode::C) => {
-                                let _ = key_sender.send(Key {
-                                    key_type: KeyType::Select,
-                                    is_down,
-                                });
-                            }
-                            Some(glutin::VirtualKeyCode::V) => {
-                                let _ = key_sender.send(Key {
-                                    key_type: KeyType::Start,
-                                    is_down,
-                                });
-                            }
-                            Some(glutin::VirtualKeyCode::Space) => {
-                                throttled = !is_down;
-                            }
-                            Some(glutin::VirtualKeyCode::Q) => {
-                                if input.modifiers.ctrl || input.modifiers.logo {
-                                    closed = true;
+                        let code = match input.virtual_keycode {
+                            Some(code) => code,
+                            None => return,
+                        };
+
+                        if input.state == glutin::ElementState::Pressed {
+                            // held keys repeat their press events, only the first one counts
+                            if !held_keys.contains(&code) {
+                                held_keys.push(code);
+                                let ctrl = input.modifiers.ctrl || input.modifiers.logo;
+                                for action in bindings.key_pressed(code, ctrl, input.modifiers.shift) {
+                                    actions.push((action, true));
                                 }
                             }
-                            _ => (),
+                        } else {
+                            held_keys.retain(|&held| held != code);
+                            for action in bindings.key_released(code) {
+                                actions.push((action, false));
+                            }
                         }
                     }
                     _ => (),
@@ -183,15 +188,156 @@ impl Screen {
             }
         });
 
-        if throttled != self.throttled {
-            self.throttled = throttled;
-            let _ = self.throttled_state_sender.send(self.throttled);
+        #[cfg(feature = "gamepad")]
+        self.poll_gamepad(&mut actions);
+
+        for (action, is_down) in actions {
+            closed |= self.handle_action(action, is_down);
         }
 
         closed
     }
 
+    #[cfg(feature = "gamepad")]
+    fn poll_gamepad(&mut self, actions: &mut Vec<(Action, bool)>) {
+        let gilrs = match self.gilrs {
+            Some(ref mut gilrs) => gilrs,
+            None => return,
+        };
+
+        while let Some(gilrs::Event { event, .. }) = gilrs.next_event() {
+            match event {
+                gilrs::EventType::ButtonPressed(button, _) => {
+                    for action in self.bindings.button(button) {
+                        actions.push((action, true));
+                    }
+                }
+                gilrs::EventType::ButtonReleased(button, _) => {
+                    for action in self.bindings.button(button) {
+                        actions.push((action, false));
+                    }
+                }
+                gilrs::EventType::AxisChanged(axis, value, _) => {
+                    // an axis acts like a button in each direction, pressed once past the threshold
+                    for &positive in &[true, false] {
+                        let pushed = if positive {
+                            value > AXIS_THRESHOLD
+                        } else {
+                            value < -AXIS_THRESHOLD
+                        };
+                        let was_pushed = self.pushed_axes.contains(&(axis, positive));
+                        if pushed == was_pushed {
+                            continue;
+                        }
+
+                        if pushed {
+                            self.pushed_axes.push((axis, positive));
+                        } else {
+                            self.pushed_axes.retain(|&pushed_axis| pushed_axis != (axis, positive));
+                        }
+                        for action in self.bindings.axis(axis, positive) {
+                            actions.push((action, pushed));
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // Returns true when the action closes the window
+    fn handle_action(&mut self, action: Action, is_down: bool) -> bool {
+        match action {
+            Action::Joypad(key_type) => {
+                let _ = self.key_data_sender.send(Key { key_type, is_down });
+            }
+            Action::FastForward => {
+                if self.throttled == is_down {
+                    self.throttled = !is_down;
+                    let _ = self.control_sender.send(Control::Throttle(self.throttled));
+                }
+            }
+            _ if !is_down => (),
+            Action::Pause => {
+                let _ = self.control_sender.send(Control::Pause);
+            }
+            Action::Reset => {
+                let _ = self.control_sender.send(Control::Reset);
+            }
+            Action::SaveState(slot) => {
+                let _ = self.control_sender.send(Control::SaveState(slot));
+            }
+            Action::LoadState(slot) => {
+                let _ = self.control_sender.send(Control::LoadState(slot));
+            }
+            Action::RecordMovie => {
+                let _ = self.control_sender.send(Control::RecordMovie);
+            }
+            Action::RecordAudio => {
+                let _ = self.control_sender.send(Control::RecordAudio);
+            }
+            Action::RecordVideo => {
+                let _ = self.control_sender.send(Control::RecordVideo);
+            }
+            Action::NextPalette => {
+                let _ = self.control_sender.send(Control::NextPalette);
+            }
+            Action::MuteChannel(channel) => {
+                let _ = self.control_sender.send(Control::ToggleMute(channel));
+            }
+            Action::SoloChannel(channel) => {
+                let _ = self.control_sender.send(Control::ToggleSolo(channel));
+            }
+            Action::Visualizer => {
+                let sender = if self.visualizer.is_some() {
+                    self.visualizer = None;
+                    None
+                } else {
+                    let (sender, receiver) = mpsc::channel();
+                    self.visualizer = Some((receiver, None));
+                    Some(sender)
+                };
+                let _ = self.control_sender.send(Control::Visualize(sender));
+            }
+            Action::Screenshot => self.save_screenshot(),
+            Action::Quit => return true,
+        }
+        false
+    }
+
+    #[cfg(feature = "screenshot")]
+    fn save_screenshot(&self) {
+        if self.last_frame.is_empty() {
+            return;
+        }
+
+        let (path, scale) = match self.screenshots {
+            Some((ref cart_path, ref dir, scale)) => {
+                (screenshot::build_screenshot_path(cart_path, dir.as_deref()), scale)
+            }
+            None => (screenshot::build_screenshot_path("", None), ScreenshotScale::Times(1)),
+        };
+        let scale = match scale {
+            ScreenshotScale::Window => self.window_scale,
+            ScreenshotScale::Times(scale) => scale,
+        };
+        match screenshot::save(&self.last_frame, scale, &path) {
+            Ok(_) => println!("Screenshot saved to {}", path),
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    #[cfg(not(feature = "screenshot"))]
+    fn save_screenshot(&self) {
+        println!("Screenshots need rustyboy built with the screenshot feature");
+    }
+
     fn draw_data(&mut self, data: &[u8]) {
+        #[cfg(feature = "screenshot")]
+        {
+            self.last_frame = data.to_vec();
+        }
+
         if !self.throttled {
             let now = Instant::now();
             if now.duration_since(self.last_screen_render).lt(&self.min_render_space) {
@@ -201,22 +347,34 @@ impl Screen {
             self.last_screen_render = now;
         }
 
+        let mut data = Cow::Borrowed(data);
+        if let Some((ref receiver, ref mut latest)) = self.visualizer {
+            while let Ok(visualization) = receiver.try_recv() {
+                *latest = Some(visualization);
+            }
+            if let Some(ref visualization) = *latest {
+                visualizer::draw(data.to_mut(), visualization);
+            }
+        }
+
+        if !self.filters.is_empty() {
+            data = Cow::Owned(self.filters.apply(&data));
+        }
+
+        let filter_scale = self.filters.scale();
         let raw_image_2d = glium::texture::RawImage2d {
-            data: Cow::Borrowed(data),
-            width: Self::WIDTH,
-            height: Self::HEIGHT,
+            data,
+            width: Self::WIDTH * filter_scale,
+            height: Self::HEIGHT * filter_scale,
             format: glium::texture::ClientFormat::U8U8U8,
         };
 
-        #[cfg(feature = "frame-capture")]
-        self.save_frame(data);
-
         self.texture.write(
             glium::Rect {
                 left: 0,
                 bottom: 0,
-                width: Self::WIDTH,
-                height: Self::HEIGHT,
+                width: Self::WIDTH * filter_scale,
+                height: Self::HEIGHT * filter_scale,
             },
             raw_image_2d,
         );
@@ -226,6 +384,13 @@ impl Screen {
         // I need to double the width and height as I'm developing on a retina display
         // only renders to quarter of window otherwise
 
+        #[cfg(feature = "screenshot")]
+        {
+            self.window_scale = (unsigned_width / Self::WIDTH)
+                .min(unsigned_height / Self::HEIGHT)
+                .max(1);
+        }
+
         let width = i32::from(unsigned_width as u16);
         let height = i32::from(unsigned_height as u16);
         let blit_target = glium::BlitTarget {
@@ -243,13 +408,17 @@ impl Screen {
             println!("ERROR: Failed to write to display: {}", e)
         }
     }
+}
 
-    #[cfg(feature = "frame-capture")]
-    fn save_frame(&mut self, data: &[u8]) {
-        let image = image::ImageBuffer::from_raw(Self::WIDTH, Self::HEIGHT, data.to_vec()).unwrap();
-        let image = image::DynamicImage::ImageRgb8(image);
-        let mut output = File::create(&Path::new(&format!("frames/frame-{:010}.png", self.frame_id))).unwrap();
-        self.frame_id += 1;
-        image.save(&mut output, image::ImageFormat::PNG).unwrap();
+fn create_texture(display: &glium::Display, scale: u32) -> texture::texture2d::Texture2d {
+    match texture::texture2d::Texture2d::empty_with_format(
+        display,
+        texture::UncompressedFloatFormat::U8U8U8,
+        texture::MipmapsOption::NoMipmap,
+        Screen::WIDTH * scale,
+        Screen::HEIGHT * scale,
+    ) {
+        Ok(t) => t,
+        Err(e) => panic!("Failed to create texture: {}", e),
     }
 }
@@ -0,0 +1,149 @@
+#[cfg(feature = "screenshot")]
+use cpu::CPU;
+use image;
+#[cfg(feature = "screenshot")]
+use movie::Movie;
+#[cfg(feature = "screenshot")]
+use palette::Palettes;
+use screen::Screen;
+#[cfg(feature = "screenshot")]
+use std::path::Path;
+#[cfg(feature = "screenshot")]
+use std::str::FromStr;
+#[cfg(feature = "screenshot")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// M-cycles in a frame of the LCD
+#[cfg(feature = "screenshot")]
+const CYCLES_PER_FRAME: u32 = 17_556;
+
+// Native is 1, the window's scale is the largest whole number the frame fits in it at
+#[cfg(feature = "screenshot")]
+#[derive(Copy, Clone)]
+pub enum ScreenshotScale {
+    Window,
+    Times(u32),
+}
+
+#[cfg(feature = "screenshot")]
+impl FromStr for ScreenshotScale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "window" => Ok(ScreenshotScale::Window),
+            "native" => Ok(ScreenshotScale::Times(1)),
+            _ => match s.parse::<u32>() {
+                Ok(scale) if scale > 0 => Ok(ScreenshotScale::Times(scale)),
+                _ => Err(format!(
+                    "Unknown screenshot scale {}, expected native, window or a number",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+// Saves a 160x144 RGB frame as a PNG, each pixel drawn as a scale x scale square
+pub fn save(frame: &[u8], scale: u32, path: &str) -> Result<(), String> {
+    let (width, height) = (Screen::WIDTH * scale, Screen::HEIGHT * scale);
+    let image = image::RgbImage::from_fn(width, height, |x, y| {
+        let index = ((y / scale * Screen::WIDTH + x / scale) * 3) as usize;
+        image::Rgb([frame[index], frame[index + 1], frame[index + 2]])
+    });
+    image
+        .save(path)
+        .map_err(|e| format!("Failed to save screenshot to {}: {}", path, e))
+}
+
+// Like game-20231018-142501.png next to the cart or in the given directory, the time's in UTC
+#[cfg(feature = "screenshot")]
+pub fn build_screenshot_path(cart_path: &str, dir: Option<&str>) -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let time_of_day = seconds % 86_400;
+    let stem = Path::new(cart_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("screenshot"));
+    let name = format!(
+        "{}-{:04}{:02}{:02}-{:02}{:02}{:02}",
+        stem,
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    );
+    let dir = match dir {
+        Some(dir) => Path::new(dir).to_path_buf(),
+        None => Path::new(cart_path).with_file_name(""),
+    };
+
+    // more than one a second gets numbered
+    let mut path = dir.join(format!("{}.png", name));
+    let mut number = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.png", name, number));
+        number += 1;
+    }
+    String::from(path.to_string_lossy())
+}
+
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+#[cfg(feature = "screenshot")]
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// Runs the cart without a window for a number of frames' worth of cycles then saves the last frame
+// drawn. A movie can drive the keys to get somewhere first.
+#[cfg(feature = "screenshot")]
+pub fn run(cart_path: &str, frames: u32, scale: ScreenshotScale, movie: Option<Movie>, palettes: Palettes, path: &str) {
+    let scale = match scale {
+        ScreenshotScale::Times(scale) => scale,
+        ScreenshotScale::Window => panic!("There's no window to take the scale from, pass a number instead"),
+    };
+    let (mut cpu, screen_data_receiver, _) = CPU::new_headless(cart_path, false);
+    cpu.set_palettes(palettes);
+    if let Some(movie) = movie {
+        if let Err(e) = cpu.play_movie(movie) {
+            panic!("Failed to play movie: {}", e);
+        }
+    }
+
+    let total_cycles = u64::from(frames) * u64::from(CYCLES_PER_FRAME);
+    let mut cycles: u64 = 0;
+    let mut last_frame = None;
+    while cycles < total_cycles {
+        cycles += u64::from(cpu.run_cycle());
+        while let Ok(frame) = screen_data_receiver.try_recv() {
+            last_frame = Some(frame);
+        }
+    }
+
+    match last_frame {
+        Some(frame) => match save(&frame, scale, path) {
+            Ok(_) => println!("Screenshot saved to {}", path),
+            Err(e) => panic!("{}", e),
+        },
+        None => panic!("Nothing was drawn in {} frames", frames),
+    }
+}
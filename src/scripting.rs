@@ -0,0 +1,281 @@
+use cpu::CPU;
+use input::{Key, KeyType};
+use rhai::{Blob, Dynamic, Engine, EvalAltResult, FnPtr, NativeCallContext};
+use screen::Screen;
+use screenshot;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::process;
+use std::rc::Rc;
+use std::sync::mpsc;
+use watches::{Access, Watch, WatchHit};
+
+// Cycles in a frame plus a line of slack, so stepping a frame still ends while the LCD is off
+const MAX_CYCLES_PER_FRAME: i64 = 154 * 114 + 114;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+struct Machine {
+    cpu: CPU,
+    screen_data_receiver: mpsc::Receiver<Vec<u8>>,
+    key_data_sender: mpsc::Sender<Key>,
+    frame: Vec<u8>,
+    frame_count: i64,
+    cycle_count: i64,
+    hooks: Vec<Hook>,
+}
+
+struct Hook {
+    access: Access,
+    start: u16,
+    end: u16,
+    callback: FnPtr,
+}
+
+// Runs the script against the cart without a window or audio, exiting with 1 if the script fails
+pub fn run(script_path: &str, cart_path: &str) {
+    let (cpu, screen_data_receiver, key_data_sender) = CPU::new_headless(cart_path, false);
+    let machine = Rc::new(RefCell::new(Machine {
+        cpu,
+        screen_data_receiver,
+        key_data_sender,
+        frame: vec![0xFF; (Screen::WIDTH * Screen::HEIGHT * 3) as usize],
+        frame_count: 0,
+        cycle_count: 0,
+        hooks: vec![],
+    }));
+
+    let engine = build_engine(&machine);
+    if let Err(e) = engine.run_file(PathBuf::from(script_path)) {
+        println!("Script {} failed: {}", script_path, e);
+        process::exit(1);
+    }
+}
+
+fn build_engine(machine: &Rc<RefCell<Machine>>) -> Engine {
+    let mut engine = Engine::new();
+
+    // registers, by name: a, f, b, c, d, e, h, l, af, bc, de, hl, sp, pc
+    let m = machine.clone();
+    engine.register_fn("reg", move |name: &str| -> ScriptResult<i64> {
+        let register = m.borrow().cpu.reg;
+        let value = match name {
+            "a" => u16::from(register.a),
+            "f" => register.get_af() & 0xFF,
+            "b" => u16::from(register.b),
+            "c" => u16::from(register.c),
+            "d" => u16::from(register.d),
+            "e" => u16::from(register.e),
+            "h" => u16::from(register.h),
+            "l" => u16::from(register.l),
+            "af" => register.get_af(),
+            "bc" => register.get_bc(),
+            "de" => register.get_de(),
+            "hl" => register.get_hl(),
+            "sp" => register.sp,
+            "pc" => register.pc,
+            _ => return Err(format!("Unknown register {}", name).into()),
+        };
+        Ok(i64::from(value))
+    });
+
+    let m = machine.clone();
+    engine.register_fn("set_reg", move |name: &str, value: i64| -> ScriptResult<()> {
+        let register = &mut m.borrow_mut().cpu.reg;
+        match name {
+            "a" => register.a = value as u8,
+            "f" => {
+                let a = register.a;
+                register.set_af(u16::from(a) << 8 | (value as u16 & 0xFF))
+            }
+            "b" => register.b = value as u8,
+            "c" => register.c = value as u8,
+            "d" => register.d = value as u8,
+            "e" => register.e = value as u8,
+            "h" => register.h = value as u8,
+            "l" => register.l = value as u8,
+            "af" => register.set_af(value as u16),
+            "bc" => register.set_bc(value as u16),
+            "de" => register.set_de(value as u16),
+            "hl" => register.set_hl(value as u16),
+            "sp" => register.sp = value as u16,
+            "pc" => register.pc = value as u16,
+            _ => return Err(format!("Unknown register {}", name).into()),
+        };
+        Ok(())
+    });
+
+    let m = machine.clone();
+    engine.register_fn("read_byte", move |addr: i64| -> i64 {
+        i64::from(m.borrow().cpu.mmu.peek_byte(addr as u16))
+    });
+
+    // the script's own write shouldn't show up as a watch hit
+    let m = machine.clone();
+    engine.register_fn("write_byte", move |addr: i64, value: i64| {
+        let mmu = &mut m.borrow_mut().cpu.mmu;
+        mmu.write_byte(addr as u16, value as u8);
+        mmu.watches.take_hits();
+    });
+
+    // keys go through the same channel as the window's, so they're picked up at the start of the next frame
+    let m = machine.clone();
+    engine.register_fn("press", move |key: &str| set_key(&m, key, true));
+    let m = machine.clone();
+    engine.register_fn("release", move |key: &str| set_key(&m, key, false));
+    let m = machine.clone();
+    engine.register_fn("is_pressed", move |key: &str| -> ScriptResult<bool> {
+        Ok(m.borrow().cpu.mmu.is_key_down(&key_type(key)?))
+    });
+
+    let m = machine.clone();
+    engine.register_fn("step", move |context: NativeCallContext| -> ScriptResult<()> {
+        step(&m, &context).map(|_| ())
+    });
+    let m = machine.clone();
+    engine.register_fn("frame", move |context: NativeCallContext| run_frames(&m, &context, 1));
+    let m = machine.clone();
+    engine.register_fn("frames", move |context: NativeCallContext, count: i64| {
+        run_frames(&m, &context, count)
+    });
+
+    let m = machine.clone();
+    engine.register_fn("frame_count", move || m.borrow().frame_count);
+    let m = machine.clone();
+    engine.register_fn("cycles", move || m.borrow().cycle_count);
+
+    // the last finished frame, as 160x144 RGB bytes
+    let m = machine.clone();
+    engine.register_fn("framebuffer", move || -> Blob { m.borrow().frame.clone() });
+    let m = machine.clone();
+    engine.register_fn("screenshot", move |path: &str| -> ScriptResult<()> {
+        Ok(screenshot::save(&m.borrow().frame, 1, path)?)
+    });
+    // screenshot(path, scale) draws each pixel as a scale x scale square
+    let m = machine.clone();
+    engine.register_fn("screenshot", move |path: &str, scale: i64| -> ScriptResult<()> {
+        if !(1..=16).contains(&scale) {
+            return Err(format!("Screenshot scale {} is out of range, expected 1-16", scale).into());
+        }
+        Ok(screenshot::save(&m.borrow().frame, scale as u32, path)?)
+    });
+
+    // sound channels are numbered 1-4: square 1, square 2, wave and noise
+    let m = machine.clone();
+    engine.register_fn("mute", move |channel: i64, muted: bool| -> ScriptResult<()> {
+        let channel = sound_channel(channel)?;
+        m.borrow_mut().cpu.mmu.set_channel_muted(channel, muted);
+        Ok(())
+    });
+    let m = machine.clone();
+    engine.register_fn("solo", move |channel: i64, soloed: bool| -> ScriptResult<()> {
+        let channel = sound_channel(channel)?;
+        m.borrow_mut().cpu.mmu.set_channel_soloed(channel, soloed);
+        Ok(())
+    });
+
+    // on_exec(addr, |addr| ..) runs once the instruction at addr has been executed,
+    // on_write(start, [end], |addr, value| ..) after each write into the range
+    let m = machine.clone();
+    engine.register_fn("on_exec", move |addr: i64, callback: FnPtr| {
+        add_hook(&m, Access::Exec, addr as u16, addr as u16, callback)
+    });
+    let m = machine.clone();
+    engine.register_fn("on_write", move |addr: i64, callback: FnPtr| {
+        add_hook(&m, Access::Write, addr as u16, addr as u16, callback)
+    });
+    let m = machine.clone();
+    engine.register_fn("on_write", move |start: i64, end: i64, callback: FnPtr| {
+        add_hook(&m, Access::Write, start as u16, end as u16, callback)
+    });
+
+    engine.register_fn("exit", |code: i64| process::exit(code as i32));
+
+    engine
+}
+
+// Runs a single instruction then the hooks it set off, returning whether a frame was finished
+fn step(machine: &Rc<RefCell<Machine>>, context: &NativeCallContext) -> ScriptResult<bool> {
+    let (frame_finished, calls) = {
+        let mut machine = machine.borrow_mut();
+        let cycles = machine.cpu.run_cycle();
+        machine.cycle_count += i64::from(cycles);
+
+        let frame_finished = match machine.screen_data_receiver.try_recv() {
+            Ok(frame) => {
+                machine.frame = frame;
+                machine.frame_count += 1;
+                true
+            }
+            Err(_) => false,
+        };
+
+        let hits = machine.cpu.mmu.watches.take_hits();
+        let calls: Vec<(FnPtr, WatchHit)> = hits
+            .into_iter()
+            .map(|hit| (machine.hooks[hit.id as usize].callback.clone(), hit))
+            .collect();
+        (frame_finished, calls)
+    };
+
+    // the machine is released first, hooks are free to call back into it
+    for (callback, hit) in calls {
+        let addr = i64::from(hit.addr);
+        if hit.access == Access::Exec {
+            let _ = callback.call_within_context::<Dynamic>(context, (addr,))?;
+        } else {
+            let _ = callback.call_within_context::<Dynamic>(context, (addr, i64::from(hit.value)))?;
+        }
+    }
+
+    Ok(frame_finished)
+}
+
+fn run_frames(machine: &Rc<RefCell<Machine>>, context: &NativeCallContext, count: i64) -> ScriptResult<()> {
+    for _ in 0..count {
+        let start = machine.borrow().cycle_count;
+        while !step(machine, context)? && machine.borrow().cycle_count - start < MAX_CYCLES_PER_FRAME {}
+    }
+    Ok(())
+}
+
+fn set_key(machine: &Rc<RefCell<Machine>>, key: &str, is_down: bool) -> ScriptResult<()> {
+    let key_type = key_type(key)?;
+    let _ = machine.borrow().key_data_sender.send(Key { key_type, is_down });
+    Ok(())
+}
+
+fn key_type(key: &str) -> ScriptResult<KeyType> {
+    KeyType::from_name(key).ok_or_else(|| format!("Unknown key {}", key).into())
+}
+
+fn sound_channel(channel: i64) -> ScriptResult<usize> {
+    match channel {
+        1..=4 => Ok(channel as usize - 1),
+        _ => Err(format!("Unknown sound channel {}, expected 1-4", channel).into()),
+    }
+}
+
+fn add_hook(machine: &Rc<RefCell<Machine>>, access: Access, start: u16, end: u16, callback: FnPtr) {
+    let mut machine = machine.borrow_mut();
+    machine.hooks.push(Hook {
+        access,
+        start,
+        end,
+        callback,
+    });
+
+    // watch ids index into the hooks
+    let watches = machine
+        .hooks
+        .iter()
+        .enumerate()
+        .map(|(id, hook)| Watch {
+            id: id as u32,
+            access: hook.access,
+            start: hook.start,
+            end: hook.end,
+        })
+        .collect();
+    machine.cpu.mmu.watches.set(watches);
+}
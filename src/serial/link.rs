@@ -0,0 +1,185 @@
+#[cfg(unix)]
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Whatever is plugged into the other end of the link cable
+pub trait Link: Send {
+    // A transfer clocked by this side, sends SB and returns the other side's byte if it's already known
+    fn transfer(&mut self, value: u8) -> Option<u8>;
+    // Checked every cycle until the other side's byte arrives, for links where transfer returned None
+    fn transfer_reply(&mut self) -> Option<u8> {
+        None
+    }
+    // A transfer clocked by the other side, value is sent back and their byte returned
+    fn poll(&mut self, value: u8) -> Option<u8>;
+}
+
+#[derive(Copy, Clone)]
+pub enum LinkMode {
+    Listen,
+    Connect,
+}
+
+// Each side sends three byte messages, a transfer it clocked or the reply to the other side's, then a
+// sequence number and the byte. Replies echo the transfer's sequence number so late ones can be ignored.
+const TRANSFER: u8 = 0x01;
+const REPLY: u8 = 0x02;
+// Long enough for the other instance to be busy, after that the transfer reads as if nothing was connected
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+enum Message {
+    Transfer { sequence: u8, value: u8 },
+    Reply { sequence: u8, value: u8 },
+}
+
+// Another rustyboy on the end of a TCP or Unix socket
+pub struct SocketLink {
+    writer: Box<dyn Write + Send>,
+    messages: mpsc::Receiver<Message>,
+    next_sequence: u8,
+    // the transfer waiting on a reply and when it was sent
+    waiting: Option<(u8, Instant)>,
+}
+
+// address is host:port for TCP, or unix:<path> for a Unix socket
+pub fn open(mode: LinkMode, address: &str) -> Box<dyn Link> {
+    match open_socket(mode, address) {
+        Ok(link) => Box::new(link),
+        Err(e) => panic!("Failed to open link cable on {}: {}", address, e),
+    }
+}
+
+fn open_socket(mode: LinkMode, address: &str) -> io::Result<SocketLink> {
+    if let Some(path) = address.strip_prefix("unix:") {
+        return open_unix_socket(mode, path);
+    }
+
+    let stream = match mode {
+        LinkMode::Listen => {
+            let listener = TcpListener::bind(address)?;
+            println!("Waiting for link partner on {}", address);
+            listener.accept()?.0
+        }
+        LinkMode::Connect => TcpStream::connect(address)?,
+    };
+    stream.set_nodelay(true)?;
+    println!("Link cable connected to {}", stream.peer_addr()?);
+    Ok(SocketLink::new(Box::new(stream.try_clone()?), stream))
+}
+
+#[cfg(unix)]
+fn open_unix_socket(mode: LinkMode, path: &str) -> io::Result<SocketLink> {
+    let stream = match mode {
+        LinkMode::Listen => {
+            remove_stale_socket(path);
+            let listener = UnixListener::bind(path)?;
+            println!("Waiting for link partner on {}", path);
+            listener.accept()?.0
+        }
+        LinkMode::Connect => UnixStream::connect(path)?,
+    };
+    println!("Link cable connected on {}", path);
+    Ok(SocketLink::new(Box::new(stream.try_clone()?), stream))
+}
+
+// A previous listener that didn't shut down cleanly leaves its socket file behind, which stops bind
+#[cfg(unix)]
+fn remove_stale_socket(path: &str) {
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn open_unix_socket(_mode: LinkMode, _path: &str) -> io::Result<SocketLink> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Unix sockets aren't supported here",
+    ))
+}
+
+impl SocketLink {
+    fn new<R: Read + Send + 'static>(writer: Box<dyn Write + Send>, reader: R) -> Self {
+        Self {
+            writer,
+            messages: spawn_reader(reader),
+            next_sequence: 0,
+            waiting: None,
+        }
+    }
+
+    fn send(&mut self, kind: u8, sequence: u8, value: u8) {
+        if let Err(e) = self.writer.write_all(&[kind, sequence, value]) {
+            println!("Failed to send over link cable: {}", e);
+        }
+    }
+}
+
+impl Link for SocketLink {
+    fn transfer(&mut self, value: u8) -> Option<u8> {
+        let sequence = self.next_sequence;
+        self.next_sequence = sequence.wrapping_add(1);
+        self.send(TRANSFER, sequence, value);
+        self.waiting = Some((sequence, Instant::now()));
+        None
+    }
+
+    fn transfer_reply(&mut self) -> Option<u8> {
+        let (waiting_on, sent) = self.waiting?;
+        loop {
+            let reply = match self.messages.try_recv() {
+                Ok(Message::Reply { sequence, value }) if sequence == waiting_on => value,
+                // both sides clocking at once just swaps the bytes
+                Ok(Message::Transfer { value, .. }) => value,
+                // a reply to an earlier transfer that already timed out
+                Ok(Message::Reply { .. }) => continue,
+                Err(TryRecvError::Empty) if sent.elapsed() < REPLY_TIMEOUT => return None,
+                Err(_) => 0xFF, // nothing on the other end drives the line high
+            };
+            self.waiting = None;
+            return Some(reply);
+        }
+    }
+
+    // Only called while a transfer on the external clock is waiting, until then the other side's transfer stays queued
+    fn poll(&mut self, data: u8) -> Option<u8> {
+        while let Ok(message) = self.messages.try_recv() {
+            if let Message::Transfer { sequence, value } = message {
+                self.send(REPLY, sequence, data);
+                return Some(value);
+            }
+            // anything else is a reply to a transfer that already timed out
+        }
+        None
+    }
+}
+
+fn spawn_reader<R: Read + Send + 'static>(mut reader: R) -> mpsc::Receiver<Message> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut message = [0; 3];
+        while reader.read_exact(&mut message).is_ok() {
+            let (sequence, value) = (message[1], message[2]);
+            let message = if message[0] == TRANSFER {
+                Message::Transfer { sequence, value }
+            } else {
+                Message::Reply { sequence, value }
+            };
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+        println!("Link cable disconnected");
+    });
+    receiver
+}
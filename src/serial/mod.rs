@@ -0,0 +1,169 @@
+pub mod link;
+#[cfg(feature = "printer")]
+pub mod printer;
+pub mod sink;
+
+use cpu::CPU;
+use serial::link::Link;
+use state::{StateReader, StateResult, StateWriter};
+
+pub struct Serial {
+    data: u8,
+    control: u8,
+    transfer: Option<Transfer>,
+    link: Option<Box<dyn Link>>,
+    pub interrupt: u8,
+}
+
+// A transfer on the internal clock, shifting one bit of SB out and one of incoming in at a time
+struct Transfer {
+    // None until the other side's byte arrives, the bits don't start shifting before then
+    incoming: Option<u8>,
+    bits_remaining: u8,
+    cycles: u32,
+}
+
+impl Serial {
+    const CYCLES_PER_BIT: u32 = CPU::CYCLE_SPEED / 8_192; // 128
+
+    pub fn new() -> Self {
+        Self {
+            data: 0,
+            control: 0,
+            transfer: None,
+            link: None,
+            interrupt: 0,
+        }
+    }
+
+    pub fn connect(&mut self, link: Box<dyn Link>) {
+        self.link = Some(link);
+    }
+
+    // Whatever is on the other end of the link isn't part of the state
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.data);
+        state.write_u8(self.control);
+        state.write_bool(self.transfer.is_some());
+        if let Some(ref transfer) = self.transfer {
+            // a reply that hasn't arrived yet won't once the state is loaded
+            state.write_u8(transfer.incoming.unwrap_or(0xFF));
+            state.write_u8(transfer.bits_remaining);
+            state.write_u32(transfer.cycles);
+        }
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.data = state.read_u8()?;
+        self.control = state.read_u8()?;
+        self.transfer = if state.read_bool()? {
+            Some(Transfer {
+                incoming: Some(state.read_u8()?),
+                bits_remaining: state.read_u8()?,
+                cycles: state.read_u32()?,
+            })
+        } else {
+            None
+        };
+        Ok(())
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF01 => self.read_data(),
+            0xFF02 => self.read_control(),
+            _ => panic!("Unknown serial read operation: 0x{:X}", addr),
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0xFF01 => self.write_data(value),
+            0xFF02 => self.write_control(value),
+            _ => panic!("Unknown serial write operation: 0x{:X}", addr),
+        }
+    }
+
+    pub fn run_cycle(&mut self, cycles: u8) {
+        if self.transfer.is_none() {
+            self.answer_link();
+            return;
+        }
+
+        let finished = {
+            let transfer = self.transfer.as_mut().unwrap();
+            let incoming = match transfer.incoming {
+                Some(incoming) => incoming,
+                None => {
+                    transfer.incoming = self.link.as_mut().and_then(|link| link.transfer_reply());
+                    return;
+                }
+            };
+            transfer.cycles += u32::from(cycles);
+            while transfer.cycles >= Self::CYCLES_PER_BIT && transfer.bits_remaining > 0 {
+                transfer.cycles -= Self::CYCLES_PER_BIT;
+                transfer.bits_remaining -= 1;
+                let bit = (incoming >> transfer.bits_remaining) & 0x01;
+                self.data = self.data << 1 | bit;
+            }
+            transfer.bits_remaining == 0
+        };
+
+        if finished {
+            self.finish_transfer();
+        }
+    }
+
+    // The other side clocks the transfer, it's only answered once a transfer on the external clock is started here
+    fn answer_link(&mut self) {
+        if self.control & 0x81 != 0x80 {
+            return;
+        }
+
+        let incoming = match self.link {
+            Some(ref mut link) => link.poll(self.data),
+            None => None,
+        };
+
+        if let Some(incoming) = incoming {
+            self.data = incoming;
+            self.finish_transfer();
+        }
+    }
+
+    fn finish_transfer(&mut self) {
+        self.transfer = None;
+        self.control &= 0x7F;
+        self.interrupt |= 0x08;
+    }
+
+    fn read_data(&self) -> u8 {
+        self.data
+    }
+
+    fn write_data(&mut self, value: u8) {
+        self.data = value;
+    }
+
+    fn read_control(&self) -> u8 {
+        self.control | 0x7E
+    }
+
+    fn write_control(&mut self, value: u8) {
+        self.control = value;
+        self.transfer = None;
+
+        // transfers on the external clock wait for the other side, see answer_link
+        if value & 0x81 == 0x81 {
+            let incoming = match self.link {
+                Some(ref mut link) => link.transfer(self.data),
+                None => Some(0xFF), // nothing connected
+            };
+            self.transfer = Some(Transfer {
+                incoming,
+                bits_remaining: 8,
+                cycles: 0,
+            });
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use image;
+use serial::link::Link;
+use std::fs;
+use std::path::PathBuf;
+
+// https://gbdev.io/pandocs/Gameboy_Printer.html
+// Packets are 0x88 0x33, command, compression, 16 bit length, data, 16 bit checksum, then two
+// bytes clocked out by the Game Boy that the printer answers with 0x81 and its status.
+const MAGIC: [u8; 2] = [0x88, 0x33];
+const ALIVE: u8 = 0x81;
+
+const INIT: u8 = 0x01;
+const PRINT: u8 = 0x02;
+const DATA: u8 = 0x04;
+const STATUS: u8 = 0x0F;
+
+const STATUS_CHECKSUM_ERROR: u8 = 0x01;
+const STATUS_BUSY: u8 = 0x02;
+const STATUS_IMAGE_FULL: u8 = 0x04;
+const STATUS_UNPROCESSED: u8 = 0x08;
+const STATUS_PACKET_ERROR: u8 = 0x10;
+
+const WIDTH: usize = 160;
+const TILES_PER_ROW: usize = WIDTH / 8;
+// the printer's 8 KiB of RAM, 9 bands of 2 tile rows only take 0x1680 of it
+const BUFFER_SIZE: usize = 0x2000;
+// how many status checks a print stays busy for, games wait on the busy bit clearing
+const BUSY_STATUS_CHECKS: u8 = 4;
+// each unit of margin feeds roughly a tile row of paper
+const ROWS_PER_MARGIN: usize = 8;
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Magic(usize),
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status,
+}
+
+// A Game Boy Printer, each finished strip of paper is saved as a PNG into the output directory
+pub struct Printer {
+    output_dir: PathBuf,
+    state: State,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16,
+    expected_checksum: u16,
+    status: u8,
+    busy_checks: u8,
+    buffer: Vec<u8>,
+    paper: Vec<u8>,
+    printed: u32,
+}
+
+impl Printer {
+    pub fn new(output_dir: &str) -> Self {
+        let output_dir = PathBuf::from(output_dir);
+        if let Err(e) = fs::create_dir_all(&output_dir) {
+            panic!(
+                "Failed to create printer output directory {}: {}",
+                output_dir.display(),
+                e
+            );
+        }
+
+        Self {
+            output_dir,
+            state: State::Magic(0),
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: vec![],
+            checksum: 0,
+            expected_checksum: 0,
+            status: 0,
+            busy_checks: 0,
+            buffer: vec![],
+            paper: vec![],
+            printed: 0,
+        }
+    }
+
+    fn receive(&mut self, value: u8) -> u8 {
+        let (next_state, reply) = match self.state {
+            State::Magic(index) => {
+                if value == MAGIC[index] {
+                    if index + 1 == MAGIC.len() {
+                        (State::Command, 0x00)
+                    } else {
+                        (State::Magic(index + 1), 0x00)
+                    }
+                } else if value == MAGIC[0] {
+                    (State::Magic(1), 0x00)
+                } else {
+                    (State::Magic(0), 0x00)
+                }
+            }
+            State::Command => {
+                self.command = value;
+                self.checksum = u16::from(value);
+                (State::Compression, 0x00)
+            }
+            State::Compression => {
+                self.compressed = value & 0x01 > 0;
+                self.add_to_checksum(value);
+                (State::LengthLow, 0x00)
+            }
+            State::LengthLow => {
+                self.length = u16::from(value);
+                self.add_to_checksum(value);
+                (State::LengthHigh, 0x00)
+            }
+            State::LengthHigh => {
+                self.length |= u16::from(value) << 8;
+                self.add_to_checksum(value);
+                self.data.clear();
+                if self.length == 0 {
+                    (State::ChecksumLow, 0x00)
+                } else {
+                    (State::Data, 0x00)
+                }
+            }
+            State::Data => {
+                self.data.push(value);
+                self.add_to_checksum(value);
+                if self.data.len() == self.length as usize {
+                    (State::ChecksumLow, 0x00)
+                } else {
+                    (State::Data, 0x00)
+                }
+            }
+            State::ChecksumLow => {
+                self.expected_checksum = u16::from(value);
+                (State::ChecksumHigh, 0x00)
+            }
+            State::ChecksumHigh => {
+                self.expected_checksum |= u16::from(value) << 8;
+                (State::Alive, 0x00)
+            }
+            State::Alive => {
+                // the packet is handled before the status goes out, so it reflects this packet
+                self.process_packet();
+                (State::Status, ALIVE)
+            }
+            State::Status => (State::Magic(0), self.status),
+        };
+
+        self.state = next_state;
+        reply
+    }
+
+    fn add_to_checksum(&mut self, value: u8) {
+        self.checksum = self.checksum.wrapping_add(u16::from(value));
+    }
+
+    fn process_packet(&mut self) {
+        if self.checksum != self.expected_checksum {
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return;
+        }
+        self.status &= !STATUS_CHECKSUM_ERROR;
+
+        match self.command {
+            INIT => {
+                self.buffer.clear();
+                self.status = 0;
+                self.busy_checks = 0;
+            }
+            DATA => {
+                if self.data.is_empty() {
+                    // an empty data packet marks the end of the image
+                    self.status |= STATUS_IMAGE_FULL;
+                } else {
+                    let data = if self.compressed {
+                        decompress(&self.data)
+                    } else {
+                        self.data.clone()
+                    };
+                    let space = BUFFER_SIZE - self.buffer.len();
+                    self.buffer.extend(data.into_iter().take(space));
+                    self.status |= STATUS_UNPROCESSED;
+                }
+            }
+            PRINT => {
+                if self.data.len() == 4 {
+                    let (sheets, margins, palette, exposure) = (self.data[0], self.data[1], self.data[2], self.data[3]);
+                    self.print(sheets, margins, palette, exposure);
+                    self.status = (self.status & !STATUS_UNPROCESSED) | STATUS_BUSY | STATUS_IMAGE_FULL;
+                    self.busy_checks = BUSY_STATUS_CHECKS;
+                } else {
+                    self.status |= STATUS_PACKET_ERROR;
+                }
+            }
+            STATUS => {
+                if self.busy_checks > 0 {
+                    self.busy_checks -= 1;
+                    if self.busy_checks == 0 {
+                        self.status &= !(STATUS_BUSY | STATUS_IMAGE_FULL);
+                    }
+                }
+            }
+            _ => self.status |= STATUS_PACKET_ERROR,
+        }
+    }
+
+    // margins holds the feed before the image in the high nibble and after it in the low one,
+    // the paper is only torn off and saved once there's a margin after
+    fn print(&mut self, sheets: u8, margins: u8, palette: u8, exposure: u8) {
+        let margin_before = (margins >> 4) as usize;
+        let margin_after = (margins & 0x0F) as usize;
+
+        self.feed(margin_before);
+        let image = render(&self.buffer, palette, exposure);
+        for _ in 0..sheets {
+            self.paper.extend_from_slice(&image);
+        }
+        self.buffer.clear();
+        self.feed(margin_after);
+
+        if margin_after > 0 {
+            self.tear_off();
+        }
+    }
+
+    fn feed(&mut self, margin: usize) {
+        let length = self.paper.len() + margin * ROWS_PER_MARGIN * WIDTH;
+        self.paper.resize(length, 0xFF);
+    }
+
+    fn tear_off(&mut self) {
+        let paper = ::std::mem::take(&mut self.paper);
+        let height = (paper.len() / WIDTH) as u32;
+        if height == 0 {
+            return;
+        }
+
+        let path = self.next_output_path();
+        let image = image::GrayImage::from_raw(WIDTH as u32, height, paper).unwrap();
+        match image.save(&path) {
+            Ok(_) => println!("Printed to {}", path.display()),
+            Err(e) => println!("Failed to save print to {}: {}", path.display(), e),
+        }
+    }
+
+    fn next_output_path(&mut self) -> PathBuf {
+        loop {
+            self.printed += 1;
+            let path = self.output_dir.join(format!("print_{:04}.png", self.printed));
+            if !path.exists() {
+                return path;
+            }
+        }
+    }
+}
+
+impl Link for Printer {
+    fn transfer(&mut self, value: u8) -> Option<u8> {
+        Some(self.receive(value))
+    }
+
+    // the printer never clocks a transfer itself
+    fn poll(&mut self, _value: u8) -> Option<u8> {
+        None
+    }
+}
+
+// Runs with the top bit set repeat the next byte (n & 0x7F) + 2 times, otherwise n + 1 bytes follow as is
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut result = vec![];
+    let mut bytes = data.iter();
+    while let Some(&control) = bytes.next() {
+        if control & 0x80 > 0 {
+            if let Some(&value) = bytes.next() {
+                let count = (control & 0x7F) as usize + 2;
+                result.extend(::std::iter::repeat_n(value, count));
+            }
+        } else {
+            result.extend(bytes.by_ref().take(control as usize + 1));
+        }
+    }
+    result
+}
+
+// Turns the buffered tiles, 20 to a row, into grey pixels
+fn render(buffer: &[u8], palette: u8, exposure: u8) -> Vec<u8> {
+    let tile_rows = buffer.len() / (TILES_PER_ROW * 16);
+    let mut pixels = vec![0xFF; tile_rows * 8 * WIDTH];
+
+    // a palette of 0 is treated as the usual 0xE4
+    let palette = if palette == 0 { 0xE4 } else { palette };
+    let shades: Vec<u8> = (0..4)
+        .map(|color| shade_with_exposure((palette >> (color * 2)) & 0x03, exposure))
+        .collect();
+
+    for tile_row in 0..tile_rows {
+        for tile in 0..TILES_PER_ROW {
+            let tile_data = &buffer[(tile_row * TILES_PER_ROW + tile) * 16..][..16];
+            for line in 0..8 {
+                let low = tile_data[line * 2];
+                let high = tile_data[line * 2 + 1];
+                for dot in 0..8 {
+                    let bit = 7 - dot;
+                    let color = ((high >> bit) & 0x01) << 1 | ((low >> bit) & 0x01);
+                    let y = tile_row * 8 + line;
+                    let x = tile * 8 + dot;
+                    pixels[y * WIDTH + x] = shades[color as usize];
+                }
+            }
+        }
+    }
+
+    pixels
+}
+
+// Exposure runs from 0x00 to 0x7F around 0x40, lightening or darkening the ink by up to 25%
+fn shade_with_exposure(shade: u8, exposure: u8) -> u8 {
+    let ink = i32::from(shade) * 0x55;
+    let ink = ink * (0x100 + i32::from(exposure & 0x7F) - 0x40) / 0x100;
+    255 - ink.clamp(0, 255) as u8
+}
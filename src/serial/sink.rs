@@ -0,0 +1,33 @@
+use serial::link::Link;
+use std::io::{self, Write};
+use std::sync::mpsc;
+
+// Collects whatever the cart sends over serial, which is how most test ROMs report their results
+pub struct SerialSink {
+    echo: bool,
+    sender: mpsc::Sender<u8>,
+}
+
+impl SerialSink {
+    pub fn new(echo: bool) -> (Self, mpsc::Receiver<u8>) {
+        let (sender, receiver) = mpsc::channel();
+        (Self { echo, sender }, receiver)
+    }
+}
+
+impl Link for SerialSink {
+    fn transfer(&mut self, value: u8) -> Option<u8> {
+        if self.echo {
+            let stdout = io::stdout();
+            let mut handle = stdout.lock();
+            let _ = handle.write_all(&[value]);
+            let _ = handle.flush();
+        }
+        let _ = self.sender.send(value);
+        Some(0xFF) // nothing on the other end drives the line high
+    }
+
+    fn poll(&mut self, _value: u8) -> Option<u8> {
+        None
+    }
+}
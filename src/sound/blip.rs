@@ -0,0 +1,129 @@
+use std::f64::consts::PI;
+
+// Band-limited step synthesis, in the style of blip_buf. Changes in amplitude are added as deltas at
+// the exact clock they happen on, each one spread over a few output samples by a windowed sinc
+// impulse. Reading the samples out integrates the deltas back into the waveform, leaving steps
+// without the aliasing you'd get from sampling the channels directly.
+pub struct BlipBuffer {
+    kernel: Vec<[f32; BlipBuffer::WIDTH]>,
+    factor: f64, // output samples per clock
+    offset: f64, // where clock 0 of the current frame lands in the buffer
+    buffer: Vec<f32>,
+    available: usize,
+    integrator: f32,
+}
+
+impl BlipBuffer {
+    // number of output samples each delta is spread over
+    const WIDTH: usize = 16;
+    // number of sub-sample positions the impulse is precomputed at
+    const PHASES: usize = 32;
+    // fraction of the output's nyquist frequency let through, the rest is the filter's roll off
+    const CUTOFF: f64 = 0.9;
+
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        Self {
+            kernel: build_kernel(),
+            factor: f64::from(sample_rate) / f64::from(clock_rate),
+            offset: 0.0,
+            buffer: vec![0.0; Self::WIDTH],
+            available: 0,
+            integrator: 0.0,
+        }
+    }
+
+    // Changes the ratio of output samples to clocks, takes effect from the next frame
+    pub fn set_rates(&mut self, clock_rate: u32, sample_rate: f64) {
+        self.factor = sample_rate / f64::from(clock_rate);
+    }
+
+    // Adds a change in amplitude at a clock since the start of the frame
+    pub fn add_delta(&mut self, clock: u32, delta: f32) {
+        let position = self.offset + f64::from(clock) * self.factor;
+        let index = position as usize;
+        let phase = ((position - index as f64) * Self::PHASES as f64).round() as usize;
+
+        if self.buffer.len() < index + Self::WIDTH {
+            self.buffer.resize(index + Self::WIDTH, 0.0);
+        }
+        for (sample, weight) in self.buffer[index..].iter_mut().zip(self.kernel[phase].iter()) {
+            *sample += delta * weight;
+        }
+    }
+
+    // Ends the frame after a number of clocks, making the samples before it available
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.offset += f64::from(clocks) * self.factor;
+        let whole = self.offset as usize;
+        self.available += whole;
+        self.offset -= whole as f64;
+        if self.buffer.len() < self.available + Self::WIDTH {
+            self.buffer.resize(self.available + Self::WIDTH, 0.0);
+        }
+    }
+
+    // Moves the available samples into out
+    pub fn read_samples(&mut self, out: &mut Vec<f32>) {
+        for &delta in &self.buffer[..self.available] {
+            self.integrator += delta;
+            out.push(self.integrator);
+        }
+
+        self.buffer.drain(..self.available);
+        if self.buffer.len() < Self::WIDTH {
+            self.buffer.resize(Self::WIDTH, 0.0);
+        }
+        self.available = 0;
+    }
+}
+
+// A Blackman windowed sinc at each phase, with the taps of every phase summing to 1 so a delta's
+// full size ends up in the integrator
+fn build_kernel() -> Vec<[f32; BlipBuffer::WIDTH]> {
+    let half_width = (BlipBuffer::WIDTH / 2) as f64;
+    (0..=BlipBuffer::PHASES)
+        .map(|phase| {
+            let fraction = phase as f64 / BlipBuffer::PHASES as f64;
+            let mut taps = [0_f64; BlipBuffer::WIDTH];
+            for (tap, value) in taps.iter_mut().enumerate() {
+                let x = tap as f64 - half_width - fraction + 1.0;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (PI * x * BlipBuffer::CUTOFF).sin() / (PI * x * BlipBuffer::CUTOFF)
+                };
+                let window = 0.42 + 0.5 * (PI * x / half_width).cos() + 0.08 * (2.0 * PI * x / half_width).cos();
+                *value = sinc * window;
+            }
+
+            let sum: f64 = taps.iter().sum();
+            let mut kernel = [0_f32; BlipBuffer::WIDTH];
+            for (weight, value) in kernel.iter_mut().zip(taps.iter()) {
+                *weight = (value / sum) as f32;
+            }
+            kernel
+        })
+        .collect()
+}
+
+// The capacitor on the DMG's output, which filters out everything around 0Hz and lets the mix
+// settle back to silence when the channels hold a level
+pub struct HighPass {
+    capacitor: f32,
+    charge: f32,
+}
+
+impl HighPass {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        Self {
+            capacitor: 0.0,
+            charge: 0.999_958_f32.powf(clock_rate as f32 / sample_rate as f32),
+        }
+    }
+
+    pub fn filter(&mut self, sample: f32) -> f32 {
+        let out = sample - self.capacitor;
+        self.capacitor = sample - out * self.charge;
+        out
+    }
+}
@@ -0,0 +1,111 @@
+use state::{StateReader, StateResult, StateWriter};
+
+// VVVV APPP - Starting volume, Envelope add mode, period
+pub struct Envelope {
+    initial_volume: u8,
+    increasing: bool,
+    period: u8,
+    volume: u8,
+    timer: u8,
+    // stops once the volume hits 0 or 15, until the channel is triggered again
+    updating: bool,
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Self {
+            initial_volume: 0,
+            increasing: false,
+            period: 0,
+            volume: 0,
+            timer: 0,
+            updating: false,
+        }
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    // The top 5 bits power the DAC, with them all clear the channel can't play
+    pub fn dac_enabled(&self) -> bool {
+        self.initial_volume > 0 || self.increasing
+    }
+
+    // Writing while the channel plays changes the volume in odd ways ("zombie mode"),
+    // this is how the DMG behaves. The volume is 4 bits, so it all wraps around at 16.
+    pub fn write_byte(&mut self, value: u8, channel_enabled: bool) {
+        let increasing = value & 0x08 > 0;
+
+        if channel_enabled {
+            if self.period == 0 && self.updating {
+                self.volume = self.volume.wrapping_add(1);
+            } else if !self.increasing {
+                self.volume = self.volume.wrapping_add(2);
+            }
+            if self.increasing != increasing {
+                self.volume = 16_u8.wrapping_sub(self.volume);
+            }
+            self.volume &= 0x0F;
+        }
+
+        self.initial_volume = value >> 4;
+        self.increasing = increasing;
+        self.period = value & 0x07;
+    }
+
+    pub fn trigger(&mut self) {
+        self.timer = timer_period(self.period);
+        self.volume = self.initial_volume;
+        self.updating = true;
+    }
+
+    // Called at 64 Hz
+    pub fn clock(&mut self) {
+        if self.period == 0 || !self.updating {
+            return;
+        }
+
+        self.timer -= 1;
+        if self.timer > 0 {
+            return;
+        }
+
+        self.timer = timer_period(self.period);
+        if self.increasing && self.volume < 15 {
+            self.volume += 1;
+        } else if !self.increasing && self.volume > 0 {
+            self.volume -= 1;
+        } else {
+            self.updating = false;
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.initial_volume);
+        state.write_bool(self.increasing);
+        state.write_u8(self.period);
+        state.write_u8(self.volume);
+        state.write_u8(self.timer);
+        state.write_bool(self.updating);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.initial_volume = state.read_u8()?;
+        self.increasing = state.read_bool()?;
+        self.period = state.read_u8()?;
+        self.volume = state.read_u8()?;
+        self.timer = state.read_u8()?;
+        self.updating = state.read_bool()?;
+        Ok(())
+    }
+}
+
+// a period of 0 counts as 8
+fn timer_period(period: u8) -> u8 {
+    if period == 0 {
+        8
+    } else {
+        period
+    }
+}
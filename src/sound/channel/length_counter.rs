@@ -0,0 +1,80 @@
+use state::{StateReader, StateResult, StateWriter};
+
+// Counts down at 256 Hz while enabled, the channel turns off when it reaches zero
+#[derive(Copy, Clone)]
+pub struct LengthCounter {
+    max: u16,
+    counter: u16,
+    enabled: bool,
+}
+
+impl LengthCounter {
+    pub fn new(max: u16) -> Self {
+        Self {
+            max,
+            counter: 0,
+            enabled: false,
+        }
+    }
+
+    pub fn load(&mut self, length: u16) {
+        self.counter = self.max - length;
+    }
+
+    // Steps left before the channel turns off, if the counter's enabled
+    pub fn remaining(&self) -> Option<u16> {
+        if self.enabled {
+            Some(self.counter)
+        } else {
+            None
+        }
+    }
+
+    // Returns false once the channel should turn off
+    pub fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter > 0;
+        }
+        true
+    }
+
+    // Writes to NRx4. Enabling the counter when the frame sequencer's next step won't clock it
+    // clocks it once straight away, which can turn the channel off if it isn't being triggered.
+    pub fn write_control(&mut self, enable: bool, trigger: bool, next_step_clocks_length: bool) -> bool {
+        let was_enabled = self.enabled;
+        self.enabled = enable;
+
+        let mut keep_on = true;
+        if !was_enabled && enable && !next_step_clocks_length && self.counter > 0 {
+            self.counter -= 1;
+            keep_on = self.counter > 0 || trigger;
+        }
+
+        // triggering with the counter at zero reloads it, and the same extra clock applies
+        if trigger && self.counter == 0 {
+            self.counter = self.max;
+            if enable && !next_step_clocks_length {
+                self.counter -= 1;
+            }
+        }
+
+        keep_on
+    }
+
+    // The counter survives the APU powering off on the DMG, only NRx4 is cleared
+    pub fn power_off(&mut self) {
+        self.enabled = false;
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.counter);
+        state.write_bool(self.enabled);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.counter = state.read_u16()?;
+        self.enabled = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -1,3 +1,6 @@
+mod envelope;
+mod length_counter;
 pub mod noise;
 pub mod square;
+mod sweep;
 pub mod wave;
@@ -1,106 +1,180 @@
-use sound::settings::noise_settings::NoiseSettings;
-use sound::Sound;
+use sound::channel::envelope::Envelope;
+use sound::channel::length_counter::LengthCounter;
+use sound::scope::ChannelState;
+use state::{StateReader, StateResult, StateWriter};
+
+/*
+NR41 FF20 --LL LLLL Length load (64-L)
+NR42 FF21 VVVV APPP Starting volume, Envelope add mode, period
+NR43 FF22 SSSS WDDD Clock shift, Width mode of LFSR, Divisor code
+NR44 FF23 TL-- ---- Trigger, Length enable
+*/
 
 pub struct Noise {
     enabled: bool,
-    volume: u8,
-    length: u8,
-    volume_tick_counter: u8,
-    sound_tick_countdown: u16,
-    lfsr_reg: u16,
-    settings: NoiseSettings,
+    clock_shift: u8,
+    width_7_bit: bool,
+    divisor_code: u8,
+    timer: u32,
+    lfsr: u16,
+    length: LengthCounter,
+    envelope: Envelope,
 }
 
 impl Noise {
+    const DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
     pub fn new() -> Self {
         Self {
             enabled: false,
-            volume: 0,
-            length: 0,
-            volume_tick_counter: 0,
-            sound_tick_countdown: 0,
-            lfsr_reg: 1,
-            settings: NoiseSettings::new(),
+            clock_shift: 0,
+            width_7_bit: false,
+            divisor_code: 0,
+            timer: 0,
+            lfsr: 0x7FFF,
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
         }
     }
 
-    pub fn write_byte(&mut self, addr: u16, value: u8) {
-        match addr {
-            0xFF20...0xFF23 => self.settings.write_byte(addr, value),
-            _ => unreachable!("Unreachable noise channel sound write operation: 0x{:X}", addr),
+    // register is the offset from the unused FF1F
+    pub fn write_register(&mut self, register: u16, value: u8, next_step_clocks_length: bool) {
+        match register {
+            1 => self.length.load(u16::from(value & 0x3F)),
+            2 => {
+                self.envelope.write_byte(value, self.enabled);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => {
+                self.clock_shift = value >> 4;
+                self.width_7_bit = value & 0x08 > 0;
+                self.divisor_code = value & 0x07;
+            }
+            4 => {
+                let trigger = value & 0x80 > 0;
+                if !self
+                    .length
+                    .write_control(value & 0x40 > 0, trigger, next_step_clocks_length)
+                {
+                    self.enabled = false;
+                }
+                if trigger {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!("Unreachable noise channel register: {}", register),
         }
+    }
 
-        if addr == 0xFF23 && value & 0x80 > 0 {
-            self.enabled = true;
-            self.length = self.settings.sound_length;
-            self.volume = self.settings.envelope.starting_volume;
-        }
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.timer = self.period();
+        self.lfsr = 0x7FFF;
+        self.envelope.trigger();
     }
 
-    // called at 256Hz (4096 CPU cycles)
-    // To get 44100 samples, this function should return about 173 samples
-    // Each of those samples ~24 cycles apart
-    pub fn generate_sound(&mut self) -> [f32; Sound::SAMPLES_PER_CALL as usize] {
-        let mut sound = [0_f32; Sound::SAMPLES_PER_CALL as usize];
-        if !self.enabled || self.volume == 0 {
-            return sound;
-        }
+    fn period(&self) -> u32 {
+        Self::DIVISORS[self.divisor_code as usize] << self.clock_shift
+    }
 
-        // every 23 cycles, add item to array
-        // every period, adjust square_wave_step
-        // run for total of Sound::CYCLES_PER_TICK cycles
-        for index in 0..Sound::SAMPLES_PER_CALL {
-            sound[index as usize] = f32::from(self.volume) * f32::from(1 - (self.lfsr_reg & 0x01));
-            self.decrement_sound_tick_countdown(Sound::CYCLES_PER_SOUND);
+    // Runs the frequency timer for a number of T-cycles, each time it runs out the LFSR shifts
+    pub fn run(&mut self, cycles: u32) {
+        if !self.enabled {
+            return;
         }
 
-        self.decrement_sound_tick_countdown(Sound::ADDITIONAL_CYCLES_PER_TICK);
+        let mut cycles = cycles;
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            // shifts of 14 and 15 leave the LFSR without a clock
+            if self.clock_shift < 14 {
+                self.shift_lfsr();
+            }
+        }
+        self.timer -= cycles;
+    }
 
-        sound
+    // The low two bits are XORed into bit 14, and bit 6 as well in 7 bit mode
+    fn shift_lfsr(&mut self) {
+        let bit = (self.lfsr & 0x01) ^ ((self.lfsr >> 1) & 0x01);
+        self.lfsr = (self.lfsr >> 1) | (bit << 14);
+        if self.width_7_bit {
+            self.lfsr = (self.lfsr & !0x40) | (bit << 6);
+        }
     }
 
-    fn decrement_sound_tick_countdown(&mut self, amount: u16) {
-        if self.settings.frequency == 0 {
-            return;
+    // How many T-cycles until the output can next change, when the LFSR shifts
+    pub fn cycles_to_next_step(&self) -> u32 {
+        if self.enabled {
+            self.timer
+        } else {
+            u32::MAX
         }
+    }
 
-        let mut amount = amount;
-        while self.sound_tick_countdown < amount {
-            amount -= self.sound_tick_countdown;
-            self.sound_tick_countdown = self.settings.frequency;
-            let mut new_lfsr = self.lfsr_reg >> 1;
-            if self.lfsr_reg & 0x01 | new_lfsr & 0x01 > 0 {
-                new_lfsr |= 0x80;
-                if self.settings.lfsr_7_bit_mode {
-                    new_lfsr |= 0x20;
-                }
-            }
-            self.lfsr_reg = new_lfsr;
+    // The 0-15 level going into the DAC
+    pub fn output(&self) -> u8 {
+        if self.enabled && self.lfsr & 0x01 == 0 {
+            self.envelope.volume()
+        } else {
+            0
         }
+    }
 
-        self.sound_tick_countdown -= amount;
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
     }
 
-    pub fn decrement_length(&mut self) {
-        if self.settings.length_enabled && self.length > 0 {
-            self.length -= 1;
-            if self.length == 0 {
-                self.enabled = false;
-            }
+    pub fn state(&self) -> ChannelState {
+        ChannelState {
+            enabled: self.enabled,
+            audible: true,
+            frequency: 4_194_304 / self.period(),
+            volume: self.envelope.volume(),
+            duty: None,
+            length: self.length.remaining(),
         }
     }
 
-    pub fn tick_volume_envelope(&mut self) {
-        if self.settings.envelope.period == 0 {
-            return;
+    pub fn clock_length(&mut self) {
+        if !self.length.clock() {
+            self.enabled = false;
         }
+    }
 
-        self.volume_tick_counter += 1;
-        if self.volume_tick_counter < self.settings.envelope.period {
-            return;
-        }
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn power_off(&mut self) {
+        let mut length = self.length;
+        length.power_off();
+        *self = Self::new();
+        self.length = length;
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_u8(self.clock_shift);
+        state.write_bool(self.width_7_bit);
+        state.write_u8(self.divisor_code);
+        state.write_u32(self.timer);
+        state.write_u16(self.lfsr);
+        self.length.save_state(state);
+        self.envelope.save_state(state);
+    }
 
-        self.volume_tick_counter = 0;
-        self.volume = self.settings.envelope.tick(self.volume);
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.enabled = state.read_bool()?;
+        self.clock_shift = state.read_u8()?;
+        self.width_7_bit = state.read_bool()?;
+        self.divisor_code = state.read_u8()?;
+        self.timer = state.read_u32()?;
+        self.lfsr = state.read_u16()?;
+        self.length.load_state(state)?;
+        self.envelope.load_state(state)
     }
 }
@@ -1,5 +1,8 @@
-use sound::settings::square_settings::SquareSettings;
-use sound::Sound;
+use sound::channel::envelope::Envelope;
+use sound::channel::length_counter::LengthCounter;
+use sound::channel::sweep::Sweep;
+use sound::scope::ChannelState;
+use state::{StateReader, StateResult, StateWriter};
 
 /*
        Square 1
@@ -19,18 +22,18 @@ NR24 FF19 TL-- -FFF Trigger, Length enable, Frequency MSB
 
 pub struct Square {
     enabled: bool,
-    sweep_enabled: bool,
-    volume: u8,
+    has_sweep: bool,
+    duty: u8,
+    duty_step: u8,
     frequency: u16,
-    length: u8,
-    square_wave_step: u8,
-    volume_tick_counter: u8,
-    sweep_tick_counter: u8,
-    sound_tick_countdown: u16,
-    settings: SquareSettings,
+    timer: u32,
+    length: LengthCounter,
+    envelope: Envelope,
+    sweep: Sweep,
 }
 
 impl Square {
+    const DUTY_PERCENTAGES: [u8; 4] = [12, 25, 50, 75];
     const DUTY_LAYOUTS: [[u8; 8]; 4] = [
         [0, 0, 0, 0, 0, 0, 0, 1],
         [1, 0, 0, 0, 0, 0, 0, 1],
@@ -38,110 +41,159 @@ impl Square {
         [0, 1, 1, 1, 1, 1, 1, 0],
     ];
 
-    pub fn new(sweep_enabled: bool) -> Self {
+    pub fn new(has_sweep: bool) -> Self {
         Self {
             enabled: false,
-            sweep_enabled,
-            volume: 0,
+            has_sweep,
+            duty: 0,
+            duty_step: 0,
             frequency: 0,
-            length: 0,
-            square_wave_step: 0,
-            volume_tick_counter: 0,
-            sweep_tick_counter: 0,
-            sound_tick_countdown: 0,
-            settings: SquareSettings::new(),
+            timer: 0,
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+            sweep: Sweep::new(),
         }
     }
 
-    pub fn write_byte(&mut self, addr: u16, value: u8) {
-        match addr {
-            0xFF10...0xFF14 | 0xFF16...0xFF19 => self.settings.write_byte(addr, value),
-            _ => unreachable!("Unreachable square channel sound write operation: 0x{:X}", addr),
-        }
-
-        if (addr == 0xFF14 || addr == 0xFF19) && value & 0x80 > 0 {
-            self.enabled = true;
-            self.length = self.settings.sound_length;
-            self.frequency = self.settings.frequency;
-            self.volume = self.settings.envelope.starting_volume;
-            if self.sweep_enabled && self.settings.sweep.shift > 0 && self.settings.sweep.period > 0 {
-                self.sweep_tick_counter = 0;
-                self.tick_sweep();
+    // register is the offset from NR10 or the unused FF15
+    pub fn write_register(&mut self, register: u16, value: u8, next_step_clocks_length: bool) {
+        match register {
+            0 => {
+                if self.has_sweep && !self.sweep.write_byte(value) {
+                    self.enabled = false;
+                }
+            }
+            1 => {
+                self.duty = value >> 6;
+                self.length.load(u16::from(value & 0x3F));
+            }
+            2 => {
+                self.envelope.write_byte(value, self.enabled);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
             }
+            3 => self.frequency = (self.frequency & 0x0700) | u16::from(value),
+            4 => {
+                self.frequency = u16::from(value & 0x07) << 8 | (self.frequency & 0x00FF);
+                let trigger = value & 0x80 > 0;
+                if !self
+                    .length
+                    .write_control(value & 0x40 > 0, trigger, next_step_clocks_length)
+                {
+                    self.enabled = false;
+                }
+                if trigger {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!("Unreachable square channel register: {}", register),
         }
     }
 
-    // called at 256Hz (4096 CPU cycles)
-    // To get 44100 samples, this function should return about 173 samples
-    // Each of those samples ~24 cycles apart
-    pub fn generate_sound(&mut self) -> [f32; Sound::SAMPLES_PER_CALL as usize] {
-        let mut sound = [0_f32; Sound::SAMPLES_PER_CALL as usize];
-        if !self.enabled || self.volume == 0 {
-            return sound;
-        }
-
-        // every 23 cycles, add item to array
-        // every period, adjust square_wave_step
-        // run for total of Sound::CYCLES_PER_TICK cycles
-        for index in 0..Sound::SAMPLES_PER_CALL {
-            sound[index as usize] = f32::from(self.volume)
-                * f32::from(Self::DUTY_LAYOUTS[self.settings.duty as usize][self.square_wave_step as usize]);
-            self.decrement_sound_tick_countdown(Sound::CYCLES_PER_SOUND);
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.timer = self.period();
+        self.envelope.trigger();
+        if self.has_sweep && !self.sweep.trigger(self.frequency) {
+            self.enabled = false;
         }
+    }
 
-        self.decrement_sound_tick_countdown(Sound::ADDITIONAL_CYCLES_PER_TICK);
-
-        sound
+    fn period(&self) -> u32 {
+        (2048 - u32::from(self.frequency)) * 4
     }
 
-    fn decrement_sound_tick_countdown(&mut self, amount: u16) {
-        let mut amount = amount;
-        while self.sound_tick_countdown < amount {
-            amount -= self.sound_tick_countdown;
-            self.sound_tick_countdown = 2048 - self.frequency;
-            self.square_wave_step = (self.square_wave_step + 1) % 8;
+    // Runs the frequency timer for a number of T-cycles, each time it runs out the duty moves on
+    pub fn run(&mut self, cycles: u32) {
+        if !self.enabled {
+            return;
         }
 
-        self.sound_tick_countdown -= amount;
+        let mut cycles = cycles;
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.duty_step = (self.duty_step + 1) % 8;
+        }
+        self.timer -= cycles;
     }
 
-    pub fn decrement_length(&mut self) {
-        if self.settings.length_enabled && self.length > 0 {
-            self.length -= 1;
-            if self.length == 0 {
-                self.enabled = false;
-            }
+    // How many T-cycles until the output can next change, when the duty moves on
+    pub fn cycles_to_next_step(&self) -> u32 {
+        if self.enabled {
+            self.timer
+        } else {
+            u32::MAX
         }
     }
 
-    pub fn tick_sweep(&mut self) {
-        if !self.sweep_enabled || self.settings.sweep.period == 0 && self.settings.sweep.shift == 0 {
-            return;
+    // The 0-15 level going into the DAC
+    pub fn output(&self) -> u8 {
+        if self.enabled && Self::DUTY_LAYOUTS[self.duty as usize][self.duty_step as usize] > 0 {
+            self.envelope.volume()
+        } else {
+            0
         }
+    }
 
-        self.sweep_tick_counter += 1;
-        if self.sweep_tick_counter < self.settings.sweep.period {
-            return;
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn state(&self) -> ChannelState {
+        ChannelState {
+            enabled: self.enabled,
+            audible: true,
+            frequency: 131_072 / (2048 - u32::from(self.frequency)),
+            volume: self.envelope.volume(),
+            duty: Some(Self::DUTY_PERCENTAGES[self.duty as usize]),
+            length: self.length.remaining(),
         }
+    }
 
-        self.sweep_tick_counter = 0;
-        self.frequency = self.settings.sweep.tick(self.frequency);
-        if self.frequency == 2048 {
+    pub fn clock_length(&mut self) {
+        if !self.length.clock() {
             self.enabled = false;
         }
     }
 
-    pub fn tick_volume_envelope(&mut self) {
-        if self.settings.envelope.period == 0 {
-            return;
+    pub fn clock_sweep(&mut self) {
+        if self.has_sweep && self.enabled && !self.sweep.clock(&mut self.frequency) {
+            self.enabled = false;
         }
+    }
 
-        self.volume_tick_counter += 1;
-        if self.volume_tick_counter < self.settings.envelope.period {
-            return;
-        }
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn power_off(&mut self) {
+        let mut length = self.length;
+        length.power_off();
+        *self = Self::new(self.has_sweep);
+        self.length = length;
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_u8(self.duty);
+        state.write_u8(self.duty_step);
+        state.write_u16(self.frequency);
+        state.write_u32(self.timer);
+        self.length.save_state(state);
+        self.envelope.save_state(state);
+        self.sweep.save_state(state);
+    }
 
-        self.volume_tick_counter = 0;
-        self.volume = self.settings.envelope.tick(self.volume);
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.enabled = state.read_bool()?;
+        self.duty = state.read_u8()?;
+        self.duty_step = state.read_u8()?;
+        self.frequency = state.read_u16()?;
+        self.timer = state.read_u32()?;
+        self.length.load_state(state)?;
+        self.envelope.load_state(state)?;
+        self.sweep.load_state(state)
     }
 }
@@ -0,0 +1,111 @@
+use state::{StateReader, StateResult, StateWriter};
+
+// -PPP NSSS - Sweep period, negate, shift
+pub struct Sweep {
+    period: u8,
+    negate: bool,
+    shift: u8,
+    enabled: bool,
+    shadow_frequency: u16,
+    timer: u8,
+    // clearing negate after a calculation has used it turns the channel off
+    negate_used: bool,
+}
+
+impl Sweep {
+    pub fn new() -> Self {
+        Self {
+            period: 0,
+            negate: false,
+            shift: 0,
+            enabled: false,
+            shadow_frequency: 0,
+            timer: 0,
+            negate_used: false,
+        }
+    }
+
+    // Returns false if the write turns the channel off
+    pub fn write_byte(&mut self, value: u8) -> bool {
+        self.period = (value >> 4) & 0x07;
+        self.negate = value & 0x08 > 0;
+        self.shift = value & 0x07;
+
+        !self.negate_used || self.negate
+    }
+
+    // Returns false if the first calculation overflows, turning the channel off
+    pub fn trigger(&mut self, frequency: u16) -> bool {
+        self.shadow_frequency = frequency;
+        self.timer = timer_period(self.period);
+        self.enabled = self.period > 0 || self.shift > 0;
+        self.negate_used = false;
+
+        self.shift == 0 || self.calculate() <= 2047
+    }
+
+    // Called at 128 Hz, updates the frequency and returns false when it overflows
+    pub fn clock(&mut self, frequency: &mut u16) -> bool {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return true;
+        }
+
+        self.timer = timer_period(self.period);
+        if !self.enabled || self.period == 0 {
+            return true;
+        }
+
+        let new_frequency = self.calculate();
+        if new_frequency > 2047 {
+            return false;
+        }
+        if self.shift > 0 {
+            self.shadow_frequency = new_frequency;
+            *frequency = new_frequency;
+            // the new frequency is checked for overflow again straight away, but not kept
+            return self.calculate() <= 2047;
+        }
+        true
+    }
+
+    fn calculate(&mut self) -> u16 {
+        let change = self.shadow_frequency >> self.shift;
+        if self.negate {
+            self.negate_used = true;
+            self.shadow_frequency - change
+        } else {
+            self.shadow_frequency + change
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.period);
+        state.write_bool(self.negate);
+        state.write_u8(self.shift);
+        state.write_bool(self.enabled);
+        state.write_u16(self.shadow_frequency);
+        state.write_u8(self.timer);
+        state.write_bool(self.negate_used);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.period = state.read_u8()?;
+        self.negate = state.read_bool()?;
+        self.shift = state.read_u8()?;
+        self.enabled = state.read_bool()?;
+        self.shadow_frequency = state.read_u16()?;
+        self.timer = state.read_u8()?;
+        self.negate_used = state.read_bool()?;
+        Ok(())
+    }
+}
+
+// a period of 0 counts as 8
+fn timer_period(period: u8) -> u8 {
+    if period == 0 {
+        8
+    } else {
+        period
+    }
+}
@@ -1,5 +1,6 @@
-use sound::settings::wave_settings::WaveSettings;
-use sound::Sound;
+use sound::channel::length_counter::LengthCounter;
+use sound::scope::ChannelState;
+use state::{StateReader, StateResult, StateWriter};
 
 /*
 NR30 FF1A E--- ---- DAC power
@@ -11,86 +12,207 @@ NR34 FF1E TL-- -FFF Trigger, Length enable, Frequency MSB
 
 pub struct Wave {
     enabled: bool,
-    length: u16,
-    sound_tick_countdown: u16,
-    wave_step: u8,
-    settings: WaveSettings,
-    wave_ram: [u8; 32],
+    dac_enabled: bool,
+    volume_code: u8,
+    frequency: u16,
+    timer: u32,
+    position: u8,
+    sample_buffer: u8,
+    cycles_since_read: u32,
+    length: LengthCounter,
+    wave_ram: [u8; 16],
 }
 
 impl Wave {
+    // how far right the sample is shifted for each volume code
+    const VOLUME_SHIFTS: [u8; 4] = [4, 0, 1, 2];
+    // the first sample is read this many T-cycles later than the period on trigger
+    const TRIGGER_DELAY: u32 = 6;
+
     pub fn new() -> Self {
         Self {
             enabled: false,
-            length: 0,
-            sound_tick_countdown: 0,
-            wave_step: 0,
-            settings: WaveSettings::new(),
-            wave_ram: [0_u8; 32],
+            dac_enabled: false,
+            volume_code: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            sample_buffer: 0,
+            cycles_since_read: 0,
+            length: LengthCounter::new(256),
+            wave_ram: [0_u8; 16],
         }
     }
 
-    pub fn write_byte(&mut self, addr: u16, value: u8) {
-        match addr {
-            0xFF1A => (), // TODO
-            0xFF1B...0xFF1E => self.settings.write_byte(addr, value),
-            _ => unreachable!("Unreachable wave channel sound write operation: 0x{:X}", addr),
+    // register is the offset from NR30
+    pub fn write_register(&mut self, register: u16, value: u8, next_step_clocks_length: bool) {
+        match register {
+            0 => {
+                self.dac_enabled = value & 0x80 > 0;
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            1 => self.length.load(u16::from(value)),
+            2 => self.volume_code = (value >> 5) & 0x03,
+            3 => self.frequency = (self.frequency & 0x0700) | u16::from(value),
+            4 => {
+                self.frequency = u16::from(value & 0x07) << 8 | (self.frequency & 0x00FF);
+                let trigger = value & 0x80 > 0;
+                if !self
+                    .length
+                    .write_control(value & 0x40 > 0, trigger, next_step_clocks_length)
+                {
+                    self.enabled = false;
+                }
+                if trigger {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!("Unreachable wave channel register: {}", register),
         }
+    }
 
-        if (addr == 0xFF14 || addr == 0xFF19) && value & 0x80 > 0 {
-            self.enabled = true;
-            self.length = self.settings.sound_length;
+    fn trigger(&mut self) {
+        // on the DMG, retriggering just as the next byte is read corrupts the start of wave RAM
+        if self.enabled && self.timer == 2 {
+            let byte = usize::from((self.position + 1) % 32 / 2);
+            if byte < 4 {
+                self.wave_ram[0] = self.wave_ram[byte];
+            } else {
+                let block = byte & !0x03;
+                for offset in 0..4 {
+                    self.wave_ram[offset] = self.wave_ram[block + offset];
+                }
+            }
         }
+
+        self.enabled = self.dac_enabled;
+        self.timer = self.period() + Self::TRIGGER_DELAY;
+        // the sample buffer isn't refilled, the last sample plays until the first read
+        self.position = 0;
     }
 
+    fn period(&self) -> u32 {
+        (2048 - u32::from(self.frequency)) * 2
+    }
+
+    // While playing, the DMG only lets wave RAM through when the channel is reading it at that
+    // moment, and then only the byte it's reading
     pub fn read_wave_ram(&self, addr: u16) -> u8 {
-        self.wave_ram[2 * (addr - 0xFF30) as usize] << 4 | self.wave_ram[1 + 2 * (addr - 0xFF30) as usize]
+        if !self.enabled {
+            return self.wave_ram[(addr - 0xFF30) as usize];
+        }
+        if self.cycles_since_read < 2 {
+            self.wave_ram[usize::from(self.position / 2)]
+        } else {
+            0xFF
+        }
     }
 
     pub fn write_wave_ram(&mut self, addr: u16, value: u8) {
-        self.wave_ram[2 * (addr - 0xFF30) as usize] = value >> 4;
-        self.wave_ram[1 + 2 * (addr - 0xFF30) as usize] = value & 0x0F;
+        if !self.enabled {
+            self.wave_ram[(addr - 0xFF30) as usize] = value;
+        } else if self.cycles_since_read < 2 {
+            self.wave_ram[usize::from(self.position / 2)] = value;
+        }
     }
 
-    // called at 256Hz (4096 CPU cycles)
-    // To get 44100 samples, this function should return about 173 samples
-    // Each of those samples ~24 cycles apart
-    pub fn generate_sound(&mut self) -> [f32; Sound::SAMPLES_PER_CALL as usize] {
-        let mut sound = [0_f32; Sound::SAMPLES_PER_CALL as usize];
-        if !self.enabled || self.settings.volume == 0.0 {
-            return sound;
+    // Runs the frequency timer for a number of T-cycles, each time it runs out the next sample is read
+    pub fn run(&mut self, cycles: u32) {
+        if !self.enabled {
+            return;
         }
 
-        // every 23 cycles, add item to array
-        // every period, adjust square_wave_step
-        // run for total of Sound::CYCLES_PER_TICK cycles
-        for index in 0..Sound::SAMPLES_PER_CALL {
-            sound[index as usize] = self.settings.volume * f32::from(self.wave_ram[self.wave_step as usize]);
-            self.decrement_sound_tick_countdown(Sound::CYCLES_PER_SOUND);
+        let mut cycles = cycles;
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.position = (self.position + 1) % 32;
+            let byte = self.wave_ram[usize::from(self.position / 2)];
+            self.sample_buffer = if self.position.is_multiple_of(2) {
+                byte >> 4
+            } else {
+                byte & 0x0F
+            };
+            self.cycles_since_read = 0;
         }
+        self.timer -= cycles;
+        self.cycles_since_read = self.cycles_since_read.saturating_add(cycles);
+    }
 
-        self.decrement_sound_tick_countdown(Sound::ADDITIONAL_CYCLES_PER_TICK);
-
-        sound
+    // How many T-cycles until the output can next change, when the next sample is read
+    pub fn cycles_to_next_step(&self) -> u32 {
+        if self.enabled {
+            self.timer
+        } else {
+            u32::MAX
+        }
     }
 
-    fn decrement_sound_tick_countdown(&mut self, amount: u16) {
-        let mut amount = amount;
-        while self.sound_tick_countdown < amount {
-            amount -= self.sound_tick_countdown;
-            self.sound_tick_countdown = (2048 - self.settings.frequency) / 2;
-            self.wave_step = (self.wave_step + 1) % 32;
+    // The 0-15 level going into the DAC
+    pub fn output(&self) -> u8 {
+        if self.enabled {
+            self.sample_buffer >> Self::VOLUME_SHIFTS[self.volume_code as usize]
+        } else {
+            0
         }
+    }
 
-        self.sound_tick_countdown -= amount;
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
     }
 
-    pub fn decrement_length(&mut self) {
-        if self.settings.length_enabled && self.length > 0 {
-            self.length -= 1;
-            if self.length == 0 {
-                self.enabled = false;
-            }
+    pub fn state(&self) -> ChannelState {
+        ChannelState {
+            enabled: self.enabled,
+            audible: true,
+            frequency: 65_536 / (2048 - u32::from(self.frequency)),
+            volume: 15 >> Self::VOLUME_SHIFTS[self.volume_code as usize],
+            duty: None,
+            length: self.length.remaining(),
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if !self.length.clock() {
+            self.enabled = false;
         }
     }
+
+    // Wave RAM is left alone
+    pub fn power_off(&mut self) {
+        let mut length = self.length;
+        length.power_off();
+        let wave_ram = self.wave_ram;
+        *self = Self::new();
+        self.length = length;
+        self.wave_ram = wave_ram;
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_bool(self.dac_enabled);
+        state.write_u8(self.volume_code);
+        state.write_u16(self.frequency);
+        state.write_u32(self.timer);
+        state.write_u8(self.position);
+        state.write_u8(self.sample_buffer);
+        state.write_u32(self.cycles_since_read);
+        self.length.save_state(state);
+        state.write_bytes(&self.wave_ram);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.enabled = state.read_bool()?;
+        self.dac_enabled = state.read_bool()?;
+        self.volume_code = state.read_u8()?;
+        self.frequency = state.read_u16()?;
+        self.timer = state.read_u32()?;
+        self.position = state.read_u8()?;
+        self.sample_buffer = state.read_u8()?;
+        self.cycles_since_read = state.read_u32()?;
+        self.length.load_state(state)?;
+        state.read_bytes(&mut self.wave_ram)
+    }
 }
@@ -1,103 +1,436 @@
+mod blip;
 mod channel;
 mod player;
-mod settings;
+mod recorder;
+mod ring;
+pub mod scope;
+pub mod wav;
 
 use cpu::CPU;
+use sound::blip::{BlipBuffer, HighPass};
 use sound::channel::noise::Noise;
 use sound::channel::square::Square;
 use sound::channel::wave::Wave;
 use sound::player::Player;
+pub use sound::recorder::build_audio_path;
+use sound::recorder::AudioRecorder;
+use sound::scope::{Scope, Visualization};
+use sound::wav::WavFormat;
+use state::{StateReader, StateResult, StateWriter};
+use std::io;
+use std::sync::mpsc;
+
+// Bits that always read back as 1, for write only and unused bits, from NR10 to NR52
+const READ_MASKS: [u8; 0x17] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // unused, NR21-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // unused, NR41-NR44
+    0x00, 0x00, 0x70, // NR50-NR52
+];
 
 pub struct Sound {
-    reg_values: [u8; 0x17], // store reg values here as shadow register is used in channels
-    cycle_counter: u32,
-    tick_counter: u8,
+    powered: bool,
+    reg_values: [u8; 0x17], // what was last written, read back through the masks
+    frame_sequencer_step: u8,
     square1: Square,
     square2: Square,
     wave: Wave,
     noise: Noise,
-    player: Player,
+    clock: u32, // T-cycles since the start of the frame
+    levels: (f32, f32),
+    left: BlipBuffer,
+    right: BlipBuffer,
+    left_high_pass: HighPass,
+    right_high_pass: HighPass,
+    left_samples: Vec<f32>,
+    right_samples: Vec<f32>,
+    sample_rate: u32,
+    player: Option<Player>,
+    recorder: Option<AudioRecorder>,
+    // square 1, square 2, wave and noise, only the soloed ones are heard while any are
+    muted: [bool; 4],
+    soloed: [bool; 4],
+    scope: Option<Scope>,
 }
 
 impl Sound {
-    pub const CYCLES_PER_TICK: u32 = CPU::CYCLE_SPEED / 256; // 4096
-    pub const CYCLES_PER_SOUND: u16 = 23;
-    pub const SAMPLES_PER_CALL: u16 = 173;
-    pub const ADDITIONAL_CYCLES_PER_TICK: u16 =
-        (Self::CYCLES_PER_TICK - (Self::SAMPLES_PER_CALL * Self::CYCLES_PER_SOUND) as u32) as u16;
+    const CLOCK_RATE: u32 = CPU::CYCLE_SPEED * 4;
+    // used when there's no player to ask
+    const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+    // samples are handed to the player in frames of about 4ms
+    const CYCLES_PER_FRAME: u32 = Self::CLOCK_RATE / 256;
+    // the most the output rate is nudged by to keep the player's queue at its target
+    const MAX_RATE_ADJUSTMENT: f64 = 0.005;
+
+    // Without audio the channels still run, the samples are just dropped
+    pub fn new(audio: bool) -> Self {
+        let player = if audio { Some(Player::new()) } else { None };
+        let sample_rate = player
+            .as_ref()
+            .map_or(Self::DEFAULT_SAMPLE_RATE, |player| player.sample_rate());
+
+        // the boot ROM leaves both sides at full volume, and NR51 at 0xF3
+        let mut reg_values = [0; 0x17];
+        reg_values[0x14] = 0x77;
+        reg_values[0x15] = 0xF3;
 
-    pub fn new() -> Self {
         Self {
-            reg_values: [0; 0x17],
-            cycle_counter: 0,
-            tick_counter: 0,
+            powered: true,
+            reg_values,
+            frame_sequencer_step: 0,
             square1: Square::new(true),
             square2: Square::new(false),
             wave: Wave::new(),
             noise: Noise::new(),
-            player: Player::new(),
+            clock: 0,
+            levels: (0.0, 0.0),
+            left: BlipBuffer::new(Self::CLOCK_RATE, sample_rate),
+            right: BlipBuffer::new(Self::CLOCK_RATE, sample_rate),
+            left_high_pass: HighPass::new(Self::CLOCK_RATE, sample_rate),
+            right_high_pass: HighPass::new(Self::CLOCK_RATE, sample_rate),
+            left_samples: vec![],
+            right_samples: vec![],
+            sample_rate,
+            player,
+            recorder: None,
+            muted: [false; 4],
+            soloed: [false; 4],
+            scope: None,
         }
     }
 
     pub fn read_byte(&self, addr: u16) -> u8 {
-        if addr >= 0xFF30 {
-            return self.wave.read_wave_ram(addr);
+        match addr {
+            0xFF26 => {
+                let statuses = [
+                    self.square1.is_enabled(),
+                    self.square2.is_enabled(),
+                    self.wave.is_enabled(),
+                    self.noise.is_enabled(),
+                ];
+                let status = statuses
+                    .iter()
+                    .enumerate()
+                    .fold(0, |status, (bit, &enabled)| status | (enabled as u8) << bit);
+                READ_MASKS[0x16] | (self.powered as u8) << 7 | status
+            }
+            0xFF10...0xFF25 => {
+                let index = (addr - 0xFF10) as usize;
+                self.reg_values[index] | READ_MASKS[index]
+            }
+            0xFF27...0xFF2F => 0xFF, // unused
+            0xFF30...0xFF3F => self.wave.read_wave_ram(addr),
+            _ => unreachable!("Unreachable sound read operation: 0x{:X}", addr),
         }
-
-        self.reg_values[(addr - 0xFF10) as usize]
     }
 
     pub fn write_byte(&mut self, addr: u16, value: u8) {
-        if addr >= 0xFF30 {
-            return self.wave.write_wave_ram(addr, value);
+        match addr {
+            0xFF30...0xFF3F => return self.wave.write_wave_ram(addr, value),
+            0xFF26 => return self.write_power(value & 0x80 > 0),
+            0xFF27...0xFF2F => return, // unused
+            _ => (),
+        }
+
+        // while powered off only the length counters can be written on the DMG
+        if !self.powered {
+            match addr {
+                0xFF11 | 0xFF16 | 0xFF20 => self.write_channel(addr, value & 0x3F),
+                0xFF1B => self.write_channel(addr, value),
+                _ => (),
+            }
+            return;
         }
 
         self.reg_values[(addr - 0xFF10) as usize] = value;
+        self.write_channel(addr, value);
+    }
+
+    fn write_channel(&mut self, addr: u16, value: u8) {
+        // the sequencer's step is the one it'll run next, length is clocked on the even ones
+        let next_step_clocks_length = self.frame_sequencer_step.is_multiple_of(2);
         match addr {
-            0xFF10...0xFF14 => self.square1.write_byte(addr, value),
-            0xFF15...0xFF19 => self.square2.write_byte(addr, value),
-            0xFF1A...0xFF1E => self.wave.write_byte(addr, value),
-            0xFF20...0xFF23 => self.noise.write_byte(addr, value),
-            0xFF24...0xFF26 => (), // control/status
-            _ => unreachable!("Unreachable sound read operation: 0x{:X}", addr),
+            0xFF10...0xFF14 => self
+                .square1
+                .write_register(addr - 0xFF10, value, next_step_clocks_length),
+            0xFF16...0xFF19 => self
+                .square2
+                .write_register(addr - 0xFF15, value, next_step_clocks_length),
+            0xFF1A...0xFF1E => self.wave.write_register(addr - 0xFF1A, value, next_step_clocks_length),
+            0xFF20...0xFF23 => self.noise.write_register(addr - 0xFF1F, value, next_step_clocks_length),
+            _ => (), // NR50 and NR51 are read back by the mixer
+        }
+    }
+
+    // Powering off clears every register but the length counters, powering on starts the
+    // frame sequencer again from step 0
+    fn write_power(&mut self, powered: bool) {
+        if self.powered && !powered {
+            self.reg_values = [0; 0x17];
+            self.square1.power_off();
+            self.square2.power_off();
+            self.wave.power_off();
+            self.noise.power_off();
+        } else if !self.powered && powered {
+            self.frame_sequencer_step = 0;
         }
+        self.powered = powered;
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.powered);
+        state.write_bytes(&self.reg_values);
+        state.write_u8(self.frame_sequencer_step);
+        self.square1.save_state(state);
+        self.square2.save_state(state);
+        self.wave.save_state(state);
+        self.noise.save_state(state);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.powered = state.read_bool()?;
+        state.read_bytes(&mut self.reg_values)?;
+        self.frame_sequencer_step = state.read_u8()?;
+        self.square1.load_state(state)?;
+        self.square2.load_state(state)?;
+        self.wave.load_state(state)?;
+        self.noise.load_state(state)
     }
 
     pub fn run_cycle(&mut self, cycles: u8) {
-        self.cycle_counter += u32::from(cycles);
-        if self.cycle_counter < Self::CYCLES_PER_TICK {
-            return;
+        // register writes and the frame sequencer change the levels between runs
+        self.update_levels();
+
+        // the channels' timers count T-cycles, they're run up to each point their output could
+        // change so the step lands on the exact cycle
+        let t_cycles = u32::from(cycles) * 4;
+        let mut cycles = t_cycles;
+        while cycles > 0 {
+            let step = [
+                self.square1.cycles_to_next_step(),
+                self.square2.cycles_to_next_step(),
+                self.wave.cycles_to_next_step(),
+                self.noise.cycles_to_next_step(),
+            ]
+            .iter()
+            .fold(cycles, |step, &next| step.min(next))
+            .max(1);
+
+            self.square1.run(step);
+            self.square2.run(step);
+            self.wave.run(step);
+            self.noise.run(step);
+            self.clock += step;
+            cycles -= step;
+            self.update_levels();
+        }
+
+        if self.clock >= Self::CYCLES_PER_FRAME {
+            self.end_frame();
         }
 
-        self.cycle_counter -= Self::CYCLES_PER_TICK;
-        self.tick_counter += 1;
+        let outputs = self.outputs();
+        let frame_done = match self.scope {
+            Some(ref mut scope) => scope.run(t_cycles, &outputs),
+            None => false,
+        };
+        if frame_done {
+            self.send_visualization();
+        }
+    }
+
+    fn outputs(&self) -> [u8; 4] {
+        [
+            self.square1.output(),
+            self.square2.output(),
+            self.wave.output(),
+            self.noise.output(),
+        ]
+    }
+
+    fn is_audible(&self, channel: usize) -> bool {
+        if self.soloed.contains(&true) {
+            self.soloed[channel]
+        } else {
+            !self.muted[channel]
+        }
+    }
+
+    fn update_levels(&mut self) {
+        let outputs = self.outputs();
+        let mut audible_outputs = outputs;
+        for (channel, output) in audible_outputs.iter_mut().enumerate() {
+            if !self.is_audible(channel) {
+                *output = 0;
+            }
+        }
+
+        let (left, right) = self.mix(&audible_outputs);
+        // the stems get every channel, muted or not
+        if let Some(ref mut recorder) = self.recorder {
+            let channels = outputs.map(|output| f32::from(output) / 15.0);
+            recorder.update(self.clock, (left, right), &channels);
+        }
+        if left != self.levels.0 {
+            self.left.add_delta(self.clock, left - self.levels.0);
+        }
+        if right != self.levels.1 {
+            self.right.add_delta(self.clock, right - self.levels.1);
+        }
+        self.levels = (left, right);
+    }
 
-        let square1_sound = self.square1.generate_sound();
-        let square2_sound = self.square2.generate_sound();
-        let wave_sound = self.wave.generate_sound();
-        let noise_sound = self.noise.generate_sound();
-        let mut output = [0_f32; Sound::SAMPLES_PER_CALL as usize];
-        for i in 0..(Sound::SAMPLES_PER_CALL as usize) {
-            output[i] = (square1_sound[i] + square2_sound[i] + wave_sound[i] + noise_sound[i]) / 15.0 / 4.0;
+    fn end_frame(&mut self) {
+        self.left.end_frame(self.clock);
+        self.right.end_frame(self.clock);
+        let clock = self.clock;
+        if let Some(Err(e)) = self.recorder.as_mut().map(|recorder| recorder.end_frame(clock)) {
+            println!("Failed to record audio: {}", e);
+            self.recorder = None;
         }
+        self.clock = 0;
 
-        self.square1.decrement_length();
-        self.square2.decrement_length();
-        self.wave.decrement_length();
-        self.noise.decrement_length();
+        self.left.read_samples(&mut self.left_samples);
+        self.right.read_samples(&mut self.right_samples);
+        for sample in &mut self.left_samples {
+            *sample = self.left_high_pass.filter(*sample);
+        }
+        for sample in &mut self.right_samples {
+            *sample = self.right_high_pass.filter(*sample);
+        }
+
+        if let Some(ref mut player) = self.player {
+            player.play(&self.left_samples, &self.right_samples);
+
+            // dynamic rate control, the host's clock never quite matches ours so the samples made
+            // for the next frame are stretched or squashed slightly to move the queue back to
+            // its target, too small a change in pitch to hear
+            let fill_level = player.fill_level().min(2.0);
+            let sample_rate = f64::from(self.sample_rate) * (1.0 + Self::MAX_RATE_ADJUSTMENT * (1.0 - fill_level));
+            self.left.set_rates(Self::CLOCK_RATE, sample_rate);
+            self.right.set_rates(Self::CLOCK_RATE, sample_rate);
+        }
+        self.left_samples.clear();
+        self.right_samples.clear();
+    }
+
+    // Whether the player has as much queued up as it needs, for pacing emulation off the audio
+    pub fn is_audio_ahead(&self) -> bool {
+        self.player.as_ref().is_some_and(|player| player.fill_level() >= 1.0)
+    }
+
+    pub fn set_channel_muted(&mut self, channel: usize, muted: bool) {
+        self.muted[channel] = muted;
+    }
+
+    pub fn is_channel_muted(&self, channel: usize) -> bool {
+        self.muted[channel]
+    }
+
+    pub fn set_channel_soloed(&mut self, channel: usize, soloed: bool) {
+        self.soloed[channel] = soloed;
+    }
+
+    pub fn is_channel_soloed(&self, channel: usize) -> bool {
+        self.soloed[channel]
+    }
+
+    // Sends each channel's levels and state once a frame until the receiver's dropped, or stops
+    // with None
+    pub fn visualize(&mut self, sender: Option<mpsc::Sender<Visualization>>) {
+        self.scope = sender.map(Scope::new);
+    }
+
+    fn send_visualization(&mut self) {
+        let mut channels = vec![
+            self.square1.state(),
+            self.square2.state(),
+            self.wave.state(),
+            self.noise.state(),
+        ];
+        for (channel, state) in channels.iter_mut().enumerate() {
+            state.audible = self.is_audible(channel);
+        }
+
+        let sent = match self.scope {
+            Some(ref mut scope) => scope.send(channels),
+            None => true,
+        };
+        if !sent {
+            self.scope = None;
+        }
+    }
+
+    // Records the mix to a WAV at the host's rate, with each channel in its own file as well if
+    // stems is set
+    pub fn start_recording(&mut self, path: &str, format: WavFormat, stems: bool) -> io::Result<()> {
+        self.recorder = Some(AudioRecorder::create(
+            path,
+            format,
+            stems,
+            Self::CLOCK_RATE,
+            self.sample_rate,
+        )?);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /*
+    NR50 FF24 ALLL BRRR VIN to left, Left volume, VIN to right, Right volume
+    NR51 FF25 NW21 NW21 Left enables, Right enables (Noise, Wave, Square 2, Square 1)
+    */
+    fn mix(&self, levels: &[u8; 4]) -> (f32, f32) {
+        let master_volume = self.reg_values[0x14];
+        let panning = self.reg_values[0x15];
+
+        // VIN brings in audio from the cartridge, which none of the supported carts produce
+        let side = |enables: u8, volume: u8| {
+            let sum = levels
+                .iter()
+                .enumerate()
+                .filter(|&(channel, _)| enables & (1 << channel) > 0)
+                .map(|(_, &level)| f32::from(level))
+                .sum::<f32>();
+            sum / 15.0 / 4.0 * f32::from(volume + 1) / 8.0
+        };
+
+        (
+            side(panning >> 4, (master_volume >> 4) & 0x07),
+            side(panning & 0x0F, master_volume & 0x07),
+        )
+    }
+
+    // Clocked at 512 Hz by DIV: length at 256 Hz, sweep at 128 Hz and envelopes at 64 Hz
+    pub fn step_frame_sequencer(&mut self) {
+        if !self.powered {
+            return;
+        }
+
+        if self.frame_sequencer_step.is_multiple_of(2) {
+            self.square1.clock_length();
+            self.square2.clock_length();
+            self.wave.clock_length();
+            self.noise.clock_length();
+        }
 
-        if self.tick_counter % 2 == 0 {
-            self.square1.tick_sweep();
-            self.square2.tick_sweep();
+        if self.frame_sequencer_step == 2 || self.frame_sequencer_step == 6 {
+            self.square1.clock_sweep();
         }
 
-        if self.tick_counter % 4 == 0 {
-            self.square1.tick_volume_envelope();
-            self.square2.tick_volume_envelope();
-            self.noise.tick_volume_envelope();
-            self.tick_counter = 0;
+        if self.frame_sequencer_step == 7 {
+            self.square1.clock_envelope();
+            self.square2.clock_envelope();
+            self.noise.clock_envelope();
         }
 
-        self.player.play(&output, &output)
+        self.frame_sequencer_step = (self.frame_sequencer_step + 1) % 8;
     }
 }
@@ -1,20 +1,30 @@
 use cpal;
-use std::sync::Arc;
-use std::sync::Mutex;
+use sound::ring::{self, Consumer, Producer};
 use std::thread;
 
 pub struct Player {
-    bit_buf: Arc<Mutex<Vec<(f32, f32)>>>,
+    producer: Producer,
+    sample_rate: u32,
 }
 
 impl Player {
+    // how much audio the ring holds before new samples are dropped
+    const CAPACITY_MS: u32 = 250;
+    // how much audio we aim to keep queued up
+    const TARGET_LATENCY_MS: u32 = 60;
+
     pub fn new() -> Self {
         let device = cpal::default_output_device().expect("Failed to get default output device");
-        let format = cpal::Format {
-            channels: 2,
-            sample_rate: cpal::SampleRate(44_100),
-            data_type: cpal::SampleFormat::F32,
+        // play at whatever rate the device runs at, so the resampling is all done on our side
+        let format = match device.default_output_format() {
+            Ok(format) => cpal::Format { channels: 2, ..format },
+            Err(_) => cpal::Format {
+                channels: 2,
+                sample_rate: cpal::SampleRate(44_100),
+                data_type: cpal::SampleFormat::F32,
+            },
         };
+        let sample_rate = format.sample_rate.0;
 
         println!("{:?}", format);
 
@@ -22,66 +32,73 @@ impl Player {
         let stream_id = event_loop.build_output_stream(&device, &format).unwrap();
         event_loop.play_stream(stream_id);
 
-        let bit_buf = Arc::new(Mutex::new(Vec::new()));
+        let capacity = (sample_rate * Self::CAPACITY_MS / 1000) as usize;
+        let (producer, consumer) = ring::new(capacity);
+        thread::spawn(move || run_event_loop(event_loop, consumer));
 
-        let bb_clone = bit_buf.clone();
-        thread::spawn(move || run_event_loop(event_loop, bb_clone));
+        Player { producer, sample_rate }
+    }
 
-        Player { bit_buf }
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // How full the queue is compared to the target latency, 1.0 being right on it
+    pub fn fill_level(&self) -> f64 {
+        let target = self.sample_rate * Self::TARGET_LATENCY_MS / 1000;
+        self.producer.buffered() as f64 / f64::from(target)
     }
 
     pub fn play(&mut self, l_stream: &[f32], r_stream: &[f32]) {
-        let mut in_bit_buf = self.bit_buf.lock().unwrap();
         for (l, r) in l_stream.iter().zip(r_stream) {
-            in_bit_buf.push((*l, *r));
+            // when the emulator runs ahead, like while fast forwarding, what doesn't fit is dropped
+            if !self.producer.push((*l, *r)) {
+                break;
+            }
         }
     }
 }
 
-fn run_event_loop(event_loop: cpal::EventLoop, bit_buf: Arc<Mutex<Vec<(f32, f32)>>>) {
+fn run_event_loop(event_loop: cpal::EventLoop, mut consumer: Consumer) {
+    // on an underrun the last sample is held, rather than jumping to 0 and back
+    let mut last = (0.0, 0.0);
     event_loop.run(move |_, data| {
-        let mut in_bit_buf = bit_buf.lock().unwrap();
-        let mut n = 0;
+        let mut next = || {
+            if let Some(sample) = consumer.pop() {
+                last = sample;
+            }
+            last
+        };
 
         match data {
             cpal::StreamData::Output {
                 buffer: cpal::UnknownTypeOutputBuffer::U16(mut buffer),
             } => {
-                n = buffer.chunks_mut(2).len();
-                for (ref mut out, (in_l, in_r)) in buffer.chunks_mut(2).zip(in_bit_buf.iter()) {
-                    out[0] = (in_l * f32::from(i16::max_value()) + f32::from(u16::max_value()) / 2.0) as u16;
-                    out[1] = (in_r * f32::from(i16::max_value()) + f32::from(u16::max_value()) / 2.0) as u16;
+                for out in buffer.chunks_mut(2) {
+                    let (in_l, in_r) = next();
+                    out[0] = (in_l * f32::from(i16::MAX) + f32::from(u16::MAX) / 2.0) as u16;
+                    out[1] = (in_r * f32::from(i16::MAX) + f32::from(u16::MAX) / 2.0) as u16;
                 }
             }
             cpal::StreamData::Output {
                 buffer: cpal::UnknownTypeOutputBuffer::I16(mut buffer),
             } => {
-                n = buffer.chunks_mut(2).len();
-                for (ref mut out, (in_l, in_r)) in buffer.chunks_mut(2).zip(in_bit_buf.iter()) {
-                    out[0] = (in_l * f32::from(i16::max_value())) as i16;
-                    out[1] = (in_r * f32::from(i16::max_value())) as i16;
+                for out in buffer.chunks_mut(2) {
+                    let (in_l, in_r) = next();
+                    out[0] = (in_l * f32::from(i16::MAX)) as i16;
+                    out[1] = (in_r * f32::from(i16::MAX)) as i16;
                 }
             }
             cpal::StreamData::Output {
                 buffer: cpal::UnknownTypeOutputBuffer::F32(mut buffer),
             } => {
-                n = buffer.chunks_mut(2).len();
-                for (ref mut out, (in_l, in_r)) in buffer.chunks_mut(2).zip(in_bit_buf.iter()) {
-                    out[0] = *in_l;
-                    out[1] = *in_r;
+                for out in buffer.chunks_mut(2) {
+                    let (in_l, in_r) = next();
+                    out[0] = in_l;
+                    out[1] = in_r;
                 }
             }
             _ => (),
         };
-
-        if in_bit_buf.len() > n {
-            in_bit_buf.drain(0..n);
-            if in_bit_buf.len() > n * 10 {
-                in_bit_buf.clear();
-                println!("CLEARING AUDIO BUFFER");
-            }
-        } else {
-            in_bit_buf.clear();
-        }
     })
 }
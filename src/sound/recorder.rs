@@ -0,0 +1,144 @@
+use sound::blip::{BlipBuffer, HighPass};
+use sound::wav::{WavFormat, WavWriter};
+use std::io;
+use std::path::Path;
+
+// A WAV file fed by its own band-limited buffers, one per channel in the file. These run at a fixed
+// rate, unlike the player's which get nudged to keep its queue steady.
+struct Track {
+    path: String,
+    writer: WavWriter,
+    buffers: Vec<BlipBuffer>,
+    high_passes: Vec<HighPass>,
+    levels: Vec<f32>,
+    samples: Vec<Vec<f32>>,
+    interleaved: Vec<f32>,
+}
+
+impl Track {
+    fn create(path: String, format: WavFormat, channels: usize, clock_rate: u32, sample_rate: u32) -> io::Result<Self> {
+        Ok(Self {
+            writer: WavWriter::create(&path, format, channels as u16, sample_rate)?,
+            path,
+            buffers: (0..channels)
+                .map(|_| BlipBuffer::new(clock_rate, sample_rate))
+                .collect(),
+            high_passes: (0..channels).map(|_| HighPass::new(clock_rate, sample_rate)).collect(),
+            levels: vec![0.0; channels],
+            samples: vec![vec![]; channels],
+            interleaved: vec![],
+        })
+    }
+
+    fn update(&mut self, clock: u32, levels: &[f32]) {
+        for ((buffer, last), &level) in self.buffers.iter_mut().zip(self.levels.iter_mut()).zip(levels) {
+            if level != *last {
+                buffer.add_delta(clock, level - *last);
+                *last = level;
+            }
+        }
+    }
+
+    fn end_frame(&mut self, clocks: u32) -> io::Result<()> {
+        for ((buffer, high_pass), samples) in self
+            .buffers
+            .iter_mut()
+            .zip(self.high_passes.iter_mut())
+            .zip(self.samples.iter_mut())
+        {
+            buffer.end_frame(clocks);
+            buffer.read_samples(samples);
+            for sample in samples.iter_mut() {
+                *sample = high_pass.filter(*sample);
+            }
+        }
+
+        self.interleaved.clear();
+        for frame in 0..self.samples[0].len() {
+            for samples in &self.samples {
+                self.interleaved.push(samples[frame]);
+            }
+        }
+        for samples in &mut self.samples {
+            samples.clear();
+        }
+        self.writer.write_samples(&self.interleaved)
+    }
+
+    fn finish(&mut self) {
+        match self.writer.finish() {
+            Ok(_) => println!("Audio saved to {}", self.path),
+            Err(e) => println!("Failed to write audio to {}: {}", self.path, e),
+        }
+    }
+}
+
+// Records the stereo mix, and optionally each channel on its own before panning and master volume
+// to out.square1.wav, out.square2.wav, out.wave.wav and out.noise.wav next to it
+pub struct AudioRecorder {
+    mix: Track,
+    stems: Vec<Track>,
+}
+
+impl AudioRecorder {
+    const STEM_NAMES: [&'static str; 4] = ["square1", "square2", "wave", "noise"];
+
+    pub fn create(path: &str, format: WavFormat, stems: bool, clock_rate: u32, sample_rate: u32) -> io::Result<Self> {
+        let mix = Track::create(path.to_owned(), format, 2, clock_rate, sample_rate)?;
+        let stems = if stems {
+            Self::STEM_NAMES
+                .iter()
+                .map(|name| {
+                    let stem_path = Path::new(path).with_extension(format!("{}.wav", name));
+                    Track::create(
+                        String::from(stem_path.to_string_lossy()),
+                        format,
+                        1,
+                        clock_rate,
+                        sample_rate,
+                    )
+                })
+                .collect::<io::Result<Vec<Track>>>()?
+        } else {
+            vec![]
+        };
+        println!("Recording audio to {}", path);
+        Ok(Self { mix, stems })
+    }
+
+    // Takes the mix and the level of each channel at a clock since the start of the frame
+    pub fn update(&mut self, clock: u32, mix: (f32, f32), channels: &[f32; 4]) {
+        self.mix.update(clock, &[mix.0, mix.1]);
+        for (stem, &level) in self.stems.iter_mut().zip(channels.iter()) {
+            stem.update(clock, &[level]);
+        }
+    }
+
+    pub fn end_frame(&mut self, clocks: u32) -> io::Result<()> {
+        self.mix.end_frame(clocks)?;
+        for stem in &mut self.stems {
+            stem.end_frame(clocks)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AudioRecorder {
+    fn drop(&mut self) {
+        self.mix.finish();
+        for stem in &mut self.stems {
+            stem.finish();
+        }
+    }
+}
+
+pub fn build_audio_path(cart_path: &str) -> String {
+    let mut number = 1;
+    loop {
+        let path = Path::new(cart_path).with_extension(format!("{}.wav", number));
+        if !path.exists() {
+            return String::from(path.to_string_lossy());
+        }
+        number += 1;
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// A lock free ring of stereo samples, for the emulator thread to hand audio to the output callback
+// without either one waiting on the other. Each sample's two f32s are packed into one atomic, so
+// only the positions need ordering between the threads.
+struct Ring {
+    slots: Vec<AtomicU64>,
+    read: AtomicUsize,  // only moved by the consumer
+    write: AtomicUsize, // only moved by the producer
+}
+
+impl Ring {
+    fn len(&self) -> usize {
+        let write = self.write.load(Ordering::Acquire);
+        let read = self.read.load(Ordering::Acquire);
+        (write + self.slots.len() - read) % self.slots.len()
+    }
+}
+
+pub struct Producer {
+    ring: Arc<Ring>,
+}
+
+pub struct Consumer {
+    ring: Arc<Ring>,
+}
+
+// One slot is always left empty to tell a full ring from an empty one
+pub fn new(capacity: usize) -> (Producer, Consumer) {
+    let ring = Arc::new(Ring {
+        slots: (0..=capacity).map(|_| AtomicU64::new(0)).collect(),
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+    });
+    (Producer { ring: ring.clone() }, Consumer { ring })
+}
+
+impl Producer {
+    // Returns false, dropping the sample, if the ring is full
+    pub fn push(&mut self, (left, right): (f32, f32)) -> bool {
+        let write = self.ring.write.load(Ordering::Relaxed);
+        let next = (write + 1) % self.ring.slots.len();
+        if next == self.ring.read.load(Ordering::Acquire) {
+            return false;
+        }
+
+        let packed = u64::from(left.to_bits()) << 32 | u64::from(right.to_bits());
+        self.ring.slots[write].store(packed, Ordering::Relaxed);
+        self.ring.write.store(next, Ordering::Release);
+        true
+    }
+
+    // How many samples are waiting to be played
+    pub fn buffered(&self) -> usize {
+        self.ring.len()
+    }
+}
+
+impl Consumer {
+    pub fn pop(&mut self) -> Option<(f32, f32)> {
+        let read = self.ring.read.load(Ordering::Relaxed);
+        if read == self.ring.write.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let packed = self.ring.slots[read].load(Ordering::Relaxed);
+        self.ring
+            .read
+            .store((read + 1) % self.ring.slots.len(), Ordering::Release);
+        Some((f32::from_bits((packed >> 32) as u32), f32::from_bits(packed as u32)))
+    }
+}
@@ -0,0 +1,63 @@
+use std::mem;
+use std::sync::mpsc;
+
+// What a channel is doing, for showing on the visualizer
+pub struct ChannelState {
+    pub enabled: bool,
+    pub audible: bool,       // false while muted, or while another channel is soloed
+    pub frequency: u32,      // in Hz, for noise how often the LFSR shifts
+    pub volume: u8,          // 0-15
+    pub duty: Option<u8>,    // as a percentage, for the square channels
+    pub length: Option<u16>, // steps left on the length counter, while it's enabled
+}
+
+// A frame of the visualizer, each channel's state and its levels across the frame
+pub struct Visualization {
+    pub channels: Vec<ChannelState>,
+    pub levels: Vec<Vec<u8>>,
+}
+
+// Samples each channel's level at even points across a frame and sends them off at the end of it,
+// for drawing as oscilloscopes
+pub struct Scope {
+    sender: mpsc::Sender<Visualization>,
+    clock: u32,
+    levels: Vec<Vec<u8>>,
+}
+
+impl Scope {
+    pub const WIDTH: usize = 160;
+    // T-cycles in a frame of the LCD
+    const CYCLES_PER_FRAME: u32 = 70_224;
+    const CYCLES_PER_SAMPLE: u32 = Self::CYCLES_PER_FRAME / Self::WIDTH as u32;
+
+    pub fn new(sender: mpsc::Sender<Visualization>) -> Self {
+        Self {
+            sender,
+            clock: 0,
+            levels: vec![vec![]; 4],
+        }
+    }
+
+    // Returns true once the frame's full, ready to be sent
+    pub fn run(&mut self, cycles: u32, outputs: &[u8; 4]) -> bool {
+        let samples_before = self.clock / Self::CYCLES_PER_SAMPLE;
+        self.clock += cycles;
+        for _ in samples_before..self.clock / Self::CYCLES_PER_SAMPLE {
+            for (levels, &output) in self.levels.iter_mut().zip(outputs) {
+                levels.push(output);
+            }
+        }
+        self.levels[0].len() >= Self::WIDTH
+    }
+
+    // Returns false once the other end has gone away
+    pub fn send(&mut self, channels: Vec<ChannelState>) -> bool {
+        self.clock = 0;
+        for levels in &mut self.levels {
+            levels.truncate(Self::WIDTH);
+        }
+        let levels = mem::replace(&mut self.levels, vec![vec![]; 4]);
+        self.sender.send(Visualization { channels, levels }).is_ok()
+    }
+}
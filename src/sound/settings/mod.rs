@@ -1,5 +0,0 @@
-mod envelope_settings;
-pub mod noise_settings;
-pub mod square_settings;
-mod sweep_settings;
-pub mod wave_settings;
@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::str::FromStr;
+
+#[derive(Copy, Clone)]
+pub enum WavFormat {
+    Pcm16,
+    Float32,
+}
+
+impl WavFormat {
+    fn format_tag(self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 1,
+            WavFormat::Float32 => 3,
+        }
+    }
+
+    fn bytes_per_sample(self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 2,
+            WavFormat::Float32 => 4,
+        }
+    }
+}
+
+impl FromStr for WavFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pcm16" => Ok(WavFormat::Pcm16),
+            "float32" => Ok(WavFormat::Float32),
+            _ => Err(format!("Unknown WAV format {}, expected pcm16 or float32", s)),
+        }
+    }
+}
+
+// Writes interleaved samples as they come, the sizes in the header are filled in on finish
+pub struct WavWriter {
+    writer: BufWriter<File>,
+    format: WavFormat,
+    data_size: u32,
+}
+
+impl WavWriter {
+    const HEADER_SIZE: u32 = 44;
+
+    pub fn create(path: &str, format: WavFormat, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let block_align = channels * format.bytes_per_sample();
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&0_u32.to_le_bytes())?; // file size, filled in on finish
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16_u32.to_le_bytes())?;
+        writer.write_all(&format.format_tag().to_le_bytes())?;
+        writer.write_all(&channels.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&(format.bytes_per_sample() * 8).to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&0_u32.to_le_bytes())?; // data size, filled in on finish
+
+        Ok(Self {
+            writer,
+            format,
+            data_size: 0,
+        })
+    }
+
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        for &sample in samples {
+            let sample = sample.clamp(-1.0, 1.0);
+            match self.format {
+                WavFormat::Pcm16 => self
+                    .writer
+                    .write_all(&((sample * f32::from(i16::MAX)) as i16).to_le_bytes())?,
+                WavFormat::Float32 => self.writer.write_all(&sample.to_le_bytes())?,
+            }
+        }
+        self.data_size += samples.len() as u32 * u32::from(self.format.bytes_per_sample());
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer
+            .write_all(&(Self::HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(40))?;
+        self.writer.write_all(&self.data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+}
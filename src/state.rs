@@ -0,0 +1,110 @@
+// Save states are each component's fields written out in a fixed order. The version is bumped
+// whenever that order changes, older states are refused rather than loaded wrongly.
+const MAGIC: &[u8; 4] = b"RBST";
+const VERSION: u8 = 4;
+
+pub type StateResult<T> = Result<T, String>;
+
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        let mut writer = Self { data: vec![] };
+        writer.write_bytes(MAGIC);
+        writer.write_u8(VERSION);
+        writer
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> StateResult<Self> {
+        let mut reader = Self { data, position: 0 };
+        let mut magic = [0_u8; 4];
+        reader.read_bytes(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(String::from("Not a rustyboy save state"));
+        }
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(format!(
+                "Save state version {} isn't supported, expected {}",
+                version, VERSION
+            ));
+        }
+        Ok(reader)
+    }
+
+    pub fn read_u8(&mut self) -> StateResult<u8> {
+        let mut bytes = [0_u8; 1];
+        self.read_bytes(&mut bytes)?;
+        Ok(bytes[0])
+    }
+
+    pub fn read_bool(&mut self) -> StateResult<bool> {
+        Ok(self.read_u8()? > 0)
+    }
+
+    pub fn read_u16(&mut self) -> StateResult<u16> {
+        let mut bytes = [0_u8; 2];
+        self.read_bytes(&mut bytes)?;
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    pub fn read_u32(&mut self) -> StateResult<u32> {
+        let mut bytes = [0_u8; 4];
+        self.read_bytes(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn read_u64(&mut self) -> StateResult<u64> {
+        let mut bytes = [0_u8; 8];
+        self.read_bytes(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    // Fills the buffer, the length has to match what was written
+    pub fn read_bytes(&mut self, buffer: &mut [u8]) -> StateResult<()> {
+        let end = self.position + buffer.len();
+        if end > self.data.len() {
+            return Err(String::from("Save state is truncated"));
+        }
+        buffer.copy_from_slice(&self.data[self.position..end]);
+        self.position = end;
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use cpu::CPU;
+use movie::Movie;
+use serial::sink::SerialSink;
+use std::process;
+
+// Mooneye's tests finish on LD B,B with the registers set to fibonacci numbers, or all 0x42 on failure
+const LD_B_B: u8 = 0x40;
+const MOONEYE_PASSED: [u8; 6] = [3, 5, 8, 13, 21, 34];
+const MOONEYE_FAILED: [u8; 6] = [0x42; 6];
+
+// Runs a test ROM without a window or audio and exits with 0 if it passed, 1 if it failed or 2 on timeout.
+// A movie can drive the keys for ROMs that need input to get to the result.
+pub fn run(cart_path: &str, timeout_seconds: u32, echo: bool, movie: Option<Movie>) {
+    let (mut cpu, screen_data_receiver, _) = CPU::new_headless(cart_path, false);
+    if let Some(movie) = movie {
+        if let Err(e) = cpu.play_movie(movie) {
+            panic!("Failed to play movie: {}", e);
+        }
+    }
+    let (sink, serial_receiver) = SerialSink::new(echo);
+    cpu.mmu.connect_link(Box::new(sink));
+
+    let timeout_cycles = u64::from(timeout_seconds) * u64::from(CPU::CYCLE_SPEED);
+    let mut cycles: u64 = 0;
+    let mut serial_output = String::new();
+
+    while cycles < timeout_cycles {
+        let opcode = cpu.mmu.read_byte(cpu.reg.pc);
+        cycles += u64::from(cpu.run_cycle());
+        // frames aren't needed, but the GPU blocks if they're left in the channel
+        while screen_data_receiver.try_recv().is_ok() {}
+
+        let mut received = false;
+        while let Ok(byte) = serial_receiver.try_recv() {
+            serial_output.push(byte as char);
+            received = true;
+        }
+        if received {
+            if serial_output.contains("Passed") {
+                finish(&serial_output, echo, "Passed", 0);
+            }
+            if serial_output.contains("Failed") {
+                finish(&serial_output, echo, "Failed", 1);
+            }
+        }
+
+        if opcode == LD_B_B {
+            let register = cpu.reg;
+            let registers = [register.b, register.c, register.d, register.e, register.h, register.l];
+            if registers == MOONEYE_PASSED {
+                finish(&serial_output, echo, "Passed", 0);
+            }
+            if registers == MOONEYE_FAILED {
+                finish(&serial_output, echo, "Failed", 1);
+            }
+        }
+    }
+
+    finish(
+        &serial_output,
+        echo,
+        &format!("Timed out after {} seconds", timeout_seconds),
+        2,
+    );
+}
+
+fn finish(serial_output: &str, echoed: bool, result: &str, exit_code: i32) {
+    if !echoed && !serial_output.is_empty() {
+        println!("{}", serial_output.trim_end());
+    } else if echoed && !serial_output.is_empty() && !serial_output.ends_with('\n') {
+        println!();
+    }
+    println!("{}", result);
+    process::exit(exit_code);
+}
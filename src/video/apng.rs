@@ -0,0 +1,99 @@
+use png::{BitDepth, ColorType, Encoder, Writer};
+use screen::Screen;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use video::{VideoWriter, CLOCK_SPEED};
+
+// The frame count comes first in the file, so it's left as many as it can hold while frames are
+// written and the real count is filled in on finish
+pub struct ApngWriter {
+    path: String,
+    writer: Option<Writer<BufWriter<File>>>,
+    frame_cycles: u64,
+    frames: u32,
+    // frames of time the written frames cover, more than frames once any have been dropped
+    position: u64,
+}
+
+impl ApngWriter {
+    pub fn create(path: &str, frame_cycles: u32) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = Encoder::new(file, Screen::WIDTH, Screen::HEIGHT);
+        encoder.set_color(ColorType::Rgb);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.set_animated(u32::MAX, 0).map_err(io::Error::other)?;
+        let writer = encoder.write_header().map_err(io::Error::other)?;
+        Ok(Self {
+            path: path.to_owned(),
+            writer: Some(writer),
+            frame_cycles: u64::from(frame_cycles),
+            frames: 0,
+            position: 0,
+        })
+    }
+
+    // Delays are a u16 fraction, which can't hold a frame exactly, so like the GIF each one is
+    // rounded from the time since the start in milliseconds, and stretched over any dropped frames
+    fn next_delay(&mut self, index: u64) -> u16 {
+        let milliseconds =
+            |frames: u64| (frames * self.frame_cycles * 1000 + u64::from(CLOCK_SPEED) / 2) / u64::from(CLOCK_SPEED);
+        let delay = milliseconds(index + 1) - milliseconds(self.position);
+        self.position = index + 1;
+        delay as u16
+    }
+
+    fn write_frame_count(&self) -> io::Result<()> {
+        let mut file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        let mut start = [0_u8; 64];
+        file.read_exact(&mut start)?;
+        let offset = match start.windows(4).position(|window| window == b"acTL") {
+            Some(offset) => offset,
+            None => return Err(io::Error::other("No animation control chunk")),
+        };
+
+        // num_frames follows the chunk type, then num_plays, then the CRC over all of it
+        let mut chunk = start[offset..offset + 12].to_vec();
+        chunk[4..8].copy_from_slice(&self.frames.to_be_bytes());
+        file.seek(SeekFrom::Start(offset as u64 + 4))?;
+        file.write_all(&chunk[4..8])?;
+        file.seek(SeekFrom::Start(offset as u64 + 12))?;
+        file.write_all(&crc32(&chunk).to_be_bytes())
+    }
+}
+
+impl VideoWriter for ApngWriter {
+    fn write_frame(&mut self, index: u64, frame: &[u8]) -> io::Result<()> {
+        let delay = self.next_delay(index);
+        if let Some(ref mut writer) = self.writer {
+            writer.set_frame_delay(delay, 1000).map_err(io::Error::other)?;
+            writer.write_image_data(frame).map_err(io::Error::other)?;
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.finish().map_err(io::Error::other)?;
+        }
+        if self.frames > 0 {
+            self.write_frame_count()?;
+        }
+        Ok(())
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 > 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
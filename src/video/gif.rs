@@ -0,0 +1,79 @@
+use gif::{Encoder, Frame, Repeat};
+use screen::Screen;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use video::{VideoWriter, CLOCK_SPEED};
+
+pub struct GifWriter {
+    encoder: Option<Encoder<BufWriter<File>>>,
+    frame_cycles: u64,
+    // frames of time the written frames cover
+    position: u64,
+}
+
+impl GifWriter {
+    pub fn create(path: &str, frame_cycles: u32) -> io::Result<Self> {
+        let writer = BufWriter::new(File::create(path)?);
+        let mut encoder =
+            Encoder::new(writer, Screen::WIDTH as u16, Screen::HEIGHT as u16, &[]).map_err(io::Error::other)?;
+        encoder.set_repeat(Repeat::Infinite).map_err(io::Error::other)?;
+        Ok(Self {
+            encoder: Some(encoder),
+            frame_cycles: u64::from(frame_cycles),
+            position: 0,
+        })
+    }
+
+    // GIF delays are in hundredths of a second, so each frame's is rounded from the time since the
+    // start to stop the error building up. It runs from where the last frame ended, which stretches
+    // it over any dropped in between.
+    fn next_delay(&mut self, index: u64) -> u16 {
+        let centiseconds =
+            |frames: u64| (frames * self.frame_cycles * 100 + u64::from(CLOCK_SPEED) / 2) / u64::from(CLOCK_SPEED);
+        let delay = centiseconds(index + 1) - centiseconds(self.position);
+        self.position = index + 1;
+        delay as u16
+    }
+}
+
+impl VideoWriter for GifWriter {
+    fn write_frame(&mut self, index: u64, frame: &[u8]) -> io::Result<()> {
+        // the DMG's few shades fit in a palette as they are, anything else gets quantized
+        let mut palette: Vec<[u8; 3]> = vec![];
+        let mut indices = Vec::with_capacity(frame.len() / 3);
+        for pixel in frame.chunks(3) {
+            let colour = [pixel[0], pixel[1], pixel[2]];
+            let index = match palette.iter().position(|&c| c == colour) {
+                Some(index) => index,
+                None => {
+                    palette.push(colour);
+                    palette.len() - 1
+                }
+            };
+            if palette.len() > 256 {
+                break;
+            }
+            indices.push(index as u8);
+        }
+        let (width, height) = (Screen::WIDTH as u16, Screen::HEIGHT as u16);
+        let mut gif_frame = if palette.len() > 256 {
+            Frame::from_rgb_speed(width, height, frame, 10)
+        } else {
+            Frame::from_palette_pixels(width, height, indices, palette.concat(), None)
+        };
+        gif_frame.delay = self.next_delay(index);
+
+        match self.encoder {
+            Some(ref mut encoder) => encoder.write_frame(&gif_frame).map_err(io::Error::other),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.encoder.take() {
+            // taking the writer back writes the trailer
+            Some(encoder) => encoder.into_inner()?.flush(),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+#[cfg(feature = "video")]
+mod apng;
+#[cfg(feature = "video")]
+mod gif;
+mod y4m;
+
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
+
+// How many frames can wait on the encoder before new ones are dropped
+const QUEUED_FRAMES: usize = 30;
+
+// T-cycles in a second and in a frame of the LCD, frames are timed off these rather than the wall
+// clock so the video lines up with the audio recorded next to it
+const CLOCK_SPEED: u32 = 4_194_304;
+const CYCLES_PER_FRAME: u32 = 70_224;
+
+#[derive(Copy, Clone)]
+pub enum VideoFormat {
+    Gif,
+    Apng,
+    Y4m,
+}
+
+impl VideoFormat {
+    // Going by the extension, .png counts as APNG
+    pub fn from_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension()?.to_str()? {
+            "gif" => Some(VideoFormat::Gif),
+            "png" | "apng" => Some(VideoFormat::Apng),
+            "y4m" => Some(VideoFormat::Y4m),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            VideoFormat::Gif => "gif",
+            VideoFormat::Apng => "png",
+            VideoFormat::Y4m => "y4m",
+        }
+    }
+}
+
+impl FromStr for VideoFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gif" => Ok(VideoFormat::Gif),
+            "apng" => Ok(VideoFormat::Apng),
+            "y4m" => Ok(VideoFormat::Y4m),
+            _ => Err(format!("Unknown video format {}, expected gif, apng or y4m", s)),
+        }
+    }
+}
+
+trait VideoWriter: Send {
+    // index counts every frame kept since the start, so a gap before it means frames were dropped
+    // and the time they'd have taken still has to be filled to keep the video in step with the audio
+    fn write_frame(&mut self, index: u64, frame: &[u8]) -> io::Result<()>;
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+// Takes every frame from the GPU, keeps one in every frame_skip + 1 and hands it to a thread to
+// encode so the CPU never waits on it
+pub struct VideoRecorder {
+    sender: Option<mpsc::SyncSender<(u64, Vec<u8>)>>,
+    encoder: Option<thread::JoinHandle<()>>,
+    frame_skip: u32,
+    frames_skipped: u32,
+    frames_kept: u64,
+    frames_dropped: u32,
+}
+
+impl VideoRecorder {
+    pub fn create(path: &str, format: VideoFormat, frame_skip: u32) -> io::Result<Self> {
+        let frame_cycles = CYCLES_PER_FRAME * (frame_skip + 1);
+        let mut writer: Box<dyn VideoWriter> = match format {
+            VideoFormat::Y4m => Box::new(y4m::Y4mWriter::create(path, frame_cycles)?),
+            #[cfg(feature = "video")]
+            VideoFormat::Gif => Box::new(gif::GifWriter::create(path, frame_cycles)?),
+            #[cfg(feature = "video")]
+            VideoFormat::Apng => Box::new(apng::ApngWriter::create(path, frame_cycles)?),
+            #[cfg(not(feature = "video"))]
+            VideoFormat::Gif | VideoFormat::Apng => {
+                return Err(io::Error::other(
+                    "GIF and APNG need rustyboy built with the video feature",
+                ))
+            }
+        };
+
+        let (sender, receiver) = mpsc::sync_channel::<(u64, Vec<u8>)>(QUEUED_FRAMES);
+        let path = path.to_owned();
+        let encoder = thread::spawn(move || {
+            for (index, frame) in receiver {
+                if let Err(e) = writer.write_frame(index, &frame) {
+                    println!("Failed to write video to {}: {}", path, e);
+                    return;
+                }
+            }
+            match writer.finish() {
+                Ok(_) => println!("Video saved to {}", path),
+                Err(e) => println!("Failed to write video to {}: {}", path, e),
+            }
+        });
+
+        Ok(Self {
+            sender: Some(sender),
+            encoder: Some(encoder),
+            frame_skip,
+            frames_skipped: frame_skip,
+            frames_kept: 0,
+            frames_dropped: 0,
+        })
+    }
+
+    pub fn frame(&mut self, frame: &[u8]) {
+        if self.frames_skipped < self.frame_skip {
+            self.frames_skipped += 1;
+            return;
+        }
+        self.frames_skipped = 0;
+        let index = self.frames_kept;
+        self.frames_kept += 1;
+        if let Some(ref sender) = self.sender {
+            if let Err(mpsc::TrySendError::Full(_)) = sender.try_send((index, frame.to_vec())) {
+                self.frames_dropped += 1;
+            }
+        }
+    }
+}
+
+impl Drop for VideoRecorder {
+    // waits for the encoder to get through what's queued
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(encoder) = self.encoder.take() {
+            let _ = encoder.join();
+        }
+        if self.frames_dropped > 0 {
+            println!(
+                "Dropped {} video frames the encoder couldn't keep up with, the frames around them were held for longer",
+                self.frames_dropped
+            );
+        }
+    }
+}
+
+// Numbered like the other recordings, skipping any number with the audio that goes with it taken too
+pub fn build_video_path(cart_path: &str, format: VideoFormat) -> String {
+    let mut number = 1;
+    loop {
+        let path = Path::new(cart_path).with_extension(format!("{}.{}", number, format.extension()));
+        if !path.exists() && !path.with_extension("wav").exists() {
+            return String::from(path.to_string_lossy());
+        }
+        number += 1;
+    }
+}
@@ -0,0 +1,67 @@
+use screen::Screen;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use video::{VideoWriter, CLOCK_SPEED};
+
+// Raw YUV 4:4:4, big but anything can read it and nothing's lost
+pub struct Y4mWriter {
+    writer: BufWriter<File>,
+    planes: Vec<u8>,
+    frames: u64,
+}
+
+impl Y4mWriter {
+    pub fn create(path: &str, frame_cycles: u32) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444",
+            Screen::WIDTH,
+            Screen::HEIGHT,
+            CLOCK_SPEED,
+            frame_cycles
+        )?;
+        Ok(Self {
+            writer,
+            planes: vec![],
+            frames: 0,
+        })
+    }
+
+    fn write_planes(&mut self) -> io::Result<()> {
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&self.planes)
+    }
+}
+
+impl VideoWriter for Y4mWriter {
+    // BT.601 in the studio range. The frame rate is fixed, so the last frame is repeated in place of
+    // any that were dropped, or this one if there's nothing before it.
+    fn write_frame(&mut self, index: u64, frame: &[u8]) -> io::Result<()> {
+        let mut repeats = index - self.frames;
+        if !self.planes.is_empty() {
+            for _ in 0..repeats {
+                self.write_planes()?;
+            }
+            repeats = 0;
+        }
+        self.frames = index + 1;
+
+        let pixels = frame.len() / 3;
+        self.planes.resize(pixels * 3, 0);
+        for (index, pixel) in frame.chunks(3).enumerate() {
+            let (r, g, b) = (i32::from(pixel[0]), i32::from(pixel[1]), i32::from(pixel[2]));
+            self.planes[index] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+            self.planes[pixels + index] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+            self.planes[pixels * 2 + index] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+        }
+        for _ in 0..=repeats {
+            self.write_planes()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,133 @@
+use screen::Screen;
+use sound::scope::{ChannelState, Scope, Visualization};
+
+// Draws the channels over the frame in four lanes, each with a line of state along the top and an
+// oscilloscope of the channel's level across the frame below it
+
+const LANE_HEIGHT: usize = Screen::HEIGHT as usize / 4;
+const TEXT_HEIGHT: usize = 7;
+const NAMES: [&str; 4] = ["SQ1", "SQ2", "WAV", "NOI"];
+const COLOURS: [[u8; 3]; 4] = [
+    [0xFF, 0x60, 0x60],
+    [0xFF, 0xC0, 0x40],
+    [0x60, 0xA0, 0xFF],
+    [0x80, 0xFF, 0x80],
+];
+const MUTED_COLOUR: [u8; 3] = [0x70, 0x70, 0x70];
+const TEXT_COLOUR: [u8; 3] = [0xFF, 0xFF, 0xFF];
+
+// 3x5 glyphs, a row per byte with the leftmost pixel in bit 2
+const GLYPH_CHARS: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const GLYPHS: [[u8; 5]; 36] = [
+    [7, 5, 5, 5, 7],
+    [2, 6, 2, 2, 7],
+    [7, 1, 7, 4, 7],
+    [7, 1, 7, 1, 7],
+    [5, 5, 7, 1, 1],
+    [7, 4, 7, 1, 7],
+    [7, 4, 7, 5, 7],
+    [7, 1, 1, 1, 1],
+    [7, 5, 7, 5, 7],
+    [7, 5, 7, 1, 7],
+    [2, 5, 7, 5, 5], // A
+    [6, 5, 6, 5, 6],
+    [3, 4, 4, 4, 3],
+    [6, 5, 5, 5, 6],
+    [7, 4, 6, 4, 7],
+    [7, 4, 6, 4, 4],
+    [3, 4, 5, 5, 3],
+    [5, 5, 7, 5, 5],
+    [7, 2, 2, 2, 7],
+    [1, 1, 1, 5, 2],
+    [5, 5, 6, 5, 5],
+    [4, 4, 4, 4, 7],
+    [5, 7, 7, 5, 5],
+    [6, 5, 5, 5, 5],
+    [2, 5, 5, 5, 2],
+    [6, 5, 6, 4, 4],
+    [2, 5, 5, 6, 3],
+    [6, 5, 6, 5, 5],
+    [3, 4, 2, 1, 6],
+    [7, 2, 2, 2, 2],
+    [5, 5, 5, 5, 7],
+    [5, 5, 5, 5, 2],
+    [5, 5, 7, 7, 5],
+    [5, 5, 2, 5, 5],
+    [5, 5, 2, 2, 2],
+    [7, 1, 2, 4, 7], // Z
+];
+
+pub fn draw(frame: &mut [u8], visualization: &Visualization) {
+    // darken the game so the lanes stand out
+    for value in frame.iter_mut() {
+        *value /= 4;
+    }
+
+    for (lane, (state, levels)) in visualization
+        .channels
+        .iter()
+        .zip(visualization.levels.iter())
+        .enumerate()
+    {
+        let top = lane * LANE_HEIGHT;
+        draw_text(frame, 1, top + 1, &describe(NAMES[lane], state));
+
+        let colour = if state.audible { COLOURS[lane] } else { MUTED_COLOUR };
+        let scope_height = LANE_HEIGHT - TEXT_HEIGHT - 1;
+        let mut last_y = None;
+        for (x, &level) in levels.iter().take(Scope::WIDTH).enumerate() {
+            let y = top + TEXT_HEIGHT + scope_height - usize::from(level) * scope_height / 15;
+            // join each point to the last so steps draw as lines
+            let (from, to) = match last_y {
+                Some(last_y) if last_y < y => (last_y, y),
+                Some(last_y) => (y, last_y),
+                None => (y, y),
+            };
+            for y in from..=to {
+                set_pixel(frame, x, y, colour);
+            }
+            last_y = Some(y);
+        }
+    }
+}
+
+// Like "SQ1 440HZ V15 D50 L12 MUTE"
+fn describe(name: &str, state: &ChannelState) -> String {
+    let mut text = format!("{} {}HZ V{}", name, state.frequency, state.volume);
+    if let Some(duty) = state.duty {
+        text += &format!(" D{}", duty);
+    }
+    if let Some(length) = state.length {
+        text += &format!(" L{}", length);
+    }
+    if !state.enabled {
+        text += " OFF";
+    } else if !state.audible {
+        text += " MUTE";
+    }
+    text
+}
+
+fn draw_text(frame: &mut [u8], x: usize, y: usize, text: &str) {
+    for (index, c) in text.chars().enumerate() {
+        let glyph = match GLYPH_CHARS.find(c) {
+            Some(glyph) => GLYPHS[glyph],
+            None => continue,
+        };
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (4 >> column) > 0 {
+                    set_pixel(frame, x + index * 4 + column, y + row, TEXT_COLOUR);
+                }
+            }
+        }
+    }
+}
+
+fn set_pixel(frame: &mut [u8], x: usize, y: usize, colour: [u8; 3]) {
+    if x >= Screen::WIDTH as usize || y >= Screen::HEIGHT as usize {
+        return;
+    }
+    let index = (y * Screen::WIDTH as usize + x) * 3;
+    frame[index..index + 3].copy_from_slice(&colour);
+}
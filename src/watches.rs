@@ -0,0 +1,66 @@
+use std::cell::RefCell;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+    Exec,
+}
+
+// Memory accesses to look out for, checked by the MMU on every access.
+// Hits are collected here for the debugger or a script to act on once the instruction is done.
+pub struct Watches {
+    watches: Vec<Watch>,
+    hits: RefCell<Vec<WatchHit>>,
+}
+
+pub struct Watch {
+    pub id: u32,
+    pub access: Access,
+    pub start: u16,
+    pub end: u16,
+}
+
+pub struct WatchHit {
+    pub id: u32,
+    pub access: Access,
+    pub addr: u16,
+    pub value: u8,
+}
+
+impl Access {
+    fn matches(self, access: Access) -> bool {
+        self == access || (self == Access::ReadWrite && (access == Access::Read || access == Access::Write))
+    }
+}
+
+impl Watches {
+    pub fn new() -> Self {
+        Self {
+            watches: vec![],
+            hits: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn set(&mut self, watches: Vec<Watch>) {
+        self.watches = watches;
+    }
+
+    pub fn check(&self, access: Access, addr: u16, value: u8) {
+        for watch in &self.watches {
+            if watch.access.matches(access) && watch.start <= addr && addr <= watch.end {
+                self.hits.borrow_mut().push(WatchHit {
+                    id: watch.id,
+                    access,
+                    addr,
+                    value,
+                });
+            }
+        }
+    }
+
+    pub fn take_hits(&self) -> Vec<WatchHit> {
+        self.hits.replace(vec![])
+    }
+}